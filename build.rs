@@ -2,4 +2,31 @@ extern crate lalrpop;
 
 fn main() {
     lalrpop::process_root().unwrap();
+    println!("cargo:rerun-if-changed=src/grammar.lalrpop");
+
+    // The runtime is linked into the compiler for the JIT,
+    // and embedded into it to link executables.
+    println!("cargo:rerun-if-changed=runtime/mill_runtime.c");
+    cc::Build::new()
+        .file("runtime/mill_runtime.c")
+        .warnings(true)
+        .compile("mill_runtime");
+
+    // The wrapper is compiled against the LLVM, which `llvm-sys` links.
+    println!("cargo:rerun-if-changed=llvm-wrapper/target.cpp");
+    let llvm_config =
+        std::env::var("DEP_LLVM_13_CONFIG_PATH").unwrap_or_else(|_| "llvm-config".to_string());
+    let output = std::process::Command::new(&llvm_config)
+        .arg("--cxxflags")
+        .output()
+        .unwrap_or_else(|err| panic!("Cannot run {}: {}", llvm_config, err));
+    let mut build = cc::Build::new();
+    for flag in String::from_utf8_lossy(&output.stdout).split_whitespace() {
+        build.flag(flag);
+    }
+    build
+        .cpp(true)
+        .file("llvm-wrapper/target.cpp")
+        .warnings(false)
+        .compile("mill_llvm_wrapper");
 }
@@ -0,0 +1,31 @@
+/*
+ * Queries of the targets of LLVM, which its C API does not offer.
+ * They are compiled with the flags of `llvm-config` by `build.rs`.
+ */
+
+#include "llvm-c/TargetMachine.h"
+#include "llvm/MC/MCSubtargetInfo.h"
+#include "llvm/Target/TargetMachine.h"
+
+using namespace llvm;
+
+static const MCSubtargetInfo *subtarget_of(LLVMTargetMachineRef machine) {
+    return reinterpret_cast<TargetMachine *>(machine)->getMCSubtargetInfo();
+}
+
+/* Returns 1 when the target of the machine has the cpu, otherwise 0. */
+extern "C" int mill_target_has_cpu(LLVMTargetMachineRef machine, const char *cpu) {
+    return subtarget_of(machine)->isCPUStringValid(cpu);
+}
+
+/*
+ * Returns 1 when the target of the machine has the feature, which is given without `+` or `-`.
+ * The subtarget does not list its features, so the feature is enabled on a copy without features.
+ * An unknown feature enables nothing, and LLVM warns that it ignores it.
+ */
+extern "C" int mill_target_has_feature(LLVMTargetMachineRef machine, const char *feature) {
+    MCSubtargetInfo subtarget(*subtarget_of(machine));
+    subtarget.setFeatureBits(FeatureBitset());
+
+    return subtarget.ApplyFeatureFlag(std::string("+") + feature).any();
+}
@@ -0,0 +1,262 @@
+/*
+ * The runtime of mill. Compiled programs call these functions,
+ * which are linked into executables and registered for the JIT.
+ */
+
+#include <errno.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/*
+ * Allocates `size` bytes on the heap. The memory is zeroed, so
+ * every field of a new struct starts with 0. Aborts when the memory
+ * is exhausted.
+ */
+void *mill_alloc(uint64_t size) {
+    void *ptr = calloc(1, size);
+
+    if (ptr == NULL && size != 0) {
+        fprintf(stderr, "mill: cannot allocate %llu bytes\n", (unsigned long long)size);
+        abort();
+    }
+
+    return ptr;
+}
+
+/*
+ * Parses the arguments of the command line into `values`, which has `count` elements
+ * for the `int` parameters of `main`. The first element of `argv` is the program.
+ * Exits when the number of arguments does not match or an argument is not an int.
+ */
+void mill_parse_args(int32_t argc, char **argv, int64_t *values, uint64_t count) {
+    uint64_t given = argc > 0 ? (uint64_t)argc - 1 : 0;
+
+    if (given != count) {
+        fprintf(stderr, "mill: function main expects %llu arguments, but %llu were given\n",
+                (unsigned long long)count, (unsigned long long)given);
+        exit(1);
+    }
+
+    for (uint64_t i = 0; i < count; i++) {
+        const char *arg = argv[i + 1];
+        char *end;
+
+        errno = 0;
+        long long value = strtoll(arg, &end, 10);
+        if (errno != 0 || end == arg || *end != '\0') {
+            fprintf(stderr, "mill: argument %s is not an int\n", arg);
+            exit(1);
+        }
+
+        values[i] = value;
+    }
+}
+
+/* Writes the bytes of a string to stdout. */
+void mill_print(const char *data, uint64_t len) {
+    fwrite(data, 1, len, stdout);
+}
+
+/* Returns 1 when both strings have the same bytes, otherwise 0. */
+int32_t mill_str_eq(const char *a, uint64_t a_len, const char *b, uint64_t b_len) {
+    return a_len == b_len && (a_len == 0 || memcmp(a, b, a_len) == 0);
+}
+
+/*
+ * Exits the program, because the index is not smaller than the length.
+ * The location is the `file:line:column` of the index, or NULL when it is unknown.
+ * Exiting flushes what the program has printed so far.
+ */
+void mill_index_out_of_bounds(int64_t index, uint64_t len, const char *location) {
+    fprintf(stderr, "mill: index %lld is out of bounds for length %llu", (long long)index,
+            (unsigned long long)len);
+    if (location != NULL) {
+        fprintf(stderr, " at %s", location);
+    }
+    fputc('\n', stderr);
+    exit(101);
+}
+
+/*
+ * Exits the program, because `head` or `tail` was applied to the empty list.
+ * The operation is the name of the operator.
+ */
+void mill_empty_list(const char *operation) {
+    fprintf(stderr, "mill: %s of the empty list\n", operation);
+    exit(101);
+}
+
+/*
+ * Mark-and-sweep collector, which is used with `--gc=marksweep`.
+ *
+ * Every struct is preceded by a header. Heap objects are linked into a list,
+ * which is swept after marking. Structs on the stack have a header as well,
+ * so they can be traced, but they are never freed.
+ *
+ * The roots are registered by the functions themselves: every function with
+ * struct variables pushes a frame with the addresses of these variables.
+ */
+
+/* Describes the layout of a struct. Generated by the compiler. */
+struct mill_type {
+    uint64_t size;
+    uint64_t pointers;
+    /* Offsets of the fields, which point to other structs. */
+    const uint64_t *offsets;
+};
+
+struct mill_header {
+    const struct mill_type *type;
+    struct mill_header *next;
+    /* The object is marked when this equals the epoch of the collection. */
+    uint64_t epoch;
+};
+
+struct mill_frame {
+    struct mill_frame *prev;
+    uint64_t count;
+    void **roots[];
+};
+
+#define MILL_GC_INITIAL_THRESHOLD (256 * 1024)
+
+static _Thread_local struct mill_frame *frames;
+static _Thread_local struct mill_header *objects;
+static _Thread_local uint64_t epoch = 1;
+static _Thread_local uint64_t allocated_bytes;
+static _Thread_local uint64_t live_bytes;
+static _Thread_local uint64_t threshold = MILL_GC_INITIAL_THRESHOLD;
+
+/* Work list of the marking phase. */
+static _Thread_local struct mill_header **pending;
+static _Thread_local uint64_t pending_len;
+static _Thread_local uint64_t pending_cap;
+
+static _Thread_local struct {
+    uint64_t collections;
+    uint64_t allocations;
+    uint64_t freed;
+} stats;
+
+static int stats_registered;
+
+static void print_stats(void) {
+    fprintf(stderr,
+            "mill gc: %llu collections, %llu allocations, %llu freed, %llu live bytes\n",
+            (unsigned long long)stats.collections, (unsigned long long)stats.allocations,
+            (unsigned long long)stats.freed, (unsigned long long)live_bytes);
+}
+
+static struct mill_header *header_of(void *object) {
+    return (struct mill_header *)object - 1;
+}
+
+static void push_pending(void *object) {
+    if (object == NULL) {
+        return;
+    }
+
+    struct mill_header *header = header_of(object);
+    if (header->epoch == epoch) {
+        return;
+    }
+    header->epoch = epoch;
+
+    if (pending_len == pending_cap) {
+        pending_cap = pending_cap == 0 ? 64 : pending_cap * 2;
+        pending = realloc(pending, pending_cap * sizeof(*pending));
+
+        if (pending == NULL) {
+            fprintf(stderr, "mill: cannot allocate the mark stack\n");
+            abort();
+        }
+    }
+
+    pending[pending_len++] = header;
+}
+
+static void mark(void) {
+    for (struct mill_frame *frame = frames; frame != NULL; frame = frame->prev) {
+        for (uint64_t i = 0; i < frame->count; i++) {
+            push_pending(*frame->roots[i]);
+        }
+    }
+
+    while (pending_len > 0) {
+        struct mill_header *header = pending[--pending_len];
+        char *object = (char *)(header + 1);
+
+        for (uint64_t i = 0; i < header->type->pointers; i++) {
+            push_pending(*(void **)(object + header->type->offsets[i]));
+        }
+    }
+}
+
+static void sweep(void) {
+    struct mill_header **link = &objects;
+    live_bytes = 0;
+
+    while (*link != NULL) {
+        struct mill_header *header = *link;
+
+        if (header->epoch == epoch) {
+            live_bytes += sizeof(struct mill_header) + header->type->size;
+            link = &header->next;
+        } else {
+            *link = header->next;
+            free(header);
+            stats.freed++;
+        }
+    }
+}
+
+static void collect(void) {
+    epoch++;
+    mark();
+    sweep();
+
+    stats.collections++;
+    allocated_bytes = 0;
+    if (threshold < live_bytes * 2) {
+        threshold = live_bytes * 2;
+    }
+}
+
+void mill_gc_push_frame(struct mill_frame *frame) {
+    frame->prev = frames;
+    frames = frame;
+}
+
+void mill_gc_pop_frame(struct mill_frame *frame) {
+    frames = frame->prev;
+}
+
+/* Allocates a zeroed struct, which is freed when it is not reachable anymore. */
+void *mill_gc_alloc(const struct mill_type *type) {
+    if (!stats_registered) {
+        stats_registered = 1;
+
+        if (getenv("MILL_GC_STATS") != NULL) {
+            atexit(print_stats);
+        }
+    }
+
+    uint64_t size = sizeof(struct mill_header) + type->size;
+
+    if (allocated_bytes + size > threshold) {
+        collect();
+    }
+
+    struct mill_header *header = mill_alloc(size);
+    header->type = type;
+    header->next = objects;
+    objects = header;
+
+    allocated_bytes += size;
+    live_bytes += size;
+    stats.allocations++;
+
+    return header + 1;
+}
@@ -1,5 +1,7 @@
 use crate::symbol_table::{FunctionSignature, SymbolTable};
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
@@ -10,6 +12,8 @@ pub struct Identifier {
     pos_r: usize,
     pub ty: Option<DataType>,
     field: Option<Box<Identifier>>,
+    /// Type arguments of a generic struct or function like `int` in `Pair<int>`.
+    pub type_args: Vec<DataType>,
 }
 
 impl Identifier {
@@ -20,9 +24,15 @@ impl Identifier {
             pos_r,
             ty,
             field: None,
+            type_args: Vec::new(),
         }
     }
 
+    pub fn update_type_args(mut self, type_args: Vec<DataType>) -> Self {
+        self.type_args = type_args;
+        self
+    }
+
     pub fn update_ty(mut self, ty: DataType) -> Self {
         self.ty = Some(ty);
         self
@@ -32,8 +42,18 @@ impl Identifier {
         &self.id
     }
 
+    /// Replaces the name of the variable, but keeps the accessed fields.
+    pub fn rename(&mut self, name: &str) {
+        self.id = name.to_string();
+    }
+
+    /// Offset of the identifier in the program.
+    pub fn get_pos(&self) -> usize {
+        self.pos_l
+    }
+
     pub fn update_field_access(mut self, field: Option<Identifier>) -> Self {
-        self.field = field.map(|x| Box::new(x));
+        self.field = field.map(Box::new);
         self
     }
 
@@ -44,11 +64,65 @@ impl Identifier {
     pub fn get_field(&self) -> &Option<Box<Identifier>> {
         &self.field
     }
+
+    /// Splits `p.a.b` into `p.a` and `b`. The path must access a field.
+    fn split_last_field(mut self) -> (Identifier, Identifier) {
+        let field = *self.field.take().expect("Path accesses a field");
+        if field.is_field_access() {
+            let (rest, last) = field.split_last_field();
+            self.field = Some(Box::new(rest));
+            (self, last)
+        } else {
+            (self, field)
+        }
+    }
+
+    /// Returns the name of the instance of a generic struct or function with
+    /// the type arguments like `Pair<int>`, which is unique in the program.
+    pub fn instantiate(&self, type_args: &[DataType]) -> Identifier {
+        let type_args = type_args
+            .iter()
+            .map(|ty| ty.to_string())
+            .collect::<Vec<_>>();
+
+        Identifier::new(
+            format!("{}<{}>", self.id, type_args.join(", ")),
+            self.pos_l,
+            self.pos_r,
+            None,
+        )
+    }
+
+    /// Returns the name of a function of the `impl` block of `owner`
+    /// like `Point::len`, which is unique in the program.
+    pub fn qualify(&self, owner: &Identifier) -> Identifier {
+        Identifier::new(
+            format!("{}::{}", owner.id, self.id),
+            self.pos_l,
+            self.pos_r,
+            None,
+        )
+    }
 }
 
 impl fmt::Display for Identifier {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.id)
+        write!(f, "{}", self.id)?;
+
+        if !self.type_args.is_empty() {
+            let type_args = self
+                .type_args
+                .iter()
+                .map(|ty| ty.to_string())
+                .collect::<Vec<_>>();
+            write!(f, "<{}>", type_args.join(", "))?;
+        }
+
+        if let Some(field) = &self.field {
+            write!(f, ".{}", field)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -62,23 +136,47 @@ impl Hash for Identifier {
 pub struct Program {
     pub functions: Vec<Func>,
     pub structs: Vec<Struct>,
+    pub enums: Vec<Enum>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Decl {
     Func(Func),
     Struct(Struct),
+    Enum(Enum),
+    Impl(Impl),
 }
 
 #[derive(Debug, Clone, Hash, PartialEq)]
 pub struct Struct {
     pub name: Identifier,
+    /// Type parameters like `T` in `struct Pair<T>`, which the fields can use.
+    pub type_pars: Vec<Identifier>,
     pub fields: Vec<Field>,
+    /// The functions of the `impl` blocks of the struct.
+    pub methods: Vec<Method>,
 }
 
 impl Struct {
-    pub fn new(name: Identifier, fields: Vec<Field>) -> Result<Self> {
-        Ok(Self { name, fields })
+    pub fn new(name: Identifier, type_pars: Vec<Identifier>, fields: Vec<Field>) -> Result<Self> {
+        Ok(Self {
+            name,
+            type_pars,
+            fields,
+            methods: Vec::new(),
+        })
+    }
+
+    /// Returns the function of the `impl` blocks with the name.
+    pub fn get_method(&self, name: &Identifier) -> Result<&Method> {
+        match self
+            .methods
+            .iter()
+            .find(|method| method.name.get_name() == name.get_name())
+        {
+            Some(method) => Ok(method),
+            None => bail!("Struct {} has no method {}", self.name, name),
+        }
     }
 
     /// Given the field name, return the index of
@@ -94,6 +192,70 @@ impl Struct {
     }
 }
 
+/// A function of an `impl` block. Methods have a `self` parameter and are called
+/// like `p.len()`, the other functions are called like `Point::origin()`.
+#[derive(Debug, Clone, Hash, PartialEq)]
+pub struct Method {
+    pub name: Identifier,
+    /// Name of the function in the program, which is qualified by the struct.
+    pub symbol: Identifier,
+    pub has_self: bool,
+}
+
+/// `impl Point { ... }`, which defines functions of the struct.
+#[derive(Debug, Clone)]
+pub struct Impl {
+    pub name: Identifier,
+    pub functions: Vec<Func>,
+}
+
+/// An enum like `enum Shape { Circle(int), Rect(int, int) }`. Its values are
+/// references to a variant and the values of its payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enum {
+    pub name: Identifier,
+    pub variants: Vec<Variant>,
+}
+
+impl Enum {
+    pub fn new(name: Identifier, variants: Vec<Variant>) -> Result<Self> {
+        let mut names = HashSet::new();
+        for variant in variants.iter() {
+            if !names.insert(variant.name.get_name()) {
+                bail!("Variant {}::{} is defined twice", name, variant.name);
+            }
+        }
+
+        Ok(Self { name, variants })
+    }
+
+    /// Returns the tag of the variant, which is its position in the enum, and the variant.
+    pub fn get_variant(&self, name: &Identifier) -> Result<(usize, &Variant)> {
+        match self
+            .variants
+            .iter()
+            .enumerate()
+            .find(|(_, variant)| variant.name.get_name() == name.get_name())
+        {
+            Some(variant) => Ok(variant),
+            None => bail!("Variant {}::{} is not defined", self.name, name),
+        }
+    }
+}
+
+/// A variant of an enum with the types of its payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: Identifier,
+    pub fields: Vec<DataType>,
+}
+
+impl Variant {
+    pub fn new(name: Identifier, fields: Vec<DataType>) -> Self {
+        Self { name, fields }
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq)]
 pub struct Field {
     name: Identifier,
@@ -106,17 +268,320 @@ impl Field {
     }
 
     pub fn get_name(&self) -> &String {
-        &self.name.get_name()
+        self.name.get_name()
+    }
+
+    pub fn get_ident(&self) -> &Identifier {
+        &self.name
     }
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+/// The types of values. `int` is a signed 64 bit integer, which is also called `i64`.
+#[derive(Debug, Clone, Eq)]
 pub enum DataType {
     Int,
+    I8,
+    I16,
+    I32,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Bool,
+    /// Immutable text, which is a pointer to the bytes and their length.
+    Str,
     Struct(Box<Identifier>),
+    /// A reference to a variant of the enum.
+    Enum(Box<Identifier>),
+    /// `[T; N]`, which holds its elements inline and is copied like the other values.
+    Array(Box<DataType>, usize),
+    /// `[T]`, an immutable cons list on the heap. The empty list `nil` is a null pointer.
+    List(Box<DataType>),
+    /// `fn(int) -> int`, a named function or a closure with the types of its
+    /// parameters and its return type. The return type is None when void.
+    Function(Vec<DataType>, Option<Box<DataType>>),
+}
+
+impl DataType {
+    /// Returns the type of the suffix of a literal like `u8` in `1u8`.
+    pub fn from_suffix(suffix: &str) -> Option<DataType> {
+        Some(match suffix {
+            "i8" => DataType::I8,
+            "i16" => DataType::I16,
+            "i32" => DataType::I32,
+            "i64" => DataType::Int,
+            "u8" => DataType::U8,
+            "u16" => DataType::U16,
+            "u32" => DataType::U32,
+            "u64" => DataType::U64,
+            "f32" => DataType::F32,
+            "f64" => DataType::F64,
+            _ => return None,
+        })
+    }
+
+    /// Returns the number of bits of the primitive types.
+    pub fn get_bits(&self) -> Option<u32> {
+        match self {
+            DataType::Bool => Some(1),
+            DataType::Str => None,
+            DataType::I8 | DataType::U8 => Some(8),
+            DataType::I16 | DataType::U16 => Some(16),
+            DataType::I32 | DataType::U32 | DataType::F32 => Some(32),
+            DataType::Int | DataType::U64 | DataType::F64 => Some(64),
+            DataType::Struct(_)
+            | DataType::Enum(_)
+            | DataType::Array(_, _)
+            | DataType::List(_)
+            | DataType::Function(_, _) => None,
+        }
+    }
+
+    /// Returns the smallest and the largest value of an integer type.
+    pub fn get_int_range(&self) -> Option<(i128, i128)> {
+        if !self.is_integer() {
+            return None;
+        }
+
+        let bits = self.get_bits().expect("Integers have a size");
+        Some(if self.is_signed() {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        } else {
+            (0, (1i128 << bits) - 1)
+        })
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            DataType::Int | DataType::I8 | DataType::I16 | DataType::I32
+        )
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.is_signed()
+            || matches!(
+                self,
+                DataType::U8 | DataType::U16 | DataType::U32 | DataType::U64
+            )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, DataType::F32 | DataType::F64)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    /// Returns true when values of the type contain pointers to the heap,
+    /// which are structs, enums, lists and the captured values of closures.
+    pub fn has_references(&self) -> bool {
+        match self {
+            DataType::Struct(_) | DataType::Enum(_) | DataType::List(_) => true,
+            DataType::Function(_, _) => true,
+            DataType::Array(element, _) => element.has_references(),
+            _ => false,
+        }
+    }
+
+    /// Replaces the named types, which refer to one of the enums, by `DataType::Enum`.
+    fn resolve_enums(&mut self, enums: &HashSet<String>) {
+        match self {
+            DataType::Struct(name) if enums.contains(name.get_name()) => {
+                *self = DataType::Enum(name.clone())
+            }
+            DataType::Array(element, _) | DataType::List(element) => element.resolve_enums(enums),
+            DataType::Function(pars, ret_ty) => {
+                for ty in pars.iter_mut().chain(ret_ty.iter_mut().map(|ty| &mut **ty)) {
+                    ty.resolve_enums(enums);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Structs are equal when they have the same name,
+/// regardless of where they were referenced.
+impl PartialEq for DataType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (DataType::Struct(a), DataType::Struct(b)) | (DataType::Enum(a), DataType::Enum(b)) => {
+                a.get_name() == b.get_name()
+            }
+            (DataType::Array(a, a_len), DataType::Array(b, b_len)) => a == b && a_len == b_len,
+            (DataType::List(a), DataType::List(b)) => a == b,
+            (DataType::Function(a, a_ret), DataType::Function(b, b_ret)) => {
+                a == b && a_ret == b_ret
+            }
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Hash for DataType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
+        match self {
+            DataType::Struct(name) | DataType::Enum(name) => name.get_name().hash(state),
+            DataType::Array(element, len) => {
+                element.hash(state);
+                len.hash(state);
+            }
+            DataType::List(element) => element.hash(state),
+            DataType::Function(pars, ret_ty) => {
+                pars.hash(state);
+                ret_ty.hash(state);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataType::Int => write!(f, "int"),
+            DataType::I8 => write!(f, "i8"),
+            DataType::I16 => write!(f, "i16"),
+            DataType::I32 => write!(f, "i32"),
+            DataType::U8 => write!(f, "u8"),
+            DataType::U16 => write!(f, "u16"),
+            DataType::U32 => write!(f, "u32"),
+            DataType::U64 => write!(f, "u64"),
+            DataType::F32 => write!(f, "f32"),
+            DataType::F64 => write!(f, "f64"),
+            DataType::Bool => write!(f, "bool"),
+            DataType::Str => write!(f, "str"),
+            DataType::Struct(name) | DataType::Enum(name) => write!(f, "{}", name),
+            DataType::Array(element, len) => write!(f, "[{}; {}]", element, len),
+            DataType::List(element) => write!(f, "[{}]", element),
+            DataType::Function(pars, ret_ty) => {
+                let pars = pars.iter().map(|ty| ty.to_string()).collect::<Vec<_>>();
+                write!(f, "fn({})", pars.join(", "))?;
+
+                match ret_ty {
+                    Some(ty) => write!(f, " -> {}", ty),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
 }
 
 impl Program {
+    /// Collects the declarations of the program. The parser cannot tell enums from structs,
+    /// so the types which name an enum are replaced by `DataType::Enum`.
+    pub fn new(decls: Vec<Decl>) -> Result<Self> {
+        let mut program = Program {
+            functions: Vec::new(),
+            structs: Vec::new(),
+            enums: Vec::new(),
+        };
+        let mut types = HashSet::new();
+
+        let mut impls = Vec::new();
+
+        for decl in decls {
+            let name = match &decl {
+                Decl::Func(_) | Decl::Impl(_) => None,
+                Decl::Struct(s) => Some(&s.name),
+                Decl::Enum(e) => Some(&e.name),
+            };
+            if let Some(name) = name {
+                if !types.insert(name.get_name().clone()) {
+                    bail!("Type {} is defined twice", name);
+                }
+            }
+
+            match decl {
+                Decl::Func(f) => program.functions.push(f),
+                Decl::Struct(s) => program.structs.push(s),
+                Decl::Enum(e) => program.enums.push(e),
+                Decl::Impl(i) => impls.push(i),
+            }
+        }
+
+        for i in impls {
+            program.add_impl(i)?;
+        }
+        for function in program.functions.iter() {
+            if let Some(par) = function.pars.iter().find(|par| par.ty.is_none()) {
+                bail!("Parameter {} of function {} needs a type", par, function.id);
+            }
+        }
+
+        let enums = program
+            .enums
+            .iter()
+            .map(|e| e.name.get_name().clone())
+            .collect::<HashSet<_>>();
+        let mut resolve = |ty: &mut DataType| ty.resolve_enums(&enums);
+
+        for function in program.functions.iter_mut() {
+            function.map_types(&mut resolve);
+        }
+        for field in program.structs.iter_mut().flat_map(|s| s.fields.iter_mut()) {
+            resolve(&mut field.ty);
+        }
+        for ty in program
+            .enums
+            .iter_mut()
+            .flat_map(|e| e.variants.iter_mut())
+            .flat_map(|variant| variant.fields.iter_mut())
+        {
+            resolve(ty);
+        }
+
+        Ok(program)
+    }
+
+    /// Adds the functions of the `impl` block to the program under their qualified names
+    /// and to the methods of the struct. A first parameter `self` without a type
+    /// gets the type of the struct.
+    fn add_impl(&mut self, i: Impl) -> Result<()> {
+        let s = match self
+            .structs
+            .iter_mut()
+            .find(|s| s.name.get_name() == i.name.get_name())
+        {
+            Some(s) => s,
+            None => bail!("Struct {} of the impl block is not defined", i.name),
+        };
+        if !s.type_pars.is_empty() {
+            bail!("The generic struct {} cannot have impl blocks", s.name);
+        }
+
+        for mut function in i.functions {
+            if s.get_method(&function.id).is_ok() {
+                bail!("Method {}::{} is defined twice", s.name, function.id);
+            }
+
+            let has_self = match function.pars.first_mut() {
+                Some(par) if par.get_name() == "self" && par.ty.is_none() => {
+                    par.ty = Some(DataType::Struct(Box::new(s.name.clone())));
+                    true
+                }
+                _ => false,
+            };
+
+            let symbol = function.id.qualify(&s.name);
+            s.methods.push(Method {
+                name: function.id.clone(),
+                symbol: symbol.clone(),
+                has_self,
+            });
+            function.id = symbol;
+            self.functions.push(function);
+        }
+
+        Ok(())
+    }
+
     /// Get all function names
     pub fn get_function_names(&self) -> Result<SymbolTable> {
         let mut set = SymbolTable::default();
@@ -133,10 +598,12 @@ impl Program {
 pub struct Func {
     /// Name of the function
     pub id: Identifier,
+    /// Type parameters like `T` in `fn first<T>(p: Pair<T>) -> T`
+    pub type_pars: Vec<Identifier>,
     /// Parameters of the function
     pub pars: Vec<Identifier>,
     /// The statements of the function
-    pub statements: Vec<Box<Statement>>,
+    pub statements: Vec<Statement>,
     /// Return type of the function. This is None when void.
     pub ret_ty: Option<DataType>,
 }
@@ -144,16 +611,26 @@ pub struct Func {
 impl Func {
     pub fn new(
         id: Identifier,
+        type_pars: Vec<Identifier>,
         pars: Vec<Identifier>,
-        statements: Vec<Box<Statement>>,
+        statements: Vec<Statement>,
         ret_ty: Option<DataType>,
     ) -> Result<Self> {
         let mut symbol_table = SymbolTable::default();
+        // Parameters like `self` can be assigned, too.
+        let is_defined = |symbol_table: &SymbolTable, id: &Identifier| {
+            symbol_table.lookup_symbol(id.get_name())
+                || pars.iter().any(|par| par.get_name() == id.get_name())
+        };
 
         for stmt in statements.iter() {
-            match &**stmt {
-                Statement::Assign(id, _) => {
-                    if !symbol_table.lookup_symbol(&id.get_name()) {
+            match stmt {
+                Statement::Assign(id, _) if !is_defined(&symbol_table, id) => {
+                    bail!("Symbol {} is not defined", id);
+                }
+                Statement::AssignIndex(place, _) => {
+                    let id = place.get_indexed_path().expect("Place is indexed");
+                    if !is_defined(&symbol_table, id) {
                         bail!("Symbol {} is not defined", id);
                     }
                 }
@@ -164,21 +641,71 @@ impl Func {
 
         Ok(Self {
             id,
+            type_pars,
+            pars,
+            statements,
+            ret_ty,
+        })
+    }
+
+    /// Creates an anonymous function, which is named `closure` at the position of `fn`.
+    /// Its parameters cannot be `self`, so all of them need a type. The assignments are
+    /// not checked, because they can assign the captured variables, which are only
+    /// known to the lowering. Every call starts with the captured values.
+    pub fn closure(
+        pos: usize,
+        pars: Vec<Identifier>,
+        statements: Vec<Statement>,
+        ret_ty: Option<DataType>,
+    ) -> Result<Self> {
+        if let Some(par) = pars.iter().find(|par| par.ty.is_none()) {
+            bail!("Parameter {} of a closure needs a type", par);
+        }
+
+        Ok(Self {
+            id: Identifier::new("closure".to_string(), pos, pos, None),
+            type_pars: Vec::new(),
             pars,
             statements,
             ret_ty,
         })
     }
 
+    /// Calls `f` with every type, which is written in the function.
+    pub fn map_types(&mut self, f: &mut impl FnMut(&mut DataType)) {
+        for ty in self.pars.iter_mut().filter_map(|par| par.ty.as_mut()) {
+            f(ty);
+        }
+        if let Some(ty) = self.ret_ty.as_mut() {
+            f(ty);
+        }
+        for stmt in self.statements.iter_mut() {
+            stmt.map_types(f);
+        }
+    }
+
     pub fn get_signature(&self) -> FunctionSignature {
         let arguments_ty = self
             .pars
             .iter()
             .map(|x| x.ty.clone().expect("Argument must have type"))
             .collect();
-        let ret_ty = self.ret_ty.clone();
+        FunctionSignature::new(arguments_ty, self.get_ret_ty())
+    }
 
-        FunctionSignature::new(arguments_ty, ret_ty)
+    /// Get the return type of the function.
+    /// When no return type was declared, but the function returns a value,
+    /// then the function returns an `int`.
+    pub fn get_ret_ty(&self) -> Option<DataType> {
+        if self.ret_ty.is_some() {
+            return self.ret_ty.clone();
+        }
+
+        if self.statements.iter().any(Statement::returns_value) {
+            Some(DataType::Int)
+        } else {
+            None
+        }
     }
 }
 
@@ -187,46 +714,502 @@ pub enum Statement {
     RetVoid,
     Ret(Box<Expr>),
     Assign(Identifier, Box<Expr>),
+    /// `a[i] = e`, where the place is an `Expr::Index` of a variable or a field.
+    AssignIndex(Box<Expr>, Box<Expr>),
     Definition(Identifier, Box<Expr>),
-    Conditional(Box<Expr>, Vec<Box<Statement>>),
+    Conditional(Box<Expr>, Vec<Statement>),
+    /// `if` with an `else` branch. An `else if` is an `else` branch,
+    /// which only contains the next conditional.
+    IfElse(Box<Expr>, Vec<Statement>, Vec<Statement>),
+    /// `match` with guards, which are checked in order.
+    Match(Vec<Guard>),
+    /// `while` with an optional label, which is checked before every iteration.
+    While(Option<String>, Box<Expr>, Vec<Statement>),
+    /// `loop` with an optional label, which only ends with `break` or `return`.
+    Loop(Option<String>, Vec<Statement>),
+    /// `for i in start..end` with an optional label. The end is excluded and
+    /// only evaluated once. The variable is defined anew in every iteration.
+    For(
+        Option<String>,
+        Identifier,
+        Box<Expr>,
+        Box<Expr>,
+        Vec<Statement>,
+    ),
+    /// `for x in a` with an optional label, which defines `x` with every element
+    /// of the array or string. The value is only evaluated once.
+    ForEach(Option<String>, Identifier, Box<Expr>, Vec<Statement>),
+    /// Leaves the innermost `match` or loop, or the loop with the label.
+    Break(Option<String>),
+    /// Starts the next iteration of the innermost loop or the loop with the label.
+    Continue(Option<String>),
+    /// Calls the function and ignores its value.
+    Call(Identifier, Vec<Expr>),
+    /// Evaluates a method call or an associated function call and ignores its value.
+    Expression(Box<Expr>),
+    /// `match` over the variants of an enum value, which takes exactly one arm.
+    MatchEnum(Box<Expr>, Vec<Arm>),
+}
+
+/// An arm of `match` over an enum like `Shape::Rect(w, h) -> { ... }`.
+#[derive(Debug, Clone)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// A variant of an enum, which binds its payload to the variables.
+    Variant(Box<Identifier>, Box<Identifier>, Vec<Identifier>),
+    /// `_`, which matches the variants without an arm.
+    Wildcard,
+}
+
+/// An arm of `match`. The arm is taken when its condition holds.
+/// `_` has no condition and is always taken.
+/// Without `break`, the arm falls through to the next guard.
+#[derive(Debug, Clone)]
+pub struct Guard {
+    pub condition: Option<Box<Expr>>,
+    pub statements: Vec<Statement>,
+}
+
+impl Guard {
+    /// Groups the parsed items of `match` into arms. Every statement
+    /// belongs to the guard before it.
+    pub fn group(first: Option<Box<Expr>>, items: Vec<MatchItem>) -> Vec<Guard> {
+        let mut guards = vec![Guard {
+            condition: first,
+            statements: Vec::new(),
+        }];
+
+        for item in items {
+            match item {
+                MatchItem::Guard(condition) => guards.push(Guard {
+                    condition,
+                    statements: Vec::new(),
+                }),
+                MatchItem::Statement(stmt) => guards
+                    .last_mut()
+                    .expect("match has a first guard")
+                    .statements
+                    .push(stmt),
+            }
+        }
+
+        guards
+    }
+}
+
+/// A guard or a statement in the body of `match`.
+#[derive(Debug, Clone)]
+pub enum MatchItem {
+    Guard(Option<Box<Expr>>),
+    Statement(Statement),
 }
 
 impl Statement {
-    pub fn get_inner(&self) -> Option<&Box<Expr>> {
-        return match self {
+    /// Creates the assignment to a variable, a field or an element of an array.
+    pub fn assign(place: Expr, value: Box<Expr>) -> Result<Self> {
+        match place {
+            Expr::Term(term) => match *term {
+                Term::Id(id) => Ok(Statement::Assign(id, value)),
+                _ => bail!("Cannot assign to a literal"),
+            },
+            place if place.get_indexed_path().is_some() => {
+                Ok(Statement::AssignIndex(Box::new(place), value))
+            }
+            _ => bail!("Only variables, fields and their elements can be assigned"),
+        }
+    }
+
+    pub fn get_inner(&self) -> Option<&Expr> {
+        match self {
             Statement::Ret(expr) => Some(expr),
             Statement::Assign(_, expr) => Some(expr),
+            Statement::AssignIndex(_, expr) => Some(expr),
             Statement::Definition(_, _) => None,
             Statement::RetVoid => None,
             Statement::Conditional(expr, _) => Some(expr),
-        };
+            Statement::IfElse(expr, _, _) => Some(expr),
+            Statement::Match(_) => None,
+            Statement::While(_, expr, _) => Some(expr),
+            Statement::Loop(_, _) => None,
+            Statement::For(_, _, _, _, _) => None,
+            Statement::ForEach(_, _, expr, _) => Some(expr),
+            Statement::Break(_) | Statement::Continue(_) => None,
+            Statement::Call(_, _) => None,
+            Statement::Expression(expr) => Some(expr),
+            Statement::MatchEnum(expr, _) => Some(expr),
+        }
+    }
+
+    /// Calls `f` with every type, which is written in the statement.
+    pub fn map_types(&mut self, f: &mut impl FnMut(&mut DataType)) {
+        match self {
+            Statement::RetVoid | Statement::Break(_) | Statement::Continue(_) => {}
+            Statement::Ret(expr) | Statement::Assign(_, expr) | Statement::Expression(expr) => {
+                expr.map_types(f)
+            }
+            Statement::AssignIndex(place, expr) => {
+                place.map_types(f);
+                expr.map_types(f);
+            }
+            Statement::Definition(id, expr) => {
+                if let Some(ty) = id.ty.as_mut() {
+                    f(ty);
+                }
+                expr.map_types(f);
+            }
+            Statement::Conditional(expr, statements)
+            | Statement::While(_, expr, statements)
+            | Statement::ForEach(_, _, expr, statements) => {
+                expr.map_types(f);
+                statements.iter_mut().for_each(|stmt| stmt.map_types(f));
+            }
+            Statement::IfElse(expr, then_statements, else_statements) => {
+                expr.map_types(f);
+                then_statements
+                    .iter_mut()
+                    .chain(else_statements.iter_mut())
+                    .for_each(|stmt| stmt.map_types(f));
+            }
+            Statement::Match(guards) => {
+                for guard in guards.iter_mut() {
+                    if let Some(condition) = guard.condition.as_mut() {
+                        condition.map_types(f);
+                    }
+                    guard
+                        .statements
+                        .iter_mut()
+                        .for_each(|stmt| stmt.map_types(f));
+                }
+            }
+            Statement::Loop(_, statements) => {
+                statements.iter_mut().for_each(|stmt| stmt.map_types(f))
+            }
+            Statement::For(_, _, start, end, statements) => {
+                start.map_types(f);
+                end.map_types(f);
+                statements.iter_mut().for_each(|stmt| stmt.map_types(f));
+            }
+            Statement::Call(_, arguments) => arguments.iter_mut().for_each(|x| x.map_types(f)),
+            Statement::MatchEnum(expr, arms) => {
+                expr.map_types(f);
+                arms.iter_mut()
+                    .flat_map(|arm| arm.statements.iter_mut())
+                    .for_each(|stmt| stmt.map_types(f));
+            }
+        }
+    }
+
+    /// Returns true when the statement or one of its nested statements
+    /// returns a value.
+    pub fn returns_value(&self) -> bool {
+        match self {
+            Statement::Ret(_) => true,
+            Statement::Conditional(_, statements)
+            | Statement::While(_, _, statements)
+            | Statement::Loop(_, statements)
+            | Statement::For(_, _, _, _, statements)
+            | Statement::ForEach(_, _, _, statements) => {
+                statements.iter().any(Statement::returns_value)
+            }
+            Statement::IfElse(_, then_statements, else_statements) => then_statements
+                .iter()
+                .chain(else_statements)
+                .any(Statement::returns_value),
+            Statement::Match(guards) => guards
+                .iter()
+                .flat_map(|guard| guard.statements.iter())
+                .any(Statement::returns_value),
+            Statement::MatchEnum(_, arms) => arms
+                .iter()
+                .flat_map(|arm| arm.statements.iter())
+                .any(Statement::returns_value),
+            _ => false,
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum Expr {
+    /// `new T`, which is allocated on the stack unless it escapes the function.
     Struct(Identifier),
-    Binary(Opcode, Box<Term>, Box<Term>),
+    /// `box T`, which is always allocated on the heap.
+    Boxed(Identifier),
+    Binary(Opcode, Box<Expr>, Box<Expr>),
+    /// `!e` or `-e`
+    Unary(Opcode, Box<Expr>),
     Term(Box<Term>),
-    Call(Identifier, Vec<Box<Expr>>),
+    Call(Identifier, Vec<Expr>),
+    /// `e[i]` with the offset of the bracket in the program
+    Index(Box<Expr>, Box<Expr>, usize),
+    /// `e as T`, which converts between the numeric types.
+    Cast(Box<Expr>, DataType),
+    /// `[a, b, c]`
+    Array(Vec<Expr>),
+    /// `[e; N]`, which repeats the value N times.
+    ArrayRepeat(Box<Expr>, usize),
+    /// `Shape::Rect(2, 3)`, which creates the variant of the enum with the payload.
+    /// It calls the function of the struct like `Point::origin()` when it does not name an enum.
+    Variant(Identifier, Identifier, Vec<Expr>),
+    /// `p.len()`, which calls the method of the struct of the variable or field.
+    MethodCall(Identifier, Identifier, Vec<Expr>),
+    /// `fn(x: int) -> int { return x + n; }`, an anonymous function,
+    /// which captures the values of the variables it uses.
+    Closure(Box<Func>),
+}
+
+impl Expr {
+    /// Calls `f` with every type, which is written in the expression.
+    pub fn map_types(&mut self, f: &mut impl FnMut(&mut DataType)) {
+        match self {
+            Expr::Struct(_) | Expr::Boxed(_) | Expr::Term(_) => {}
+            Expr::Binary(_, a, b) | Expr::Index(a, b, _) => {
+                a.map_types(f);
+                b.map_types(f);
+            }
+            Expr::Unary(_, a) | Expr::ArrayRepeat(a, _) => a.map_types(f),
+            Expr::Cast(a, ty) => {
+                a.map_types(f);
+                f(ty);
+            }
+            Expr::Call(_, arguments)
+            | Expr::Array(arguments)
+            | Expr::Variant(_, _, arguments)
+            | Expr::MethodCall(_, _, arguments) => {
+                arguments.iter_mut().for_each(|x| x.map_types(f))
+            }
+            Expr::Closure(function) => function.map_types(f),
+        }
+    }
+
+    /// Creates the call of the method, which is the last field of the path like `len` in `p.len()`.
+    pub fn method_call(path: Identifier, arguments: Vec<Expr>) -> Self {
+        let (receiver, method) = path.split_last_field();
+        Expr::MethodCall(receiver, method, arguments)
+    }
+
+    /// Checks the range of a literal, unless it is part of a bigger expression.
+    pub fn check_literal(e: Box<Expr>) -> Result<Box<Expr>> {
+        if let Expr::Term(term) = &*e {
+            term.check()?;
+        }
+
+        Ok(e)
+    }
+
+    /// Creates `-e`. The minus is folded into a number, so `-128i8` is in the range of `i8`.
+    pub fn negate(e: Box<Expr>) -> Result<Box<Expr>> {
+        let term = match &*e {
+            Expr::Term(term) => match &**term {
+                Term::Num(value) => {
+                    let term = Term::Num(-value);
+                    term.check()?;
+                    term
+                }
+                Term::Literal(Literal::Int(value, ty)) => {
+                    let literal = Literal::Int(-value, ty.clone());
+                    literal.check()?;
+                    Term::Literal(literal)
+                }
+                Term::Literal(Literal::Float(value, ty)) => {
+                    Term::Literal(Literal::Float(-value, ty.clone()))
+                }
+                _ => return Ok(Box::new(Expr::Unary(Opcode::Neg, e))),
+            },
+            _ => return Ok(Box::new(Expr::Unary(Opcode::Neg, e))),
+        };
+
+        Ok(Box::new(Expr::Term(Box::new(term))))
+    }
+
+    /// Returns the variable or field, which is indexed by `a[i][j]`.
+    pub fn get_indexed_path(&self) -> Option<&Identifier> {
+        match self {
+            Expr::Index(value, _, _) => match &**value {
+                Expr::Term(term) => match &**term {
+                    Term::Id(id) => Some(id),
+                    _ => None,
+                },
+                value => value.get_indexed_path(),
+            },
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Opcode {
     Mul,
     Div,
+    /// Remainder `%`
+    Rem,
     Add,
     Sub,
+    /// Negation `-e`
+    Neg,
+    /// Logical negation `!e`
     Not,
+    /// `head l`, the first element of a list
     Head,
+    /// `tail l`, the list without its first element
     Tail,
+    /// `x : l`, which prepends the element to the list
+    Cons,
+    /// Logical `&&`, which only evaluates the right operand when the left one holds
+    And,
+    /// Logical `||`, which only evaluates the right operand when the left one does not hold
     Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    /// Arithmetic shift to the right
+    Shr,
+    Lt,
+    Leq,
+    Gt,
     Geq,
     Cmp,
+    /// Not equal `!=`
+    Neq,
 }
 
 #[derive(Debug, Clone)]
 pub enum Term {
-    Num(i64),
+    /// Integer without a suffix, which takes the type of the other operand.
+    /// It is wider than `int` until its range has been checked.
+    Num(i128),
+    Literal(Literal),
+    Bool(bool),
+    /// String literal, whose escape sequences are already replaced.
+    Str(String),
     Id(Identifier),
+    /// The empty list, which takes the list type of its context.
+    Nil,
+}
+
+impl Term {
+    /// Checks that an integer without a suffix fits into `int`
+    /// and that a literal fits into its type.
+    pub fn check(&self) -> Result<()> {
+        match self {
+            Term::Num(value) => check_num(*value).map(|_| ()),
+            Term::Literal(literal) => literal.check(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parses an integer without a suffix, whose range is checked after
+/// a minus has been folded into it.
+pub fn parse_num(s: &str) -> Result<i128> {
+    s.parse()
+        .map_err(|_| anyhow!("Literal {} does not fit into {}", s, DataType::Int))
+}
+
+/// Returns the integer without a suffix, when it fits into `int`.
+pub fn check_num(value: i128) -> Result<i64> {
+    i64::try_from(value)
+        .map_err(|_| anyhow!("Literal {} does not fit into {}", value, DataType::Int))
+}
+
+/// Returns the length of an array, which has to fit into `int` like its indices.
+pub fn check_length(len: i128) -> Result<usize> {
+    Ok(check_num(len)? as usize)
+}
+
+/// Replaces the escape sequences of a string literal, which is given with its quotes.
+pub fn parse_string(s: &str) -> Result<String> {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s[1..s.len() - 1].chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some('\\') => result.push('\\'),
+            Some('"') => result.push('"'),
+            Some(c) => bail!("Unknown escape sequence \\{} in string", c),
+            None => bail!("String ends with a backslash"),
+        }
+    }
+
+    Ok(result)
+}
+
+/// A number with a suffix like `1u8` or `2.5f32`.
+/// Numbers with a fraction and without a suffix are `f64`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i128, DataType),
+    Float(f64, DataType),
+}
+
+impl Literal {
+    /// Parses the literal without its sign. The range of an integer is checked by
+    /// `check`, after a minus in front of it has been folded into it.
+    pub fn parse(s: &str) -> Result<Self> {
+        let end = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+        let (digits, suffix) = s.split_at(end);
+
+        let ty = match suffix {
+            "" => DataType::F64,
+            suffix => match DataType::from_suffix(suffix) {
+                Some(ty) => ty,
+                None => bail!("Literal {} has an unknown suffix", s),
+            },
+        };
+
+        if ty.is_float() {
+            return Ok(Literal::Float(digits.parse()?, ty));
+        }
+
+        match digits.parse() {
+            Ok(value) => Ok(Literal::Int(value, ty)),
+            Err(_) => bail!("Literal {} does not fit into {}", s, ty),
+        }
+    }
+
+    /// Fails when the integer does not fit into its type.
+    pub fn check(&self) -> Result<()> {
+        if let Literal::Int(value, ty) = self {
+            let (min, max) = ty.get_int_range().expect("Literal has an integer type");
+            if *value < min || *value > max {
+                bail!("Literal {} does not fit into {}", self, ty);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_ty(&self) -> &DataType {
+        match self {
+            Literal::Int(_, ty) | Literal::Float(_, ty) => ty,
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (value, ty) = match self {
+            Literal::Int(value, ty) => (value.to_string(), ty),
+            Literal::Float(value, ty) => (value.to_string(), ty),
+        };
+
+        match ty {
+            DataType::Int => write!(f, "{}i64", value),
+            ty => write!(f, "{}{}", value, ty),
+        }
+    }
 }
@@ -1,3 +1,6 @@
+#[allow(clippy::module_inception)]
 mod ast;
 
-pub use ast::*;
\ No newline at end of file
+pub mod monomorphization;
+
+pub use ast::*;
@@ -0,0 +1,469 @@
+use super::ast::*;
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Maps the type parameters of an instance to its type arguments.
+type Substitution = HashMap<String, DataType>;
+
+/// How deep the type arguments of an instance can be nested. Polymorphic recursion
+/// like calling `grow::<Pair<T>>` in `grow<T>` creates deeper instances forever.
+const MAX_DEPTH: usize = 64;
+
+/**
+ * Instantiates the generic structs and functions with the type arguments of their uses.
+ *
+ * Every instance is a copy, whose type parameters are replaced by the type arguments,
+ * and is named like `Pair<int>`. Generic declarations are not part of the result,
+ * so the lowering only sees concrete types.
+ */
+pub struct MonomorphizationPass {
+    generic_structs: HashMap<String, Struct>,
+    generic_functions: HashMap<String, Func>,
+    enums: HashSet<String>,
+    /// Names of the instances, which were already created.
+    instances: HashSet<String>,
+    /// How deep the type arguments of the struct instances are nested,
+    /// like 2 for `Pair<Pair<int>>`.
+    depths: HashMap<String, usize>,
+    structs: Vec<Struct>,
+    /// Functions, whose statements still have to be instantiated.
+    pending: VecDeque<(Func, Substitution)>,
+}
+
+impl MonomorphizationPass {
+    pub fn default() -> Self {
+        Self {
+            generic_structs: HashMap::new(),
+            generic_functions: HashMap::new(),
+            enums: HashSet::new(),
+            instances: HashSet::new(),
+            depths: HashMap::new(),
+            structs: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn run(&mut self, program: &Program) -> Result<Program> {
+        self.enums = program
+            .enums
+            .iter()
+            .map(|e| e.name.get_name().clone())
+            .collect();
+
+        for s in program.structs.iter() {
+            if !s.type_pars.is_empty() {
+                self.generic_structs
+                    .insert(s.name.get_name().clone(), s.clone());
+            }
+        }
+
+        for function in program.functions.iter() {
+            if function.type_pars.is_empty() {
+                self.pending
+                    .push_back((function.clone(), Substitution::new()));
+            } else {
+                self.generic_functions
+                    .insert(function.id.get_name().clone(), function.clone());
+            }
+        }
+
+        for s in program.structs.iter().filter(|s| s.type_pars.is_empty()) {
+            let mut s = s.clone();
+            let name = &s.name;
+            for field in s.fields.iter_mut() {
+                field.ty = self
+                    .resolve_type(&field.ty, &Substitution::new())
+                    .with_context(|| format!("Cannot instantiate struct {}", name))?;
+            }
+            self.structs.push(s);
+        }
+
+        // The functions are instantiated in order, so concrete programs are unchanged.
+        let mut functions = Vec::new();
+        while let Some((mut function, substitution)) = self.pending.pop_front() {
+            self.instantiate_body(&mut function, &substitution)
+                .with_context(|| format!("Cannot instantiate function {}", function.id))?;
+            functions.push(function);
+        }
+
+        Ok(Program {
+            functions,
+            structs: std::mem::take(&mut self.structs),
+            enums: program.enums.clone(),
+        })
+    }
+
+    /// Replaces the type parameters in the type and instantiates the generic structs.
+    fn resolve_type(&mut self, ty: &DataType, substitution: &Substitution) -> Result<DataType> {
+        match ty {
+            DataType::Struct(id) => {
+                if id.type_args.is_empty() {
+                    if let Some(ty) = substitution.get(id.get_name()) {
+                        return Ok(ty.clone());
+                    }
+                    // Enums in type arguments are not resolved by the parser.
+                    if self.enums.contains(id.get_name()) {
+                        return Ok(DataType::Enum(id.clone()));
+                    }
+                }
+
+                if self.generic_structs.contains_key(id.get_name()) {
+                    let instance = self.instantiate_struct(id, substitution)?;
+                    return Ok(DataType::Struct(Box::new(instance)));
+                }
+                if !id.type_args.is_empty() {
+                    bail!("Struct {} has no type parameters", id.get_name());
+                }
+
+                Ok(ty.clone())
+            }
+            DataType::Array(element, len) => Ok(DataType::Array(
+                Box::new(self.resolve_type(element, substitution)?),
+                *len,
+            )),
+            DataType::List(element) => Ok(DataType::List(Box::new(
+                self.resolve_type(element, substitution)?,
+            ))),
+            DataType::Function(pars, ret_ty) => Ok(DataType::Function(
+                pars.iter()
+                    .map(|ty| self.resolve_type(ty, substitution))
+                    .collect::<Result<_>>()?,
+                match ret_ty {
+                    Some(ty) => Some(Box::new(self.resolve_type(ty, substitution)?)),
+                    None => None,
+                },
+            )),
+            ty => Ok(ty.clone()),
+        }
+    }
+
+    /// Returns how deep the type is nested. The instances of generic structs
+    /// are as deep as their type arguments and one more.
+    fn get_depth(&self, ty: &DataType) -> usize {
+        match ty {
+            DataType::Struct(id) => self.depths.get(id.get_name()).copied().unwrap_or(0),
+            DataType::Array(element, _) | DataType::List(element) => self.get_depth(element) + 1,
+            DataType::Function(pars, ret_ty) => {
+                pars.iter()
+                    .chain(ret_ty.as_deref())
+                    .map(|ty| self.get_depth(ty))
+                    .max()
+                    .unwrap_or(0)
+                    + 1
+            }
+            _ => 0,
+        }
+    }
+
+    /// Fails when an instance of the generic struct or function is nested too deep.
+    fn check_depth(kind: &str, generic: &Identifier, depth: usize) -> Result<()> {
+        if depth > MAX_DEPTH {
+            bail!(
+                "{} {} is instantiated with type arguments, which are nested deeper than {} levels",
+                kind,
+                generic,
+                MAX_DEPTH
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Returns how deep an instance with the type arguments is nested.
+    fn get_instance_depth(&self, type_args: &[DataType]) -> usize {
+        type_args
+            .iter()
+            .map(|ty| self.get_depth(ty))
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    fn resolve_type_args(
+        &mut self,
+        id: &Identifier,
+        substitution: &Substitution,
+    ) -> Result<Vec<DataType>> {
+        id.type_args
+            .iter()
+            .map(|ty| self.resolve_type(ty, substitution))
+            .collect()
+    }
+
+    /// Creates the instance of the generic struct with the type arguments of `id`,
+    /// unless it already exists, and returns the name of the instance.
+    fn instantiate_struct(
+        &mut self,
+        id: &Identifier,
+        substitution: &Substitution,
+    ) -> Result<Identifier> {
+        let type_args = self.resolve_type_args(id, substitution)?;
+        let generic = self.generic_structs[id.get_name()].clone();
+        let inner = bind_type_args("Struct", &generic.name, &generic.type_pars, &type_args)?;
+
+        let instance = generic.name.instantiate(&type_args);
+        // The instance is registered before its fields, which can refer to it.
+        if self.instances.insert(instance.get_name().clone()) {
+            let depth = self.get_instance_depth(&type_args);
+            Self::check_depth("Struct", &generic.name, depth)?;
+            self.depths.insert(instance.get_name().clone(), depth);
+
+            let mut s = generic;
+            s.name = instance;
+            s.type_pars = Vec::new();
+            for field in s.fields.iter_mut() {
+                field.ty = self.resolve_type(&field.ty, &inner)?;
+            }
+            self.structs.push(s);
+        }
+
+        Ok(id.instantiate(&type_args))
+    }
+
+    /// Queues the instance of the generic function with the type arguments of `id`,
+    /// unless it already exists, and returns the name of the instance.
+    fn instantiate_function(
+        &mut self,
+        id: &Identifier,
+        substitution: &Substitution,
+    ) -> Result<Identifier> {
+        let generic = match self.generic_functions.get(id.get_name()) {
+            Some(generic) => generic.clone(),
+            None if id.type_args.is_empty() => return Ok(id.clone()),
+            None => bail!("Function {} has no type parameters", id.get_name()),
+        };
+
+        let type_args = self.resolve_type_args(id, substitution)?;
+        let inner = bind_type_args("Function", &generic.id, &generic.type_pars, &type_args)?;
+
+        let instance = generic.id.instantiate(&type_args);
+        if self.instances.insert(instance.get_name().clone()) {
+            Self::check_depth("Function", &generic.id, self.get_instance_depth(&type_args))?;
+
+            let mut function = generic;
+            function.id = instance;
+            function.type_pars = Vec::new();
+            self.pending.push_back((function, inner));
+        }
+
+        Ok(id.instantiate(&type_args))
+    }
+
+    /// Returns the struct of `new T` or `box T`.
+    fn instantiate_allocation(
+        &mut self,
+        id: &Identifier,
+        substitution: &Substitution,
+    ) -> Result<Identifier> {
+        match self.resolve_type(&DataType::Struct(Box::new(id.clone())), substitution)? {
+            DataType::Struct(name) => Ok(*name),
+            ty => bail!("Cannot allocate {}, because it is not a struct", ty),
+        }
+    }
+
+    fn instantiate_body(&mut self, function: &mut Func, substitution: &Substitution) -> Result<()> {
+        for par in function.pars.iter_mut() {
+            if let Some(ty) = par.ty.as_ref() {
+                par.ty = Some(self.resolve_type(ty, substitution)?);
+            }
+        }
+        if let Some(ty) = function.ret_ty.as_ref() {
+            function.ret_ty = Some(self.resolve_type(ty, substitution)?);
+        }
+
+        self.instantiate_statements(&mut function.statements, substitution)
+    }
+
+    fn instantiate_statements(
+        &mut self,
+        statements: &mut [Statement],
+        substitution: &Substitution,
+    ) -> Result<()> {
+        for stmt in statements.iter_mut() {
+            self.instantiate_statement(stmt, substitution)?;
+        }
+
+        Ok(())
+    }
+
+    fn instantiate_statement(
+        &mut self,
+        stmt: &mut Statement,
+        substitution: &Substitution,
+    ) -> Result<()> {
+        match stmt {
+            Statement::RetVoid | Statement::Break(_) | Statement::Continue(_) => {}
+            Statement::Ret(expr) | Statement::Assign(_, expr) | Statement::Expression(expr) => {
+                self.instantiate_expr(expr, substitution)?
+            }
+            Statement::AssignIndex(place, expr) => {
+                self.instantiate_expr(place, substitution)?;
+                self.instantiate_expr(expr, substitution)?;
+            }
+            Statement::Definition(id, expr) => {
+                if let Some(ty) = id.ty.as_ref() {
+                    id.ty = Some(self.resolve_type(ty, substitution)?);
+                }
+                self.instantiate_expr(expr, substitution)?;
+            }
+            Statement::Conditional(expr, statements)
+            | Statement::While(_, expr, statements)
+            | Statement::ForEach(_, _, expr, statements) => {
+                self.instantiate_expr(expr, substitution)?;
+                self.instantiate_statements(statements, substitution)?;
+            }
+            Statement::IfElse(expr, then_statements, else_statements) => {
+                self.instantiate_expr(expr, substitution)?;
+                self.instantiate_statements(then_statements, substitution)?;
+                self.instantiate_statements(else_statements, substitution)?;
+            }
+            Statement::Match(guards) => {
+                for guard in guards.iter_mut() {
+                    if let Some(condition) = guard.condition.as_mut() {
+                        self.instantiate_expr(condition, substitution)?;
+                    }
+                    self.instantiate_statements(&mut guard.statements, substitution)?;
+                }
+            }
+            Statement::Loop(_, statements) => {
+                self.instantiate_statements(statements, substitution)?
+            }
+            Statement::For(_, _, start, end, statements) => {
+                self.instantiate_expr(start, substitution)?;
+                self.instantiate_expr(end, substitution)?;
+                self.instantiate_statements(statements, substitution)?;
+            }
+            Statement::Call(id, arguments) => {
+                *id = self.instantiate_function(id, substitution)?;
+                for argument in arguments.iter_mut() {
+                    self.instantiate_expr(argument, substitution)?;
+                }
+            }
+            Statement::MatchEnum(expr, arms) => {
+                self.instantiate_expr(expr, substitution)?;
+                for arm in arms.iter_mut() {
+                    self.instantiate_statements(&mut arm.statements, substitution)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn instantiate_expr(&mut self, expr: &mut Expr, substitution: &Substitution) -> Result<()> {
+        match expr {
+            Expr::Term(_) => {}
+            Expr::Struct(id) | Expr::Boxed(id) => {
+                *id = self.instantiate_allocation(id, substitution)?;
+            }
+            Expr::Binary(_, a, b) | Expr::Index(a, b, _) => {
+                self.instantiate_expr(a, substitution)?;
+                self.instantiate_expr(b, substitution)?;
+            }
+            Expr::Unary(_, a) | Expr::ArrayRepeat(a, _) => {
+                self.instantiate_expr(a, substitution)?
+            }
+            Expr::Cast(a, ty) => {
+                self.instantiate_expr(a, substitution)?;
+                *ty = self.resolve_type(ty, substitution)?;
+            }
+            Expr::Call(id, arguments) => {
+                *id = self.instantiate_function(id, substitution)?;
+                for argument in arguments.iter_mut() {
+                    self.instantiate_expr(argument, substitution)?;
+                }
+            }
+            Expr::Array(arguments)
+            | Expr::Variant(_, _, arguments)
+            | Expr::MethodCall(_, _, arguments) => {
+                for argument in arguments.iter_mut() {
+                    self.instantiate_expr(argument, substitution)?;
+                }
+            }
+            // The closure can use the type parameters of the function, which creates it.
+            Expr::Closure(function) => self.instantiate_body(function, substitution)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps the type parameters of the struct or function to the type arguments.
+fn bind_type_args(
+    kind: &str,
+    name: &Identifier,
+    type_pars: &[Identifier],
+    type_args: &[DataType],
+) -> Result<Substitution> {
+    if type_pars.len() != type_args.len() {
+        bail!(
+            "{} {} expects {} type arguments, but {} were given",
+            kind,
+            name,
+            type_pars.len(),
+            type_args.len()
+        );
+    }
+
+    Ok(type_pars
+        .iter()
+        .map(|par| par.get_name().clone())
+        .zip(type_args.iter().cloned())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar;
+
+    #[test]
+    fn instantiate_each_use_once() {
+        let program = grammar::ProgramParser::new()
+            .parse(
+                "struct Pair<T> { a: T, b: T }
+                fn pair<T>(a: T, b: T) -> Pair<T> { let p = box Pair::<T>; p.a = a; p.b = b; return p; }
+                fn main() -> int { let p = pair::<int>(1, 2); let q = pair::<int>(3, 4); return p.a; }",
+            )
+            .unwrap();
+
+        // When
+        let result = MonomorphizationPass::default().run(&program).unwrap();
+
+        // Then
+        let functions = result
+            .functions
+            .iter()
+            .map(|f| f.id.get_name().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(functions, ["main", "pair<int>"]);
+        assert_eq!(result.structs.len(), 1);
+        assert_eq!(result.structs[0].name.get_name(), "Pair<int>");
+        assert_eq!(result.structs[0].fields[0].ty, DataType::Int);
+        assert_eq!(
+            result.functions[1].ret_ty,
+            Some(DataType::Struct(Box::new(Identifier::new(
+                "Pair<int>".to_string(),
+                0,
+                0,
+                None
+            ))))
+        );
+    }
+
+    #[test]
+    fn reject_wrong_number_of_type_args() {
+        let program = grammar::ProgramParser::new()
+            .parse(
+                "fn id<T>(x: T) -> T { return x; } fn main() -> int { return id::<int, int>(1); }",
+            )
+            .unwrap();
+
+        let error = MonomorphizationPass::default().run(&program).unwrap_err();
+
+        assert_eq!(
+            format!("{:#}", error),
+            "Cannot instantiate function main: Function id expects 1 type arguments, but 2 were given"
+        );
+    }
+}
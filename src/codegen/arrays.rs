@@ -0,0 +1,142 @@
+use super::runtime::INDEX_OUT_OF_BOUNDS;
+use super::Codegen;
+use crate::ast::DataType;
+use crate::c_str;
+use crate::source_map::SourceMap;
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::LLVMIntPredicate;
+
+/**
+ * Code for arrays and the bounds checks of indices.
+ *
+ * An array `[T; N]` holds its elements inline and is copied when it is assigned or passed.
+ * Elements are accessed through the pointer of the variable, so indexing does not copy it.
+ */
+impl Codegen {
+    pub fn set_unchecked_indexing(&mut self, unchecked_indexing: bool) {
+        self.unchecked_indexing = unchecked_indexing;
+    }
+
+    /// Sets the files of the program, which locate the indices that are out of bounds.
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = source_map;
+    }
+
+    /// Creates the array of the values, which is a constant when all values are constants.
+    pub(super) fn build_array(
+        &self,
+        element_ty: LLVMTypeRef,
+        values: &mut [LLVMValueRef],
+    ) -> LLVMValueRef {
+        unsafe {
+            if values.iter().all(|value| LLVMIsConstant(*value) != 0) {
+                return LLVMConstArray(element_ty, values.as_mut_ptr(), values.len() as u32);
+            }
+
+            let mut array = LLVMGetUndef(LLVMArrayType(element_ty, values.len() as u32));
+            for (i, value) in values.iter().enumerate() {
+                array = LLVMBuildInsertValue(self.builder, array, *value, i as u32, c_str!(""));
+            }
+
+            array
+        }
+    }
+
+    /// Converts the index, which is an integer of type `index_ty`, into an `i64`.
+    pub(super) fn build_index_cast(
+        &self,
+        index: LLVMValueRef,
+        index_ty: &DataType,
+    ) -> LLVMValueRef {
+        unsafe {
+            LLVMBuildIntCast2(
+                self.builder,
+                index,
+                LLVMInt64TypeInContext(self.context),
+                index_ty.is_signed() as LLVMBool,
+                c_str!(""),
+            )
+        }
+    }
+
+    /// Returns a pointer to the element of the array with `len` elements, which `ptr` points to.
+    pub(super) fn build_element_ptr(
+        &self,
+        ptr: LLVMValueRef,
+        index: LLVMValueRef,
+        len: usize,
+        pos: usize,
+    ) -> LLVMValueRef {
+        unsafe {
+            let int_ty = LLVMInt64TypeInContext(self.context);
+            self.build_bounds_check(index, LLVMConstInt(int_ty, len as u64, 0), pos);
+
+            let mut indices = [LLVMConstInt(int_ty, 0, 0), index];
+            LLVMBuildGEP(
+                self.builder,
+                ptr,
+                indices.as_mut_ptr(),
+                indices.len() as u32,
+                c_str!(""),
+            )
+        }
+    }
+
+    /// Continues in a new block when the index is in bounds. A negative index is compared
+    /// as unsigned number, so it is out of bounds as well. The failed check reports the
+    /// position of the index. Nothing is checked with `--unchecked-indexing`.
+    pub(super) fn build_bounds_check(&self, index: LLVMValueRef, len: LLVMValueRef, pos: usize) {
+        if self.unchecked_indexing {
+            return;
+        }
+
+        unsafe {
+            let function = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+            let ok = LLVMAppendBasicBlockInContext(self.context, function, c_str!("bounds.ok"));
+            let failed =
+                LLVMAppendBasicBlockInContext(self.context, function, c_str!("bounds.failed"));
+
+            let in_bounds = LLVMBuildICmp(
+                self.builder,
+                LLVMIntPredicate::LLVMIntULT,
+                index,
+                len,
+                c_str!(""),
+            );
+            LLVMBuildCondBr(self.builder, in_bounds, ok, failed);
+
+            LLVMPositionBuilderAtEnd(self.builder, failed);
+            let int_ty = LLVMInt64TypeInContext(self.context);
+            let abort = self.get_runtime_function(
+                INDEX_OUT_OF_BOUNDS,
+                None,
+                &[int_ty, int_ty, self.get_ptr_type()],
+            );
+            let mut args = [index, len, self.build_location(pos)];
+            LLVMBuildCall(
+                self.builder,
+                abort,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+            LLVMBuildUnreachable(self.builder);
+
+            LLVMPositionBuilderAtEnd(self.builder, ok);
+        }
+    }
+
+    /// Returns `file:line:column` of the offset as C string, which is null
+    /// when the source map does not know the offset.
+    fn build_location(&self, pos: usize) -> LLVMValueRef {
+        let location = match self.source_map.lookup(pos) {
+            Some(location) => location,
+            None => return unsafe { LLVMConstNull(self.get_ptr_type()) },
+        };
+        let file = &self.source_map.get_files()[location.file];
+        let text = format!("{}:{}:{}", file.name, location.line, location.column);
+
+        self.build_c_string("location", &text)
+    }
+}
@@ -0,0 +1,260 @@
+use super::gc::collect_pointer_offsets;
+use super::Codegen;
+use crate::ast::{DataType, Identifier};
+use crate::c_str;
+use crate::symbol_table::FunctionSignature;
+use anyhow::{Context, Result};
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::target::*;
+use llvm_sys::LLVMLinkage;
+
+/// Type of the function values, which is a pointer to the code and a pointer to the environment.
+const CLOSURE: &str = "mill.closure";
+
+/**
+ * Code for function values and closures.
+ *
+ * A closure is lifted into a function, whose first parameter is its environment on the heap.
+ * The environment holds the values of the captured variables, which are copied when the
+ * closure is created. Named functions have no environment, so their values point to a
+ * trampoline, which ignores it.
+ */
+impl Codegen {
+    pub(super) fn get_closure_type(&self) -> LLVMTypeRef {
+        unsafe {
+            let ty = LLVMGetTypeByName2(self.context, c_str!(CLOSURE));
+            if !ty.is_null() {
+                return ty;
+            }
+
+            let ty = LLVMStructCreateNamed(self.context, c_str!(CLOSURE));
+            let mut fields_ty = [self.get_ptr_type(), self.get_ptr_type()];
+            LLVMStructSetBody(ty, fields_ty.as_mut_ptr(), fields_ty.len() as u32, 0);
+
+            ty
+        }
+    }
+
+    /// Returns the type of the code of a function value with the signature,
+    /// which takes the environment first.
+    fn get_code_type(&self, signature: &FunctionSignature) -> Result<LLVMTypeRef> {
+        let mut args_ty = vec![self.get_ptr_type()];
+        for ty in signature.get_args_ty() {
+            args_ty.push(self.get_llvm_type(ty)?);
+        }
+
+        unsafe {
+            let ret_ty = match signature.get_ret_ty() {
+                Some(ty) => self.get_llvm_type(ty)?,
+                None => LLVMVoidTypeInContext(self.context),
+            };
+
+            Ok(LLVMFunctionType(
+                ret_ty,
+                args_ty.as_mut_ptr(),
+                args_ty.len() as u32,
+                0,
+            ))
+        }
+    }
+
+    /// Returns the struct type of an environment with the captured values.
+    fn get_env_type(&self, captures: &[DataType]) -> Result<LLVMTypeRef> {
+        let mut fields_ty = captures
+            .iter()
+            .map(|ty| self.get_llvm_type(ty))
+            .collect::<Result<Vec<_>>>()?;
+
+        unsafe {
+            Ok(LLVMStructTypeInContext(
+                self.context,
+                fields_ty.as_mut_ptr(),
+                fields_ty.len() as u32,
+                0,
+            ))
+        }
+    }
+
+    unsafe fn build_closure_value(&self, code: LLVMValueRef, env: LLVMValueRef) -> LLVMValueRef {
+        let value = LLVMGetUndef(self.get_closure_type());
+        let code = LLVMBuildBitCast(self.builder, code, self.get_ptr_type(), c_str!(""));
+        let value = LLVMBuildInsertValue(self.builder, value, code, 0, c_str!(""));
+
+        LLVMBuildInsertValue(self.builder, value, env, 1, c_str!(""))
+    }
+
+    /// Allocates the environment of the lifted function and returns its closure.
+    /// The captured values are loaded from their variables after the allocation,
+    /// because the collector would not see them while it allocates.
+    pub(super) fn build_closure(
+        &mut self,
+        lifted: &Identifier,
+        captures: &[(LLVMValueRef, DataType)],
+    ) -> Result<LLVMValueRef> {
+        let code = self
+            .function_table
+            .get(lifted.get_name())
+            .with_context(|| format!("Closure {} was not declared", lifted))?
+            .1;
+        let types = captures
+            .iter()
+            .map(|(_, ty)| ty.clone())
+            .collect::<Vec<_>>();
+        let env_ty = self.get_env_type(&types)?;
+
+        unsafe {
+            let env = if captures.is_empty() {
+                LLVMConstNull(self.get_ptr_type())
+            } else {
+                let env = if self.uses_gc() {
+                    let target_data = LLVMGetModuleDataLayout(self.module);
+                    let mut offsets = Vec::new();
+                    for (i, ty) in types.iter().enumerate() {
+                        collect_pointer_offsets(
+                            target_data,
+                            ty,
+                            LLVMStructGetTypeAtIndex(env_ty, i as u32),
+                            LLVMOffsetOfElement(target_data, env_ty, i as u32),
+                            &mut offsets,
+                        );
+                    }
+
+                    let global_name = format!("mill.type.{}.env", lifted.get_name());
+                    let descriptor = self.build_type_descriptor(&global_name, env_ty, offsets);
+                    self.build_gc_alloc_object(descriptor, env_ty, "env")
+                } else {
+                    self.build_heap_alloc(env_ty, "env")
+                };
+
+                for (i, (ptr, _)) in captures.iter().enumerate() {
+                    let value = LLVMBuildLoad(self.builder, *ptr, c_str!(""));
+                    let field = LLVMBuildStructGEP(self.builder, env, i as u32, c_str!(""));
+                    LLVMBuildStore(self.builder, value, field);
+                }
+
+                LLVMBuildBitCast(self.builder, env, self.get_ptr_type(), c_str!(""))
+            };
+
+            self.closure_envs.insert(lifted.get_name().clone(), types);
+
+            Ok(self.build_closure_value(code, env))
+        }
+    }
+
+    /// Loads the captured values of the lifted function from its environment.
+    pub(super) fn load_captures(
+        &self,
+        lifted: &Identifier,
+        env: LLVMValueRef,
+    ) -> Result<Vec<(DataType, LLVMValueRef)>> {
+        let types = self
+            .closure_envs
+            .get(lifted.get_name())
+            .with_context(|| format!("Closure {} is never created", lifted))?;
+        let env_ty = self.get_env_type(types)?;
+
+        unsafe {
+            let env =
+                LLVMBuildBitCast(self.builder, env, LLVMPointerType(env_ty, 0), c_str!("env"));
+
+            Ok(types
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| {
+                    let field = LLVMBuildStructGEP(self.builder, env, i as u32, c_str!(""));
+                    (ty.clone(), LLVMBuildLoad(self.builder, field, c_str!("")))
+                })
+                .collect())
+        }
+    }
+
+    /// Returns the value of the named function, which points to its trampoline.
+    /// The trampoline is created when the function is used as a value first.
+    pub(super) fn build_function_value(&self, id: &Identifier) -> Result<LLVMValueRef> {
+        let (signature, function) = self
+            .function_table
+            .get(id.get_name())
+            .with_context(|| format!("Function {} is not defined", id))?;
+
+        unsafe {
+            let name = format!("{}.closure", id.get_name());
+            let mut trampoline = LLVMGetNamedFunction(self.module, c_str!(name));
+            if trampoline.is_null() {
+                trampoline = self.build_trampoline(&name, signature, *function)?;
+            }
+
+            Ok(self.build_closure_value(trampoline, LLVMConstNull(self.get_ptr_type())))
+        }
+    }
+
+    /// Creates the function, which calls `function` without the environment.
+    unsafe fn build_trampoline(
+        &self,
+        name: &str,
+        signature: &FunctionSignature,
+        function: LLVMValueRef,
+    ) -> Result<LLVMValueRef> {
+        let trampoline = LLVMAddFunction(self.module, c_str!(name), self.get_code_type(signature)?);
+        LLVMSetLinkage(trampoline, LLVMLinkage::LLVMPrivateLinkage);
+
+        // The builder continues in the current function afterwards. The trampoline
+        // has no debug information, so it must not get the current location.
+        let block = LLVMGetInsertBlock(self.builder);
+        let location = LLVMGetCurrentDebugLocation2(self.builder);
+        LLVMSetCurrentDebugLocation2(self.builder, std::ptr::null_mut());
+
+        let entry = LLVMAppendBasicBlockInContext(self.context, trampoline, c_str!("entry"));
+        LLVMPositionBuilderAtEnd(self.builder, entry);
+
+        let mut args = (1..LLVMCountParams(trampoline))
+            .map(|i| LLVMGetParam(trampoline, i))
+            .collect::<Vec<_>>();
+        let value = LLVMBuildCall(
+            self.builder,
+            function,
+            args.as_mut_ptr(),
+            args.len() as u32,
+            c_str!(""),
+        );
+        match signature.get_ret_ty() {
+            Some(_) => LLVMBuildRet(self.builder, value),
+            None => LLVMBuildRetVoid(self.builder),
+        };
+
+        LLVMPositionBuilderAtEnd(self.builder, block);
+        LLVMSetCurrentDebugLocation2(self.builder, location);
+
+        Ok(trampoline)
+    }
+
+    /// Calls the function value, which is loaded from `slot`, with its environment
+    /// and the arguments.
+    pub(super) fn build_closure_call(
+        &self,
+        slot: LLVMValueRef,
+        signature: &FunctionSignature,
+        arguments: &[LLVMValueRef],
+        name: &str,
+    ) -> Result<LLVMValueRef> {
+        let code_ty = self.get_code_type(signature)?;
+
+        unsafe {
+            let value = LLVMBuildLoad(self.builder, slot, c_str!(""));
+            let code = LLVMBuildExtractValue(self.builder, value, 0, c_str!(""));
+            let code =
+                LLVMBuildBitCast(self.builder, code, LLVMPointerType(code_ty, 0), c_str!(""));
+
+            let mut args = vec![LLVMBuildExtractValue(self.builder, value, 1, c_str!("env"))];
+            args.extend_from_slice(arguments);
+
+            Ok(LLVMBuildCall(
+                self.builder,
+                code,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(name),
+            ))
+        }
+    }
+}
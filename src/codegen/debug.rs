@@ -0,0 +1,791 @@
+use super::Codegen;
+use crate::ast::{DataType, Enum, Identifier, Struct};
+use crate::lir::tree::LoweredFunction;
+use crate::source_map::SourceMap;
+use anyhow::{bail, Context, Result};
+use llvm_sys::core::*;
+use llvm_sys::debuginfo::*;
+use llvm_sys::prelude::*;
+use llvm_sys::target::*;
+use llvm_sys::LLVMModuleFlagBehavior;
+use std::collections::HashMap;
+
+const PRODUCER: &str = "mill";
+
+/// `DW_ATE_boolean`
+const ENCODING_BOOLEAN: LLVMDWARFTypeEncoding = 0x02;
+/// `DW_ATE_float`
+const ENCODING_FLOAT: LLVMDWARFTypeEncoding = 0x04;
+/// `DW_ATE_signed`
+const ENCODING_SIGNED: LLVMDWARFTypeEncoding = 0x05;
+/// `DW_ATE_unsigned`
+const ENCODING_UNSIGNED: LLVMDWARFTypeEncoding = 0x08;
+/// `DW_TAG_structure_type`
+const TAG_STRUCTURE_TYPE: u32 = 0x13;
+
+/**
+ * The DWARF debug information of the module.
+ */
+pub struct DebugInfo {
+    builder: LLVMDIBuilderRef,
+    source_map: SourceMap,
+    /// The files of the source map at the same position.
+    files: Vec<LLVMMetadataRef>,
+    compile_unit: LLVMMetadataRef,
+    is_optimized: bool,
+    struct_types: HashMap<String, LLVMMetadataRef>,
+    subprograms: HashMap<String, LLVMMetadataRef>,
+    /// The subprogram of the function which is translated.
+    scope: Option<LLVMMetadataRef>,
+}
+
+impl DebugInfo {
+    /// Returns the file, line and column of the offset.
+    fn locate(&self, offset: usize) -> (LLVMMetadataRef, u32, u32) {
+        match self.source_map.lookup(offset) {
+            Some(location) => (self.files[location.file], location.line, location.column),
+            None => (self.files[0], 0, 0),
+        }
+    }
+
+    fn get_scope(&self) -> Result<LLVMMetadataRef> {
+        self.scope.context("No function is translated")
+    }
+}
+
+impl Drop for DebugInfo {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMDisposeDIBuilder(self.builder);
+        }
+    }
+}
+
+impl Codegen {
+    /// Emits debug information for the translated functions, which maps
+    /// the code to the files of the source map.
+    pub fn enable_debug_info(&mut self, source_map: SourceMap, is_optimized: bool) -> Result<()> {
+        if source_map.get_files().is_empty() {
+            bail!("Debug information requires at least one file");
+        }
+
+        unsafe {
+            let builder = LLVMCreateDIBuilder(self.module);
+
+            let files: Vec<_> = source_map
+                .get_files()
+                .iter()
+                .map(|file| {
+                    LLVMDIBuilderCreateFile(
+                        builder,
+                        file.name.as_ptr() as *const i8,
+                        file.name.len(),
+                        file.directory.as_ptr() as *const i8,
+                        file.directory.len(),
+                    )
+                })
+                .collect();
+
+            let compile_unit = LLVMDIBuilderCreateCompileUnit(
+                builder,
+                LLVMDWARFSourceLanguage::LLVMDWARFSourceLanguageC,
+                files[0],
+                PRODUCER.as_ptr() as *const i8,
+                PRODUCER.len(),
+                is_optimized as LLVMBool,
+                std::ptr::null(),
+                0,
+                0,
+                std::ptr::null(),
+                0,
+                LLVMDWARFEmissionKind::LLVMDWARFEmissionKindFull,
+                0,
+                0,
+                0,
+                std::ptr::null(),
+                0,
+                std::ptr::null(),
+                0,
+            );
+
+            self.add_module_flag("Dwarf Version", 4);
+            self.add_module_flag("Debug Info Version", LLVMDebugMetadataVersion());
+
+            self.debug_info = Some(DebugInfo {
+                builder,
+                source_map,
+                files,
+                compile_unit,
+                is_optimized,
+                struct_types: HashMap::default(),
+                subprograms: HashMap::default(),
+                scope: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn add_module_flag(&self, key: &str, value: u32) {
+        unsafe {
+            let value = LLVMConstInt(LLVMInt32TypeInContext(self.context), value as u64, 0);
+            LLVMAddModuleFlag(
+                self.module,
+                LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning,
+                key.as_ptr() as *const i8,
+                key.len(),
+                LLVMValueAsMetadata(value),
+            );
+        }
+    }
+
+    /// Creates the types of the structs. Every struct is declared first,
+    /// because fields can refer to structs which are defined later.
+    pub(super) fn declare_debug_structs(&mut self, structs: &[Struct]) -> Result<()> {
+        let debug_info = match &mut self.debug_info {
+            Some(debug_info) => debug_info,
+            None => return Ok(()),
+        };
+
+        unsafe {
+            for s in structs {
+                let name = s.name.get_name();
+                let (file, line, _) = debug_info.locate(s.name.get_pos());
+
+                let ty = LLVMDIBuilderCreateReplaceableCompositeType(
+                    debug_info.builder,
+                    TAG_STRUCTURE_TYPE,
+                    name.as_ptr() as *const i8,
+                    name.len(),
+                    debug_info.compile_unit,
+                    file,
+                    line,
+                    0,
+                    0,
+                    0,
+                    LLVMDIFlagFwdDecl,
+                    std::ptr::null(),
+                    0,
+                );
+                debug_info.struct_types.insert(name.clone(), ty);
+            }
+        }
+
+        for s in structs {
+            self.declare_debug_struct(s)
+                .with_context(|| format!("Cannot declare debug information of {}", s.name))?;
+        }
+
+        Ok(())
+    }
+
+    fn declare_debug_struct(&mut self, s: &Struct) -> Result<()> {
+        let target_data = unsafe { LLVMGetModuleDataLayout(self.module) };
+        let llvm_ty = self.get_struct(&s.name)?.1;
+
+        let debug_info = self.debug_info.as_mut().context("Debug info is disabled")?;
+
+        unsafe {
+            let name = s.name.get_name();
+            let (file, line, _) = debug_info.locate(s.name.get_pos());
+            let temporary = debug_info.struct_types[name];
+
+            let mut members = Vec::with_capacity(s.fields.len());
+            for (i, field) in s.fields.iter().enumerate() {
+                let ty = get_debug_type(debug_info, target_data, &field.ty)?;
+                let llvm_field_ty = LLVMStructGetTypeAtIndex(llvm_ty, i as u32);
+                let (field_file, field_line, _) = debug_info.locate(field.get_ident().get_pos());
+
+                members.push(LLVMDIBuilderCreateMemberType(
+                    debug_info.builder,
+                    temporary,
+                    field.get_name().as_ptr() as *const i8,
+                    field.get_name().len(),
+                    field_file,
+                    field_line,
+                    LLVMSizeOfTypeInBits(target_data, llvm_field_ty),
+                    LLVMABIAlignmentOfType(target_data, llvm_field_ty) * 8,
+                    LLVMOffsetOfElement(target_data, llvm_ty, i as u32) * 8,
+                    LLVMDIFlagZero,
+                    ty,
+                ));
+            }
+
+            let ty = LLVMDIBuilderCreateStructType(
+                debug_info.builder,
+                debug_info.compile_unit,
+                name.as_ptr() as *const i8,
+                name.len(),
+                file,
+                line,
+                LLVMSizeOfTypeInBits(target_data, llvm_ty),
+                LLVMABIAlignmentOfType(target_data, llvm_ty) * 8,
+                LLVMDIFlagZero,
+                std::ptr::null_mut(),
+                members.as_mut_ptr(),
+                members.len() as u32,
+                0,
+                std::ptr::null_mut(),
+                name.as_ptr() as *const i8,
+                name.len(),
+            );
+
+            LLVMMetadataReplaceAllUsesWith(temporary, ty);
+            debug_info.struct_types.insert(name.clone(), ty);
+        }
+
+        Ok(())
+    }
+
+    /// Creates the types of the enums, which only describe the tag,
+    /// because the payload depends on the variant.
+    pub(super) fn declare_debug_enums(&mut self, enums: &[Enum]) -> Result<()> {
+        let target_data = unsafe { LLVMGetModuleDataLayout(self.module) };
+        let llvm_tys = enums
+            .iter()
+            .map(|e| Ok(self.get_enum(&e.name)?.1))
+            .collect::<Result<Vec<_>>>()?;
+
+        let debug_info = match &mut self.debug_info {
+            Some(debug_info) => debug_info,
+            None => return Ok(()),
+        };
+
+        unsafe {
+            for (e, llvm_ty) in enums.iter().zip(llvm_tys) {
+                let name = e.name.get_name();
+                let (file, line, _) = debug_info.locate(e.name.get_pos());
+                let tag_ty = get_debug_type(debug_info, target_data, &DataType::U32)?;
+
+                let mut members = [LLVMDIBuilderCreateMemberType(
+                    debug_info.builder,
+                    debug_info.compile_unit,
+                    "tag".as_ptr() as *const i8,
+                    "tag".len(),
+                    file,
+                    line,
+                    32,
+                    32,
+                    0,
+                    LLVMDIFlagZero,
+                    tag_ty,
+                )];
+
+                let ty = LLVMDIBuilderCreateStructType(
+                    debug_info.builder,
+                    debug_info.compile_unit,
+                    name.as_ptr() as *const i8,
+                    name.len(),
+                    file,
+                    line,
+                    LLVMSizeOfTypeInBits(target_data, llvm_ty),
+                    LLVMABIAlignmentOfType(target_data, llvm_ty) * 8,
+                    LLVMDIFlagZero,
+                    std::ptr::null_mut(),
+                    members.as_mut_ptr(),
+                    members.len() as u32,
+                    0,
+                    std::ptr::null_mut(),
+                    name.as_ptr() as *const i8,
+                    name.len(),
+                );
+                debug_info.struct_types.insert(name.clone(), ty);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates the subprogram of the function.
+    pub(super) fn declare_debug_function(
+        &mut self,
+        function: &LoweredFunction,
+        llvm_function: LLVMValueRef,
+    ) -> Result<()> {
+        let target_data = unsafe { LLVMGetModuleDataLayout(self.module) };
+        let debug_info = match &mut self.debug_info {
+            Some(debug_info) => debug_info,
+            None => return Ok(()),
+        };
+
+        let signature = function.get_signature();
+
+        // The first type is the return type, which is null for void.
+        let mut types = vec![match signature.get_ret_ty() {
+            Some(ty) => get_debug_type(debug_info, target_data, ty)?,
+            None => std::ptr::null_mut(),
+        }];
+        for ty in signature.get_args_ty() {
+            types.push(get_debug_type(debug_info, target_data, ty)?);
+        }
+
+        unsafe {
+            let name = function.id.get_name();
+            let (file, line, _) = debug_info.locate(function.id.get_pos());
+
+            let ty = LLVMDIBuilderCreateSubroutineType(
+                debug_info.builder,
+                file,
+                types.as_mut_ptr(),
+                types.len() as u32,
+                LLVMDIFlagZero,
+            );
+
+            let subprogram = LLVMDIBuilderCreateFunction(
+                debug_info.builder,
+                file,
+                name.as_ptr() as *const i8,
+                name.len(),
+                std::ptr::null(),
+                0,
+                file,
+                line,
+                ty,
+                0,
+                1,
+                line,
+                LLVMDIFlagPrototyped,
+                debug_info.is_optimized as LLVMBool,
+            );
+
+            LLVMSetSubprogram(llvm_function, subprogram);
+            debug_info.subprograms.insert(name.clone(), subprogram);
+        }
+
+        Ok(())
+    }
+
+    /// Uses the subprogram of the function for the following locations.
+    pub(super) fn enter_debug_function(&mut self, function: &LoweredFunction) -> Result<()> {
+        if let Some(debug_info) = &mut self.debug_info {
+            let subprogram = debug_info
+                .subprograms
+                .get(function.id.get_name())
+                .with_context(|| format!("Function {} has no subprogram", function.id))?;
+            debug_info.scope = Some(*subprogram);
+        }
+
+        self.set_debug_location(&function.id)
+    }
+
+    /// Attaches the position of the identifier to the following instructions.
+    pub(super) fn set_debug_location(&self, id: &Identifier) -> Result<()> {
+        if let Some(debug_info) = &self.debug_info {
+            let (_, line, column) = debug_info.locate(id.get_pos());
+
+            unsafe {
+                let location = LLVMDIBuilderCreateDebugLocation(
+                    self.context,
+                    line,
+                    column,
+                    debug_info.get_scope()?,
+                    std::ptr::null_mut(),
+                );
+                LLVMSetCurrentDebugLocation2(self.builder, location);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Describes the variable, which is stored in `storage`.
+    /// Parameters are numbered from 1.
+    pub(super) fn declare_debug_variable(
+        &self,
+        id: &Identifier,
+        parameter: Option<u32>,
+        storage: LLVMValueRef,
+    ) -> Result<()> {
+        let debug_info = match &self.debug_info {
+            Some(debug_info) => debug_info,
+            None => return Ok(()),
+        };
+
+        unsafe {
+            let target_data = LLVMGetModuleDataLayout(self.module);
+            let ty = get_debug_type(
+                debug_info,
+                target_data,
+                id.ty.as_ref().unwrap_or(&DataType::Int),
+            )?;
+            let scope = debug_info.get_scope()?;
+            // Variables of loops and arms are prefixed with their scope like `for3.i`.
+            let name = id.get_name().rsplit('.').next().unwrap_or_default();
+            let (file, line, column) = debug_info.locate(id.get_pos());
+
+            let variable = match parameter {
+                Some(arg_no) => LLVMDIBuilderCreateParameterVariable(
+                    debug_info.builder,
+                    scope,
+                    name.as_ptr() as *const i8,
+                    name.len(),
+                    arg_no,
+                    file,
+                    line,
+                    ty,
+                    1,
+                    LLVMDIFlagZero,
+                ),
+                None => LLVMDIBuilderCreateAutoVariable(
+                    debug_info.builder,
+                    scope,
+                    name.as_ptr() as *const i8,
+                    name.len(),
+                    file,
+                    line,
+                    ty,
+                    1,
+                    LLVMDIFlagZero,
+                    0,
+                ),
+            };
+
+            let location = LLVMDIBuilderCreateDebugLocation(
+                self.context,
+                line,
+                column,
+                scope,
+                std::ptr::null_mut(),
+            );
+
+            LLVMDIBuilderInsertDeclareAtEnd(
+                debug_info.builder,
+                storage,
+                variable,
+                LLVMDIBuilderCreateExpression(debug_info.builder, std::ptr::null_mut(), 0),
+                location,
+                LLVMGetInsertBlock(self.builder),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the debug information, which has to happen before the module is verified.
+    pub(super) fn finalize_debug_info(&mut self) {
+        if let Some(debug_info) = &mut self.debug_info {
+            debug_info.scope = None;
+
+            unsafe {
+                LLVMSetCurrentDebugLocation2(self.builder, std::ptr::null_mut());
+                LLVMDIBuilderFinalize(debug_info.builder);
+            }
+        }
+    }
+}
+
+/// Basic types are uniqued by LLVM, so they are created every time they are used.
+fn get_debug_type(
+    debug_info: &DebugInfo,
+    target_data: LLVMTargetDataRef,
+    ty: &DataType,
+) -> Result<LLVMMetadataRef> {
+    let (bits, encoding) = match ty {
+        DataType::Struct(name) | DataType::Enum(name) => {
+            let ty = debug_info
+                .struct_types
+                .get(name.get_name())
+                .with_context(|| format!("Type {} is not defined", name))?;
+
+            unsafe {
+                return Ok(LLVMDIBuilderCreatePointerType(
+                    debug_info.builder,
+                    *ty,
+                    LLVMPointerSize(target_data) as u64 * 8,
+                    0,
+                    0,
+                    std::ptr::null(),
+                    0,
+                ));
+            }
+        }
+        DataType::Str => return Ok(get_str_debug_type(debug_info, target_data)),
+        DataType::Function(_, _) => {
+            return Ok(get_function_debug_type(debug_info, target_data, ty))
+        }
+        DataType::List(element) => {
+            return get_list_debug_type(debug_info, target_data, ty, element)
+        }
+        DataType::Array(element, len) => {
+            let element_ty = get_debug_type(debug_info, target_data, element)?;
+
+            unsafe {
+                let mut subscripts = [LLVMDIBuilderGetOrCreateSubrange(
+                    debug_info.builder,
+                    0,
+                    *len as i64,
+                )];
+
+                return Ok(LLVMDIBuilderCreateArrayType(
+                    debug_info.builder,
+                    LLVMDITypeGetSizeInBits(element_ty) * *len as u64,
+                    0,
+                    element_ty,
+                    subscripts.as_mut_ptr(),
+                    subscripts.len() as u32,
+                ));
+            }
+        }
+        DataType::Bool => (8, ENCODING_BOOLEAN),
+        ty if ty.is_float() => (ty.get_bits().unwrap_or(64), ENCODING_FLOAT),
+        ty if ty.is_signed() => (ty.get_bits().unwrap_or(64), ENCODING_SIGNED),
+        ty => (ty.get_bits().unwrap_or(64), ENCODING_UNSIGNED),
+    };
+
+    let name = ty.to_string();
+
+    unsafe {
+        Ok(LLVMDIBuilderCreateBasicType(
+            debug_info.builder,
+            name.as_ptr() as *const i8,
+            name.len(),
+            bits as u64,
+            encoding,
+            LLVMDIFlagZero,
+        ))
+    }
+}
+
+/// `[T]` is described as a pointer to a cell with the head and the pointer to the next cell.
+fn get_list_debug_type(
+    debug_info: &DebugInfo,
+    target_data: LLVMTargetDataRef,
+    ty: &DataType,
+    element: &DataType,
+) -> Result<LLVMMetadataRef> {
+    let name = ty.to_string();
+    let head_ty = get_debug_type(debug_info, target_data, element)?;
+    let pointer_bits = unsafe { LLVMPointerSize(target_data) as u64 * 8 };
+    let file = debug_info.files[0];
+
+    unsafe {
+        let temporary = LLVMDIBuilderCreateReplaceableCompositeType(
+            debug_info.builder,
+            TAG_STRUCTURE_TYPE,
+            name.as_ptr() as *const i8,
+            name.len(),
+            debug_info.compile_unit,
+            file,
+            0,
+            0,
+            0,
+            0,
+            LLVMDIFlagFwdDecl,
+            std::ptr::null(),
+            0,
+        );
+        let tail_ty = LLVMDIBuilderCreatePointerType(
+            debug_info.builder,
+            temporary,
+            pointer_bits,
+            0,
+            0,
+            std::ptr::null(),
+            0,
+        );
+
+        // The tail is aligned like a pointer after the head.
+        let head_bits = LLVMDITypeGetSizeInBits(head_ty);
+        let tail_offset = head_bits.div_ceil(pointer_bits) * pointer_bits;
+
+        let mut members = [
+            ("head", head_ty, head_bits, 0),
+            ("tail", tail_ty, pointer_bits, tail_offset),
+        ]
+        .iter()
+        .map(|(member, ty, bits, offset)| {
+            LLVMDIBuilderCreateMemberType(
+                debug_info.builder,
+                temporary,
+                member.as_ptr() as *const i8,
+                member.len(),
+                file,
+                0,
+                *bits,
+                0,
+                *offset,
+                LLVMDIFlagZero,
+                *ty,
+            )
+        })
+        .collect::<Vec<_>>();
+
+        let cell_ty = LLVMDIBuilderCreateStructType(
+            debug_info.builder,
+            debug_info.compile_unit,
+            name.as_ptr() as *const i8,
+            name.len(),
+            file,
+            0,
+            tail_offset + pointer_bits,
+            pointer_bits as u32,
+            LLVMDIFlagZero,
+            std::ptr::null_mut(),
+            members.as_mut_ptr(),
+            members.len() as u32,
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            0,
+        );
+        LLVMMetadataReplaceAllUsesWith(temporary, cell_ty);
+
+        Ok(LLVMDIBuilderCreatePointerType(
+            debug_info.builder,
+            cell_ty,
+            pointer_bits,
+            0,
+            0,
+            std::ptr::null(),
+            0,
+        ))
+    }
+}
+
+/// `str` is described as a struct with the pointer to the bytes and the length.
+fn get_str_debug_type(debug_info: &DebugInfo, target_data: LLVMTargetDataRef) -> LLVMMetadataRef {
+    let name = DataType::Str.to_string();
+    let pointer_bits = unsafe { LLVMPointerSize(target_data) as u64 * 8 };
+    let file = debug_info.files[0];
+
+    unsafe {
+        let byte_name = DataType::U8.to_string();
+        let byte_ty = LLVMDIBuilderCreateBasicType(
+            debug_info.builder,
+            byte_name.as_ptr() as *const i8,
+            byte_name.len(),
+            8,
+            ENCODING_UNSIGNED,
+            LLVMDIFlagZero,
+        );
+        let len_name = DataType::U64.to_string();
+        let len_ty = LLVMDIBuilderCreateBasicType(
+            debug_info.builder,
+            len_name.as_ptr() as *const i8,
+            len_name.len(),
+            64,
+            ENCODING_UNSIGNED,
+            LLVMDIFlagZero,
+        );
+        let ptr_ty = LLVMDIBuilderCreatePointerType(
+            debug_info.builder,
+            byte_ty,
+            pointer_bits,
+            0,
+            0,
+            std::ptr::null(),
+            0,
+        );
+
+        let mut members = [
+            ("ptr", ptr_ty, pointer_bits, 0),
+            ("len", len_ty, 64, pointer_bits),
+        ]
+        .iter()
+        .map(|(member, ty, bits, offset)| {
+            LLVMDIBuilderCreateMemberType(
+                debug_info.builder,
+                debug_info.compile_unit,
+                member.as_ptr() as *const i8,
+                member.len(),
+                file,
+                0,
+                *bits,
+                *bits as u32,
+                *offset,
+                LLVMDIFlagZero,
+                *ty,
+            )
+        })
+        .collect::<Vec<_>>();
+
+        LLVMDIBuilderCreateStructType(
+            debug_info.builder,
+            debug_info.compile_unit,
+            name.as_ptr() as *const i8,
+            name.len(),
+            file,
+            0,
+            pointer_bits + 64,
+            64,
+            LLVMDIFlagZero,
+            std::ptr::null_mut(),
+            members.as_mut_ptr(),
+            members.len() as u32,
+            0,
+            std::ptr::null_mut(),
+            name.as_ptr() as *const i8,
+            name.len(),
+        )
+    }
+}
+
+/// Describes a function value as a struct of the pointers to its code and its environment.
+fn get_function_debug_type(
+    debug_info: &DebugInfo,
+    target_data: LLVMTargetDataRef,
+    ty: &DataType,
+) -> LLVMMetadataRef {
+    let name = ty.to_string();
+    let pointer_bits = unsafe { LLVMPointerSize(target_data) as u64 * 8 };
+    let file = debug_info.files[0];
+
+    unsafe {
+        let byte_name = DataType::U8.to_string();
+        let byte_ty = LLVMDIBuilderCreateBasicType(
+            debug_info.builder,
+            byte_name.as_ptr() as *const i8,
+            byte_name.len(),
+            8,
+            ENCODING_UNSIGNED,
+            LLVMDIFlagZero,
+        );
+        let ptr_ty = LLVMDIBuilderCreatePointerType(
+            debug_info.builder,
+            byte_ty,
+            pointer_bits,
+            0,
+            0,
+            std::ptr::null(),
+            0,
+        );
+
+        let mut members = [("code", 0), ("env", pointer_bits)]
+            .iter()
+            .map(|(member, offset)| {
+                LLVMDIBuilderCreateMemberType(
+                    debug_info.builder,
+                    debug_info.compile_unit,
+                    member.as_ptr() as *const i8,
+                    member.len(),
+                    file,
+                    0,
+                    pointer_bits,
+                    pointer_bits as u32,
+                    *offset,
+                    LLVMDIFlagZero,
+                    ptr_ty,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        LLVMDIBuilderCreateStructType(
+            debug_info.builder,
+            debug_info.compile_unit,
+            name.as_ptr() as *const i8,
+            name.len(),
+            file,
+            0,
+            pointer_bits * 2,
+            pointer_bits as u32,
+            LLVMDIFlagZero,
+            std::ptr::null_mut(),
+            members.as_mut_ptr(),
+            members.len() as u32,
+            0,
+            std::ptr::null_mut(),
+            name.as_ptr() as *const i8,
+            name.len(),
+        )
+    }
+}
@@ -0,0 +1,225 @@
+use super::{runtime, Codegen};
+use crate::c_str;
+use crate::utils::LLVMString;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use llvm_sys::bit_writer::LLVMWriteBitcodeToFile;
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::target_machine::*;
+use log::info;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Name of the `main` of the program in an executable, whose `main` is the C entry point.
+const MAIN: &str = "mill.main";
+
+/// The kind of output which is written by the compiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EmitKind {
+    /// Textual LLVM IR
+    LlvmIr,
+    /// LLVM bitcode
+    LlvmBc,
+    /// Assembly of the target
+    Asm,
+    /// Object file of the target
+    Obj,
+    /// Executable linked by the system C toolchain
+    Exe,
+}
+
+impl EmitKind {
+    /// The path which is used when no output was given.
+    pub fn default_output(&self) -> PathBuf {
+        PathBuf::from(match self {
+            EmitKind::LlvmIr => "main.ll",
+            EmitKind::LlvmBc => "main.bc",
+            EmitKind::Asm => "main.s",
+            EmitKind::Obj => "main.o",
+            EmitKind::Exe => "main",
+        })
+    }
+}
+
+impl Codegen {
+    /// Writes the module to `output`.
+    pub fn emit(&mut self, kind: EmitKind, output: &Path) -> Result<()> {
+        info!("=> Emitting {:?} to {}", kind, output.display());
+
+        match kind {
+            EmitKind::LlvmIr => self.emit_llvm_ir(output),
+            EmitKind::LlvmBc => self.emit_llvm_bc(output),
+            EmitKind::Asm => {
+                self.emit_with_target_machine(LLVMCodeGenFileType::LLVMAssemblyFile, output)
+            }
+            EmitKind::Obj => {
+                self.emit_with_target_machine(LLVMCodeGenFileType::LLVMObjectFile, output)
+            }
+            EmitKind::Exe => {
+                self.build_c_main()?;
+                self.emit_executable(output)
+            }
+        }
+    }
+
+    fn emit_llvm_ir(&self, output: &Path) -> Result<()> {
+        let path = path_to_c_string(output)?;
+
+        unsafe {
+            let mut message = std::ptr::null_mut();
+
+            if LLVMPrintModuleToFile(self.module, path.as_ptr(), &mut message) != 0 {
+                bail!(
+                    "Cannot write {}: {}",
+                    output.display(),
+                    LLVMString::new(message)
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_llvm_bc(&self, output: &Path) -> Result<()> {
+        let path = path_to_c_string(output)?;
+
+        unsafe {
+            if LLVMWriteBitcodeToFile(self.module, path.as_ptr()) != 0 {
+                bail!("Cannot write {}", output.display());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_with_target_machine(
+        &self,
+        file_type: LLVMCodeGenFileType,
+        output: &Path,
+    ) -> Result<()> {
+        let path = path_to_c_string(output)?;
+
+        unsafe {
+            let target_machine = self.create_target_machine()?;
+            let mut message = std::ptr::null_mut();
+
+            let failed = LLVMTargetMachineEmitToFile(
+                target_machine,
+                self.module,
+                path.as_ptr() as *mut _,
+                file_type,
+                &mut message,
+            );
+
+            LLVMDisposeTargetMachine(target_machine);
+
+            if failed != 0 {
+                bail!(
+                    "Cannot write {}: {}",
+                    output.display(),
+                    LLVMString::new(message)
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renames `main` of the program and builds the C `main`, which parses the arguments
+    /// of the command line like `run_main` and returns the value of `main` as exit code.
+    fn build_c_main(&mut self) -> Result<()> {
+        let (signature, main) = self
+            .function_table
+            .get(&"main".to_string())
+            .context("Function main is not defined")?
+            .clone();
+
+        unsafe {
+            LLVMSetValueName2(main, c_str!(MAIN), MAIN.len());
+        }
+
+        let entry = self.build_entry(main, &signature)?;
+
+        unsafe {
+            let int_ty = LLVMInt64TypeInContext(self.context);
+            let code_ty = LLVMInt32TypeInContext(self.context);
+            let argv_ty = LLVMPointerType(self.get_ptr_type(), 0);
+            let mut params_ty = [code_ty, argv_ty];
+            let function_ty = LLVMFunctionType(code_ty, params_ty.as_mut_ptr(), 2, 0);
+            let function = LLVMAddFunction(self.module, c_str!("main"), function_ty);
+
+            let block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("entry"));
+            LLVMPositionBuilderAtEnd(self.builder, block);
+
+            let count = LLVMConstInt(int_ty, signature.get_args_ty().len() as u64, 0);
+            let values = LLVMBuildArrayAlloca(self.builder, int_ty, count, c_str!("args"));
+
+            let parse_args = self.get_runtime_function(
+                runtime::PARSE_ARGS,
+                None,
+                &[code_ty, self.get_ptr_type(), self.get_ptr_type(), int_ty],
+            );
+            let mut args = [
+                LLVMGetParam(function, 0),
+                self.build_ptr_cast(LLVMGetParam(function, 1)),
+                self.build_ptr_cast(values),
+                count,
+            ];
+            LLVMBuildCall(
+                self.builder,
+                parse_args,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+
+            let mut args = [values];
+            let value = LLVMBuildCall(self.builder, entry, args.as_mut_ptr(), 1, c_str!("main"));
+            let code = LLVMBuildTrunc(self.builder, value, code_ty, c_str!("code"));
+            LLVMBuildRet(self.builder, code);
+        }
+
+        Ok(())
+    }
+
+    unsafe fn build_ptr_cast(&self, value: LLVMValueRef) -> LLVMValueRef {
+        LLVMBuildBitCast(self.builder, value, self.get_ptr_type(), c_str!(""))
+    }
+
+    /// Writes an object file and the runtime into a private temporary directory and
+    /// links them with the system C toolchain. The linker can be changed with `CC`.
+    fn emit_executable(&self, output: &Path) -> Result<()> {
+        let directory = tempfile::Builder::new()
+            .prefix("mill-")
+            .tempdir()
+            .context("Cannot create a temporary directory")?;
+        let object = directory.path().join("main.o");
+        let runtime = directory.path().join("libmill_runtime.a");
+
+        self.emit_with_target_machine(LLVMCodeGenFileType::LLVMObjectFile, &object)?;
+        std::fs::write(&runtime, runtime::ARCHIVE)
+            .with_context(|| format!("Cannot write {}", runtime.display()))?;
+
+        let linker = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+        let status = Command::new(&linker)
+            .arg(&object)
+            .arg(&runtime)
+            .arg("-o")
+            .arg(output)
+            .status()
+            .with_context(|| format!("Cannot run linker {}", linker))?;
+
+        if !status.success() {
+            bail!("Linking {} failed", output.display());
+        }
+
+        Ok(())
+    }
+}
+
+fn path_to_c_string(path: &Path) -> Result<CString> {
+    CString::new(path.to_string_lossy().as_bytes())
+        .with_context(|| format!("Invalid path {}", path.display()))
+}
@@ -0,0 +1,176 @@
+use super::gc::collect_pointer_offsets;
+use super::Codegen;
+use crate::ast::{Enum, Identifier};
+use crate::c_str;
+use anyhow::{Context, Result};
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::target::*;
+
+/**
+ * Code for enums.
+ *
+ * An enum value is a pointer to a tagged union on the heap. The union starts with the tag,
+ * which is the position of the variant in the enum, and has room for the largest payload.
+ * Every variant has its own struct type `{ tag, fields... }`, which the union is accessed through.
+ */
+impl Codegen {
+    pub(super) fn get_enum(&self, name: &Identifier) -> Result<&(Enum, LLVMTypeRef)> {
+        self.enum_table
+            .get(name.get_name())
+            .with_context(|| format!("Enum {} is not defined", name))
+    }
+
+    /// Returns the struct type of the variant with the tag, which is the tag and the payload.
+    pub(super) fn get_variant_type(&self, e: &Enum, tag: usize) -> Result<LLVMTypeRef> {
+        unsafe {
+            let mut fields_ty = vec![LLVMInt32TypeInContext(self.context)];
+            for field in e.variants[tag].fields.iter() {
+                fields_ty.push(self.get_llvm_type(field)?);
+            }
+
+            Ok(LLVMStructTypeInContext(
+                self.context,
+                fields_ty.as_mut_ptr(),
+                fields_ty.len() as u32,
+                0,
+            ))
+        }
+    }
+
+    /// Sets the body of the union, which was created by `declare_types`. The payload
+    /// is an array of `i64`, so it is aligned for every field.
+    pub(super) fn define_enum(&self, e: &Enum) -> Result<()> {
+        let ty = self.get_enum(&e.name)?.1;
+
+        unsafe {
+            let target_data = LLVMGetModuleDataLayout(self.module);
+
+            let mut size = 0;
+            for tag in 0..e.variants.len() {
+                size = size.max(LLVMABISizeOfType(
+                    target_data,
+                    self.get_variant_type(e, tag)?,
+                ));
+            }
+
+            let words = size.saturating_sub(8).div_ceil(8);
+            let mut fields_ty = [
+                LLVMInt32TypeInContext(self.context),
+                LLVMArrayType(LLVMInt64TypeInContext(self.context), words as u32),
+            ];
+            LLVMStructSetBody(ty, fields_ty.as_mut_ptr(), fields_ty.len() as u32, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Allocates the variant with the tag and stores the tag. Returns the enum value
+    /// and the pointer to the variant, which the payload is stored through.
+    pub(super) fn build_variant(
+        &self,
+        name: &Identifier,
+        tag: usize,
+    ) -> Result<(LLVMValueRef, LLVMValueRef)> {
+        let (e, ty) = self.get_enum(name)?;
+        let variant_ty = self.get_variant_type(e, tag)?;
+
+        unsafe {
+            let value = if self.uses_gc() {
+                // Every variant has its own descriptor, so the collector
+                // only traces the pointers of its payload.
+                let target_data = LLVMGetModuleDataLayout(self.module);
+                let mut offsets = Vec::new();
+                for (i, field) in e.variants[tag].fields.iter().enumerate() {
+                    collect_pointer_offsets(
+                        target_data,
+                        field,
+                        LLVMStructGetTypeAtIndex(variant_ty, i as u32 + 1),
+                        LLVMOffsetOfElement(target_data, variant_ty, i as u32 + 1),
+                        &mut offsets,
+                    );
+                }
+
+                let global_name = format!("mill.type.{}.{}", name, e.variants[tag].name);
+                let descriptor = self.build_type_descriptor(&global_name, *ty, offsets);
+                self.build_gc_alloc_object(descriptor, *ty, name.get_name())
+            } else {
+                self.build_heap_alloc(*ty, name.get_name())
+            };
+
+            let variant = LLVMBuildBitCast(
+                self.builder,
+                value,
+                LLVMPointerType(variant_ty, 0),
+                c_str!(""),
+            );
+            let tag_ptr = LLVMBuildStructGEP(self.builder, variant, 0, c_str!(""));
+            LLVMBuildStore(
+                self.builder,
+                LLVMConstInt(LLVMInt32TypeInContext(self.context), tag as u64, 0),
+                tag_ptr,
+            );
+
+            Ok((value, variant))
+        }
+    }
+
+    /// Stores the field of the payload through the pointer of `build_variant`.
+    pub(super) fn build_payload_store(
+        &self,
+        variant: LLVMValueRef,
+        index: usize,
+        value: LLVMValueRef,
+    ) {
+        unsafe {
+            let ptr = LLVMBuildStructGEP(self.builder, variant, index as u32 + 1, c_str!(""));
+            LLVMBuildStore(self.builder, value, ptr);
+        }
+    }
+
+    /// Loads the field of the payload of the enum value, which has the variant with the tag.
+    pub(super) fn build_payload_load(
+        &self,
+        value: LLVMValueRef,
+        name: &Identifier,
+        tag: usize,
+        index: usize,
+    ) -> Result<LLVMValueRef> {
+        let e = &self.get_enum(name)?.0;
+        let variant_ty = self.get_variant_type(e, tag)?;
+
+        unsafe {
+            let variant = LLVMBuildBitCast(
+                self.builder,
+                value,
+                LLVMPointerType(variant_ty, 0),
+                c_str!(""),
+            );
+            let ptr = LLVMBuildStructGEP(self.builder, variant, index as u32 + 1, c_str!(""));
+
+            Ok(LLVMBuildLoad(self.builder, ptr, c_str!("")))
+        }
+    }
+
+    /// Jumps to the block of the tag of the enum value, or to the default block.
+    pub(super) fn build_switch(
+        &self,
+        value: LLVMValueRef,
+        cases: &[(usize, LLVMBasicBlockRef)],
+        default: LLVMBasicBlockRef,
+    ) {
+        unsafe {
+            let tag_ptr = LLVMBuildStructGEP(self.builder, value, 0, c_str!(""));
+            let tag = LLVMBuildLoad(self.builder, tag_ptr, c_str!("tag"));
+
+            let switch = LLVMBuildSwitch(self.builder, tag, default, cases.len() as u32);
+            for (tag, block) in cases {
+                LLVMAddCase(
+                    switch,
+                    LLVMConstInt(LLVMInt32TypeInContext(self.context), *tag as u64, 0),
+                    *block,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use super::runtime::{GC_ALLOC, GC_POP_FRAME, GC_PUSH_FRAME};
+use super::Codegen;
+use crate::ast::{DataType, Identifier};
+use crate::c_str;
+use anyhow::Result;
+use clap::ValueEnum;
+use llvm_sys::core::*;
+use llvm_sys::debuginfo::LLVMInstructionGetDebugLoc;
+use llvm_sys::prelude::*;
+use llvm_sys::target::*;
+use llvm_sys::{LLVMOpcode, LLVMTypeKind};
+
+/// Header which precedes every struct and list cell when the collector is enabled.
+/// It has to match `struct mill_header` of the runtime.
+const HEADER: &str = "mill.header";
+
+/// The garbage collector, which reclaims the structs, lists, enums and closures on the heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GcStrategy {
+    /// Structs on the heap are never freed
+    None,
+    /// Precise mark-and-sweep collector of the runtime
+    Marksweep,
+}
+
+/**
+ * Code for the mark-and-sweep collector of the runtime.
+ *
+ * Every struct gets a header with its type descriptor, which tells the collector
+ * where the pointers of the struct are. Every function registers the addresses of
+ * its struct variables in a frame of the shadow stack, so the collector finds the roots.
+ */
+impl Codegen {
+    pub fn set_gc(&mut self, gc: GcStrategy) {
+        self.gc = gc;
+    }
+
+    pub(super) fn uses_gc(&self) -> bool {
+        self.gc == GcStrategy::Marksweep
+    }
+
+    pub(super) fn get_ptr_type(&self) -> LLVMTypeRef {
+        unsafe { LLVMPointerType(LLVMInt8TypeInContext(self.context), 0) }
+    }
+
+    fn get_header_type(&self) -> LLVMTypeRef {
+        unsafe {
+            let ty = LLVMGetTypeByName2(self.context, c_str!(HEADER));
+            if !ty.is_null() {
+                return ty;
+            }
+
+            let ty = LLVMStructCreateNamed(self.context, c_str!(HEADER));
+            let mut fields_ty = [
+                self.get_ptr_type(),
+                self.get_ptr_type(),
+                LLVMInt64TypeInContext(self.context),
+            ];
+            LLVMStructSetBody(ty, fields_ty.as_mut_ptr(), fields_ty.len() as u32, 0);
+
+            ty
+        }
+    }
+
+    /// Returns the type descriptor of the struct, which is created when it is used first.
+    fn get_type_descriptor(&self, name: &Identifier) -> Result<LLVMValueRef> {
+        let (s, ty) = self.get_struct(name)?;
+
+        unsafe {
+            let target_data = LLVMGetModuleDataLayout(self.module);
+
+            let mut offsets = Vec::new();
+            for (i, field) in s.fields.iter().enumerate() {
+                collect_pointer_offsets(
+                    target_data,
+                    &field.ty,
+                    LLVMStructGetTypeAtIndex(*ty, i as u32),
+                    LLVMOffsetOfElement(target_data, *ty, i as u32),
+                    &mut offsets,
+                );
+            }
+
+            Ok(self.build_type_descriptor(&format!("mill.type.{}", name.get_name()), *ty, offsets))
+        }
+    }
+
+    /// Returns the descriptor of an object of type `ty` with pointers at the offsets,
+    /// which is created when it is used first. It has to match `struct mill_type` of the runtime.
+    pub(super) unsafe fn build_type_descriptor(
+        &self,
+        global_name: &str,
+        ty: LLVMTypeRef,
+        offsets: Vec<u64>,
+    ) -> LLVMValueRef {
+        let global = LLVMGetNamedGlobal(self.module, c_str!(global_name));
+        if !global.is_null() {
+            return global;
+        }
+
+        let target_data = LLVMGetModuleDataLayout(self.module);
+        let int_ty = LLVMInt64TypeInContext(self.context);
+
+        let mut offsets = offsets
+            .into_iter()
+            .map(|offset| LLVMConstInt(int_ty, offset, 0))
+            .collect::<Vec<_>>();
+
+        let offsets_ptr = if offsets.is_empty() {
+            LLVMConstNull(LLVMPointerType(int_ty, 0))
+        } else {
+            let array = LLVMConstArray(int_ty, offsets.as_mut_ptr(), offsets.len() as u32);
+            let global = self.add_constant(&format!("{}.offsets", global_name), array);
+            LLVMConstBitCast(global, LLVMPointerType(int_ty, 0))
+        };
+
+        let mut fields = [
+            LLVMConstInt(int_ty, LLVMABISizeOfType(target_data, ty), 0),
+            LLVMConstInt(int_ty, offsets.len() as u64, 0),
+            offsets_ptr,
+        ];
+        let descriptor =
+            LLVMConstStructInContext(self.context, fields.as_mut_ptr(), fields.len() as u32, 0);
+
+        self.add_constant(global_name, descriptor)
+    }
+
+    pub(super) unsafe fn add_constant(&self, name: &str, value: LLVMValueRef) -> LLVMValueRef {
+        let global = LLVMAddGlobal(self.module, LLVMTypeOf(value), c_str!(name));
+        LLVMSetInitializer(global, value);
+        LLVMSetGlobalConstant(global, 1);
+        LLVMSetLinkage(global, llvm_sys::LLVMLinkage::LLVMPrivateLinkage);
+
+        global
+    }
+
+    /// Allocates the struct with the collector.
+    pub(super) fn build_gc_alloc(&self, name: &Identifier) -> Result<LLVMValueRef> {
+        let ty = self.get_struct(name)?.1;
+        let descriptor = self.get_type_descriptor(name)?;
+
+        Ok(self.build_gc_alloc_object(descriptor, ty, name.get_name()))
+    }
+
+    /// Allocates an object of type `ty` with the collector and returns a pointer to it.
+    pub(super) fn build_gc_alloc_object(
+        &self,
+        descriptor: LLVMValueRef,
+        ty: LLVMTypeRef,
+        name: &str,
+    ) -> LLVMValueRef {
+        unsafe {
+            let function = self.get_runtime_function(
+                GC_ALLOC,
+                Some(self.get_ptr_type()),
+                &[self.get_ptr_type()],
+            );
+            let mut args = [LLVMConstBitCast(descriptor, self.get_ptr_type())];
+
+            let ptr = LLVMBuildCall(
+                self.builder,
+                function,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+
+            LLVMBuildBitCast(self.builder, ptr, LLVMPointerType(ty, 0), c_str!(name))
+        }
+    }
+
+    /// Allocates the struct with a header on the stack, so the collector can trace its fields.
+    pub(super) fn build_gc_stack_object(
+        &self,
+        llvm_function: LLVMValueRef,
+        name: &Identifier,
+    ) -> Result<LLVMValueRef> {
+        let ty = self.get_struct(name)?.1;
+        let descriptor = self.get_type_descriptor(name)?;
+
+        unsafe {
+            let header_ty = self.get_header_type();
+            let mut fields_ty = [header_ty, ty];
+            let object_ty = LLVMStructTypeInContext(self.context, fields_ty.as_mut_ptr(), 2, 0);
+            let object = self.build_entry_alloca(llvm_function, name.get_name(), object_ty)?;
+
+            let mut header = [
+                LLVMConstBitCast(descriptor, self.get_ptr_type()),
+                LLVMConstNull(self.get_ptr_type()),
+                LLVMConstInt(LLVMInt64TypeInContext(self.context), 0, 0),
+            ];
+            let mut fields = [
+                LLVMConstNamedStruct(header_ty, header.as_mut_ptr(), header.len() as u32),
+                LLVMConstNull(ty),
+            ];
+
+            // The header and the fields are reset every time `new` is evaluated.
+            LLVMBuildStore(
+                self.builder,
+                LLVMConstStructInContext(self.context, fields.as_mut_ptr(), 2, 0),
+                object.value,
+            );
+
+            Ok(LLVMBuildStructGEP(
+                self.builder,
+                object.value,
+                1,
+                c_str!(""),
+            ))
+        }
+    }
+
+    /// Registers the variable as root of the current function.
+    pub(super) fn add_gc_root(&mut self, slot: LLVMValueRef) {
+        if self.uses_gc() {
+            self.gc_roots.push(slot);
+        }
+    }
+
+    /// Stores the value in a new root, so it survives collections until it is loaded again.
+    pub(super) fn spill_gc_root(&mut self, value: LLVMValueRef) -> Result<LLVMValueRef> {
+        unsafe {
+            let llvm_function = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+            let slot = self.build_entry_alloca(llvm_function, "gc.tmp", LLVMTypeOf(value))?;
+
+            LLVMBuildStore(self.builder, value, slot.value);
+            self.add_gc_root(slot.value);
+
+            Ok(slot.value)
+        }
+    }
+
+    /// Pushes a frame with the roots of the function at its start and
+    /// pops it before every return.
+    pub(super) fn build_gc_frame(&mut self, llvm_function: LLVMValueRef) -> Result<()> {
+        let variables = std::mem::take(&mut self.gc_roots);
+        if variables.is_empty() {
+            return Ok(());
+        }
+
+        unsafe {
+            // The frame is pushed after the allocas of the entry block,
+            // before the variables can be used.
+            let mut start = LLVMGetFirstInstruction(LLVMGetEntryBasicBlock(llvm_function));
+            while LLVMGetInstructionOpcode(start) == LLVMOpcode::LLVMAlloca {
+                start = LLVMGetNextInstruction(start);
+            }
+            LLVMPositionBuilderBefore(self.builder, start);
+            LLVMSetCurrentDebugLocation2(self.builder, LLVMInstructionGetDebugLoc(start));
+
+            let mut roots = Vec::with_capacity(variables.len());
+            for variable in variables {
+                // The collector must not see the garbage of uninitialized variables.
+                let variable_ty = LLVMGetElementType(LLVMTypeOf(variable));
+                LLVMBuildStore(self.builder, LLVMConstNull(variable_ty), variable);
+
+                self.collect_root_slots(variable, &mut roots);
+            }
+
+            let int_ty = LLVMInt64TypeInContext(self.context);
+            let slot_ty = LLVMPointerType(self.get_ptr_type(), 0);
+            let mut fields_ty = [
+                self.get_ptr_type(),
+                int_ty,
+                LLVMArrayType(slot_ty, roots.len() as u32),
+            ];
+            let frame_ty = LLVMStructTypeInContext(self.context, fields_ty.as_mut_ptr(), 3, 0);
+
+            let frame = LLVMBuildAlloca(self.builder, frame_ty, c_str!("gc.frame"));
+            let count = LLVMBuildStructGEP(self.builder, frame, 1, c_str!(""));
+            LLVMBuildStore(
+                self.builder,
+                LLVMConstInt(int_ty, roots.len() as u64, 0),
+                count,
+            );
+
+            for (i, slot) in roots.iter().enumerate() {
+                let mut indices = [
+                    LLVMConstInt(LLVMInt32TypeInContext(self.context), 0, 0),
+                    LLVMConstInt(LLVMInt32TypeInContext(self.context), 2, 0),
+                    LLVMConstInt(int_ty, i as u64, 0),
+                ];
+                let root = LLVMBuildGEP(
+                    self.builder,
+                    frame,
+                    indices.as_mut_ptr(),
+                    indices.len() as u32,
+                    c_str!(""),
+                );
+                let slot = LLVMBuildBitCast(self.builder, *slot, slot_ty, c_str!(""));
+                LLVMBuildStore(self.builder, slot, root);
+            }
+
+            let frame = LLVMBuildBitCast(self.builder, frame, self.get_ptr_type(), c_str!(""));
+            let push = self.get_runtime_function(GC_PUSH_FRAME, None, &[self.get_ptr_type()]);
+            let pop = self.get_runtime_function(GC_POP_FRAME, None, &[self.get_ptr_type()]);
+
+            let mut args = [frame];
+            LLVMBuildCall(self.builder, push, args.as_mut_ptr(), 1, c_str!(""));
+
+            let mut block = LLVMGetFirstBasicBlock(llvm_function);
+            while !block.is_null() {
+                let terminator = LLVMGetBasicBlockTerminator(block);
+
+                if !terminator.is_null()
+                    && LLVMGetInstructionOpcode(terminator) == LLVMOpcode::LLVMRet
+                {
+                    LLVMPositionBuilderBefore(self.builder, terminator);
+                    LLVMSetCurrentDebugLocation2(
+                        self.builder,
+                        LLVMInstructionGetDebugLoc(terminator),
+                    );
+                    LLVMBuildCall(self.builder, pop, args.as_mut_ptr(), 1, c_str!(""));
+                }
+
+                block = LLVMGetNextBasicBlock(block);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collects the slots of the struct pointers in the variable. Arrays of
+    /// structs have a slot for every element and function values have a slot
+    /// for their environment.
+    unsafe fn collect_root_slots(&self, slot: LLVMValueRef, roots: &mut Vec<LLVMValueRef>) {
+        let ty = LLVMGetElementType(LLVMTypeOf(slot));
+        if ty == self.get_closure_type() {
+            roots.push(LLVMBuildStructGEP(self.builder, slot, 1, c_str!("")));
+            return;
+        }
+        if LLVMGetTypeKind(ty) != LLVMTypeKind::LLVMArrayTypeKind {
+            roots.push(slot);
+            return;
+        }
+
+        let int_ty = LLVMInt64TypeInContext(self.context);
+        for i in 0..LLVMGetArrayLength(ty) {
+            let mut indices = [
+                LLVMConstInt(int_ty, 0, 0),
+                LLVMConstInt(int_ty, i as u64, 0),
+            ];
+            let element = LLVMBuildGEP(
+                self.builder,
+                slot,
+                indices.as_mut_ptr(),
+                indices.len() as u32,
+                c_str!(""),
+            );
+
+            self.collect_root_slots(element, roots);
+        }
+    }
+}
+
+/// Collects the offsets of the pointers to structs, lists, enums and environments
+/// in a value of `ty`, which starts at `offset`.
+pub(super) unsafe fn collect_pointer_offsets(
+    target_data: LLVMTargetDataRef,
+    ty: &DataType,
+    llvm_ty: LLVMTypeRef,
+    offset: u64,
+    offsets: &mut Vec<u64>,
+) {
+    match ty {
+        DataType::Struct(_) | DataType::List(_) | DataType::Enum(_) => offsets.push(offset),
+        DataType::Function(_, _) => {
+            offsets.push(offset + LLVMOffsetOfElement(target_data, llvm_ty, 1))
+        }
+        DataType::Array(element, len) if element.has_references() => {
+            let element_ty = LLVMGetElementType(llvm_ty);
+            let size = LLVMABISizeOfType(target_data, element_ty);
+
+            for i in 0..*len as u64 {
+                collect_pointer_offsets(
+                    target_data,
+                    element,
+                    element_ty,
+                    offset + i * size,
+                    offsets,
+                );
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,155 @@
+use super::{runtime, Codegen};
+use crate::ast::DataType;
+use crate::c_str;
+use crate::symbol_table::FunctionSignature;
+use crate::utils::LLVMString;
+use anyhow::{bail, Context, Result};
+use llvm_sys::core::*;
+use llvm_sys::execution_engine::*;
+use llvm_sys::prelude::*;
+use llvm_sys::target::{LLVM_InitializeNativeAsmPrinter, LLVM_InitializeNativeTarget};
+use log::info;
+
+/// Name of the function which calls `main` with the arguments of the command line.
+const ENTRY: &str = "mill.entry";
+
+type Entry = extern "C" fn(*const i64) -> i64;
+
+impl Codegen {
+    /// Compiles the module in memory and calls `main` with the given arguments.
+    /// Every argument is passed as `int` to the parameter of `main` at the same position.
+    /// Returns the value of `main`, or 0 when `main` returns nothing.
+    pub fn run_main(&mut self, arguments: &[String]) -> Result<i64> {
+        let (signature, main) = self
+            .function_table
+            .get(&"main".to_string())
+            .context("Function main is not defined")?
+            .clone();
+
+        if signature.get_args_ty().len() != arguments.len() {
+            bail!(
+                "Function main expects {} arguments, but {} were given",
+                signature.get_args_ty().len(),
+                arguments.len()
+            );
+        }
+
+        let arguments = arguments
+            .iter()
+            .map(|x| {
+                x.parse::<i64>()
+                    .with_context(|| format!("Argument {} is not an int", x))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.build_entry(main, &signature)?;
+
+        info!("=> Running main");
+
+        unsafe {
+            LLVMLinkInMCJIT();
+            runtime::register_symbols();
+
+            if LLVM_InitializeNativeTarget() != 0 || LLVM_InitializeNativeAsmPrinter() != 0 {
+                bail!("Cannot initialize the native target");
+            }
+
+            let mut options: LLVMMCJITCompilerOptions = std::mem::zeroed();
+            let options_size = std::mem::size_of::<LLVMMCJITCompilerOptions>();
+            LLVMInitializeMCJITCompilerOptions(&mut options, options_size);
+            options.OptLevel = self.opt_level.get_codegen_level() as u32;
+
+            let mut engine = std::ptr::null_mut();
+            let mut message = std::ptr::null_mut();
+
+            if LLVMCreateMCJITCompilerForModule(
+                &mut engine,
+                self.module,
+                &mut options,
+                options_size,
+                &mut message,
+            ) != 0
+            {
+                bail!("Cannot create the JIT: {}", LLVMString::new(message));
+            }
+
+            let address = LLVMGetFunctionAddress(engine, c_str!(ENTRY));
+            let result = if address != 0 {
+                let entry: Entry = std::mem::transmute(address as usize);
+                Ok(entry(arguments.as_ptr()))
+            } else {
+                Err(anyhow::anyhow!("Cannot find function {}", ENTRY))
+            };
+
+            // The engine owns the module until it is removed again.
+            let mut module = std::ptr::null_mut();
+            let mut message = std::ptr::null_mut();
+            LLVMRemoveModule(engine, self.module, &mut module, &mut message);
+            LLVMDisposeExecutionEngine(engine);
+
+            result
+        }
+    }
+
+    /// Builds the function `ENTRY`, which loads the arguments from
+    /// an array of `int` and calls `main`.
+    pub(super) fn build_entry(
+        &mut self,
+        main: LLVMValueRef,
+        signature: &FunctionSignature,
+    ) -> Result<LLVMValueRef> {
+        if signature
+            .get_args_ty()
+            .iter()
+            .any(|ty| *ty != DataType::Int)
+        {
+            bail!("Parameters of function main have to be int");
+        }
+
+        if !matches!(signature.get_ret_ty(), None | Some(DataType::Int)) {
+            bail!("Function main has to return int or nothing");
+        }
+
+        unsafe {
+            let int_ty = LLVMInt64TypeInContext(self.context);
+            let mut args_ty = vec![LLVMPointerType(int_ty, 0)];
+            let function_ty = LLVMFunctionType(int_ty, args_ty.as_mut_ptr(), 1, 0);
+            let function = LLVMAddFunction(self.module, c_str!(ENTRY), function_ty);
+
+            // The entry has no debug information.
+            LLVMSetCurrentDebugLocation2(self.builder, std::ptr::null_mut());
+            let block = LLVMAppendBasicBlockInContext(self.context, function, c_str!("entry"));
+            LLVMPositionBuilderAtEnd(self.builder, block);
+
+            let array = LLVMGetParam(function, 0);
+            let mut args = (0..signature.get_args_ty().len())
+                .map(|i| {
+                    let mut index = [LLVMConstInt(int_ty, i as u64, 0)];
+                    let ptr = LLVMBuildGEP(self.builder, array, index.as_mut_ptr(), 1, c_str!(""));
+                    LLVMBuildLoad(self.builder, ptr, c_str!(""))
+                })
+                .collect::<Vec<_>>();
+
+            let name = if signature.get_ret_ty().is_some() {
+                "main"
+            } else {
+                ""
+            };
+            let value = LLVMBuildCall(
+                self.builder,
+                main,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(name),
+            );
+
+            if signature.get_ret_ty().is_some() {
+                LLVMBuildRet(self.builder, value);
+            } else {
+                LLVMBuildRet(self.builder, LLVMConstInt(int_ty, 0, 0));
+            }
+
+            Ok(function)
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use super::gc::collect_pointer_offsets;
+use super::runtime::EMPTY_LIST;
+use super::Codegen;
+use crate::ast::{DataType, Opcode};
+use crate::c_str;
+use anyhow::{bail, Result};
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::target::*;
+
+/**
+ * Code for the cons lists.
+ *
+ * A list `[T]` is a pointer to its first cell, which holds the head and the pointer
+ * to the next cell. The empty list `nil` is a null pointer. Cells are never modified
+ * after they were created, so lists can share their tails.
+ */
+impl Codegen {
+    /// Returns the type of the cells of a list of `element`, which is created when it is used first.
+    pub(super) fn get_list_cell_type(&self, element: &DataType) -> Result<LLVMTypeRef> {
+        let name = format!("mill.list.{}", element);
+
+        unsafe {
+            let ty = LLVMGetTypeByName2(self.context, c_str!(name));
+            if !ty.is_null() {
+                return Ok(ty);
+            }
+
+            let ty = LLVMStructCreateNamed(self.context, c_str!(name));
+            let mut fields_ty = [self.get_llvm_type(element)?, LLVMPointerType(ty, 0)];
+            LLVMStructSetBody(ty, fields_ty.as_mut_ptr(), fields_ty.len() as u32, 0);
+
+            Ok(ty)
+        }
+    }
+
+    /// Allocates an uninitialized cell on the heap, which is traced by the collector when it is enabled.
+    pub(super) fn build_list_cell(&self, element: &DataType) -> Result<LLVMValueRef> {
+        let cell_ty = self.get_list_cell_type(element)?;
+
+        unsafe {
+            if self.uses_gc() {
+                let target_data = LLVMGetModuleDataLayout(self.module);
+
+                let mut offsets = Vec::new();
+                collect_pointer_offsets(
+                    target_data,
+                    element,
+                    LLVMStructGetTypeAtIndex(cell_ty, 0),
+                    0,
+                    &mut offsets,
+                );
+                offsets.push(LLVMOffsetOfElement(target_data, cell_ty, 1));
+
+                let name = format!("mill.type.{}", DataType::List(Box::new(element.clone())));
+                let descriptor = self.build_type_descriptor(&name, cell_ty, offsets);
+
+                return Ok(self.build_gc_alloc_object(descriptor, cell_ty, "cell"));
+            }
+
+            Ok(self.build_heap_alloc(cell_ty, "cell"))
+        }
+    }
+
+    /// Stores the head and the tail in the new cell.
+    pub(super) fn build_list_init(
+        &self,
+        cell: LLVMValueRef,
+        head: LLVMValueRef,
+        tail: LLVMValueRef,
+    ) {
+        unsafe {
+            let head_ptr = LLVMBuildStructGEP(self.builder, cell, 0, c_str!(""));
+            LLVMBuildStore(self.builder, head, head_ptr);
+
+            let tail_ptr = LLVMBuildStructGEP(self.builder, cell, 1, c_str!(""));
+            LLVMBuildStore(self.builder, tail, tail_ptr);
+        }
+    }
+
+    /// Loads the head or the tail of the list. The program exits when the list is empty.
+    pub(super) fn build_list_access(
+        &self,
+        op: &Opcode,
+        list: LLVMValueRef,
+    ) -> Result<LLVMValueRef> {
+        let (index, operation) = match op {
+            Opcode::Head => (0, "head"),
+            Opcode::Tail => (1, "tail"),
+            _ => bail!("Opcode {:?} cannot be applied to lists", op),
+        };
+
+        unsafe {
+            let function = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+            let ok = LLVMAppendBasicBlockInContext(self.context, function, c_str!("list.ok"));
+            let empty = LLVMAppendBasicBlockInContext(self.context, function, c_str!("list.empty"));
+
+            let is_empty = LLVMBuildIsNull(self.builder, list, c_str!(""));
+            LLVMBuildCondBr(self.builder, is_empty, empty, ok);
+
+            LLVMPositionBuilderAtEnd(self.builder, empty);
+            let abort = self.get_runtime_function(EMPTY_LIST, None, &[self.get_ptr_type()]);
+            let mut args = [self.build_c_string("operation", operation)];
+            LLVMBuildCall(
+                self.builder,
+                abort,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+            LLVMBuildUnreachable(self.builder);
+
+            LLVMPositionBuilderAtEnd(self.builder, ok);
+            let ptr = LLVMBuildStructGEP(self.builder, list, index, c_str!(""));
+
+            Ok(LLVMBuildLoad(self.builder, ptr, c_str!(operation)))
+        }
+    }
+}
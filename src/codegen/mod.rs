@@ -1,46 +1,100 @@
+mod arrays;
+mod closures;
+mod debug;
+mod emit;
+mod enums;
+mod gc;
+mod jit;
+mod lists;
+mod optimize;
+mod runtime;
+mod strings;
+mod target;
+mod translation;
+
+pub use debug::DebugInfo;
+pub use emit::EmitKind;
+pub use gc::GcStrategy;
+pub use optimize::OptLevel;
+pub use target::Target;
+
 use crate::symbol_table::*;
 use llvm_sys::core::*;
 
-use anyhow::Context;
 use std::collections::HashMap;
 
-use crate::ast::Identifier;
-use crate::c_str;
+use crate::ast::{DataType, Enum, Identifier};
+use crate::source_map::SourceMap;
+use anyhow::Result;
 use llvm_sys::prelude::*;
+use llvm_sys::target::{LLVMDisposeTargetData, LLVMSetModuleDataLayout};
+use llvm_sys::target_machine::{
+    LLVMCreateTargetDataLayout, LLVMDisposeTargetMachine, LLVMTargetMachineRef,
+};
 
 pub struct Codegen {
     pub context: LLVMContextRef,
     pub module: LLVMModuleRef,
     pub builder: LLVMBuilderRef,
+    pub target: Target,
+    pub opt_level: OptLevel,
     pub symbol_tables: HashMap<Identifier, LLVMSymbolTable>,
     pub function_table: LLVMFunctionTable,
     pub block_table: LLVMBlockTable,
     pub struct_table: LLVMStructTable,
-    pub expr_tables: HashMap<Identifier, LLVMExprTable>,
+    /// Enums of the program with the LLVM type of their tagged union.
+    pub enum_table: HashMap<String, (Enum, LLVMTypeRef)>,
+    /// Types of the values in the environments of the closures, which are
+    /// known when the closure is created.
+    pub closure_envs: HashMap<String, Vec<DataType>>,
+    pub debug_info: Option<DebugInfo>,
+    pub gc: GcStrategy,
+    /// Variables of the current function, which the collector has to trace.
+    pub gc_roots: Vec<LLVMValueRef>,
+    /// Files of the program, which locate the errors at runtime.
+    pub source_map: SourceMap,
+    pub unchecked_indexing: bool,
 }
 
 impl Codegen {
-    pub fn new(context: LLVMContextRef, module: LLVMModuleRef, builder: LLVMBuilderRef) -> Codegen {
+    pub fn new(
+        context: LLVMContextRef,
+        module: LLVMModuleRef,
+        builder: LLVMBuilderRef,
+        target: Target,
+    ) -> Result<Codegen> {
         unsafe {
-            LLVMSetTarget(module, c_str!("x86_64-unknown-linux-gnu"));
+            let target_machine = target.create_target_machine(OptLevel::O0)?;
+
+            LLVMSetTarget(module, target.get_triple().as_ptr());
+            let data_layout = LLVMCreateTargetDataLayout(target_machine);
+            LLVMSetModuleDataLayout(module, data_layout);
+            LLVMDisposeTargetData(data_layout);
+            LLVMDisposeTargetMachine(target_machine);
 
-            Codegen {
+            Ok(Codegen {
                 context,
                 module,
                 builder,
+                target,
+                opt_level: OptLevel::O0,
                 symbol_tables: HashMap::default(),
                 function_table: LLVMFunctionTable::default(),
                 block_table: LLVMBlockTable::default(),
                 struct_table: LLVMStructTable::default(),
-                expr_tables: HashMap::default(),
-            }
+                enum_table: HashMap::default(),
+                closure_envs: HashMap::default(),
+                debug_info: None,
+                gc: GcStrategy::None,
+                gc_roots: Vec::new(),
+                source_map: SourceMap::default(),
+                unchecked_indexing: false,
+            })
         }
     }
 
-    pub fn clear_expr_table(&mut self, function: &Identifier) -> anyhow::Result<()> {
-        self.expr_tables
-            .get_mut(function)
-            .with_context(|| "Cannot find expr table".to_string())?
-            .clear()
+    /// The caller has to dispose the target machine.
+    pub fn create_target_machine(&self) -> Result<LLVMTargetMachineRef> {
+        self.target.create_target_machine(self.opt_level)
     }
 }
@@ -0,0 +1,89 @@
+use super::Codegen;
+use crate::utils::LLVMString;
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use llvm_sys::error::{LLVMDisposeErrorMessage, LLVMGetErrorMessage};
+use llvm_sys::target_machine::{LLVMCodeGenOptLevel, LLVMDisposeTargetMachine};
+use llvm_sys::transforms::pass_builder::*;
+use log::info;
+use std::ffi::CString;
+
+/// The optimization level of the LLVM pass pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OptLevel {
+    #[value(name = "0")]
+    O0,
+    #[value(name = "1")]
+    O1,
+    #[value(name = "2")]
+    O2,
+    #[value(name = "3")]
+    O3,
+    /// Optimize for size
+    #[value(name = "s")]
+    Os,
+}
+
+impl OptLevel {
+    /// The pipeline of the new pass manager.
+    fn get_pipeline(&self) -> &'static str {
+        match self {
+            OptLevel::O0 => "default<O0>",
+            OptLevel::O1 => "default<O1>",
+            OptLevel::O2 => "default<O2>",
+            OptLevel::O3 => "default<O3>",
+            OptLevel::Os => "default<Os>",
+        }
+    }
+
+    /// The optimization level of the code generator.
+    pub fn get_codegen_level(&self) -> LLVMCodeGenOptLevel {
+        match self {
+            OptLevel::O0 => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+            OptLevel::O1 => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+            OptLevel::O2 | OptLevel::Os => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+            OptLevel::O3 => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
+        }
+    }
+
+    /// Vectorization and unrolling are only enabled when optimizing for speed.
+    fn optimizes_for_speed(&self) -> bool {
+        matches!(self, OptLevel::O2 | OptLevel::O3)
+    }
+}
+
+impl Codegen {
+    /// Runs the pass pipeline of the given level on the module.
+    /// The level is also used by the code generator when emitting the module.
+    pub fn optimize(&mut self, level: OptLevel) -> Result<()> {
+        info!("=> Running pass pipeline {}", level.get_pipeline());
+
+        self.opt_level = level;
+
+        let pipeline = CString::new(level.get_pipeline())?;
+        let target_machine = self.create_target_machine()?;
+
+        unsafe {
+            let options = LLVMCreatePassBuilderOptions();
+            let speed = level.optimizes_for_speed() as i32;
+            LLVMPassBuilderOptionsSetLoopVectorization(options, speed);
+            LLVMPassBuilderOptionsSetSLPVectorization(options, speed);
+            LLVMPassBuilderOptionsSetLoopUnrolling(options, speed);
+
+            let error = LLVMRunPasses(self.module, pipeline.as_ptr(), target_machine, options);
+
+            LLVMDisposePassBuilderOptions(options);
+            LLVMDisposeTargetMachine(target_machine);
+
+            if !error.is_null() {
+                let message = LLVMGetErrorMessage(error);
+                let description = LLVMString::new(message).to_string();
+                LLVMDisposeErrorMessage(message);
+
+                bail!("Running the pass pipeline failed: {}", description);
+            }
+        }
+
+        Ok(())
+    }
+}
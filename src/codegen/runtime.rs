@@ -0,0 +1,130 @@
+use super::Codegen;
+use crate::c_str;
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::support::LLVMAddSymbol;
+use std::sync::Once;
+
+/// Allocates zeroed memory on the heap.
+pub const ALLOC: &str = "mill_alloc";
+/// Allocates a struct, which is managed by the garbage collector.
+pub const GC_ALLOC: &str = "mill_gc_alloc";
+/// Registers the roots of a function.
+pub const GC_PUSH_FRAME: &str = "mill_gc_push_frame";
+/// Removes the roots of a function before it returns.
+pub const GC_POP_FRAME: &str = "mill_gc_pop_frame";
+/// Writes a string to stdout.
+pub const PRINT: &str = "mill_print";
+/// Compares the bytes of two strings.
+pub const STR_EQ: &str = "mill_str_eq";
+/// Aborts the program, because an index is out of bounds.
+pub const INDEX_OUT_OF_BOUNDS: &str = "mill_index_out_of_bounds";
+/// Aborts the program, because `head` or `tail` was applied to the empty list.
+pub const EMPTY_LIST: &str = "mill_empty_list";
+/// Parses the arguments of the command line of an executable for `main`.
+pub const PARSE_ARGS: &str = "mill_parse_args";
+
+/// The static library of the runtime, which is compiled by `build.rs`.
+/// It is written next to the object file when linking an executable.
+pub const ARCHIVE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/libmill_runtime.a"));
+
+static REGISTER_SYMBOLS: Once = Once::new();
+
+extern "C" {
+    fn mill_alloc(size: u64) -> *mut u8;
+    fn mill_gc_alloc(ty: *const u8) -> *mut u8;
+    fn mill_gc_push_frame(frame: *mut u8);
+    fn mill_gc_pop_frame(frame: *mut u8);
+    fn mill_print(data: *const u8, len: u64);
+    fn mill_str_eq(a: *const u8, a_len: u64, b: *const u8, b_len: u64) -> i32;
+    fn mill_index_out_of_bounds(index: i64, len: u64, location: *const u8);
+    fn mill_empty_list(operation: *const u8);
+}
+
+/// Makes the functions of the runtime available to the JIT.
+pub fn register_symbols() {
+    REGISTER_SYMBOLS.call_once(|| unsafe {
+        LLVMAddSymbol(c_str!(ALLOC), mill_alloc as *mut libc::c_void);
+        LLVMAddSymbol(c_str!(GC_ALLOC), mill_gc_alloc as *mut libc::c_void);
+        LLVMAddSymbol(
+            c_str!(GC_PUSH_FRAME),
+            mill_gc_push_frame as *mut libc::c_void,
+        );
+        LLVMAddSymbol(c_str!(GC_POP_FRAME), mill_gc_pop_frame as *mut libc::c_void);
+        LLVMAddSymbol(c_str!(PRINT), mill_print as *mut libc::c_void);
+        LLVMAddSymbol(c_str!(STR_EQ), mill_str_eq as *mut libc::c_void);
+        LLVMAddSymbol(
+            c_str!(INDEX_OUT_OF_BOUNDS),
+            mill_index_out_of_bounds as *mut libc::c_void,
+        );
+        LLVMAddSymbol(c_str!(EMPTY_LIST), mill_empty_list as *mut libc::c_void);
+    });
+}
+
+impl Codegen {
+    /// Returns the declaration of a function of the runtime, which is
+    /// added to the module when it is used first.
+    /// Every pointer of the runtime is passed as `i8*`.
+    pub(super) fn get_runtime_function(
+        &self,
+        name: &str,
+        ret_ty: Option<LLVMTypeRef>,
+        args_ty: &[LLVMTypeRef],
+    ) -> LLVMValueRef {
+        unsafe {
+            let function = LLVMGetNamedFunction(self.module, c_str!(name));
+            if !function.is_null() {
+                return function;
+            }
+
+            let ret_ty = ret_ty.unwrap_or_else(|| LLVMVoidTypeInContext(self.context));
+            let mut args_ty = args_ty.to_vec();
+            let function_ty =
+                LLVMFunctionType(ret_ty, args_ty.as_mut_ptr(), args_ty.len() as u32, 0);
+
+            LLVMAddFunction(self.module, c_str!(name), function_ty)
+        }
+    }
+
+    /// Returns the text as constant C string, which ends with a null byte.
+    pub(super) fn build_c_string(&self, name: &str, text: &str) -> LLVMValueRef {
+        unsafe {
+            let bytes = LLVMConstStringInContext(
+                self.context,
+                text.as_ptr() as *const i8,
+                text.len() as u32,
+                0,
+            );
+            let global = self.add_constant(name, bytes);
+            LLVMSetUnnamedAddress(global, llvm_sys::LLVMUnnamedAddr::LLVMGlobalUnnamedAddr);
+
+            LLVMConstBitCast(global, self.get_ptr_type())
+        }
+    }
+
+    /// Returns the declaration of `mill_alloc`.
+    pub(super) fn get_alloc_function(&self) -> LLVMValueRef {
+        unsafe {
+            let ptr_ty = LLVMPointerType(LLVMInt8TypeInContext(self.context), 0);
+            let size_ty = LLVMInt64TypeInContext(self.context);
+
+            self.get_runtime_function(ALLOC, Some(ptr_ty), &[size_ty])
+        }
+    }
+
+    /// Allocates a zeroed value of type `ty` on the heap, which is never freed.
+    pub(super) fn build_heap_alloc(&self, ty: LLVMTypeRef, name: &str) -> LLVMValueRef {
+        unsafe {
+            let mut args = [LLVMSizeOf(ty)];
+            let ptr = LLVMBuildCall(
+                self.builder,
+                self.get_alloc_function(),
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+
+            LLVMBuildBitCast(self.builder, ptr, LLVMPointerType(ty, 0), c_str!(name))
+        }
+    }
+}
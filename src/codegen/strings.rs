@@ -0,0 +1,188 @@
+use super::runtime::{PRINT, STR_EQ};
+use super::Codegen;
+use crate::ast::{DataType, Identifier, Opcode};
+use crate::c_str;
+use crate::symbol_table::FunctionSignature;
+use anyhow::{bail, Result};
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::{LLVMIntPredicate, LLVMTypeKind};
+
+/// The type of `str`, which is a pointer to the bytes and their length.
+const STR: &str = "mill.str";
+
+/// `len(s)` returns the number of bytes of the string or the number of elements of the array.
+const LEN_BUILTIN: &str = "len";
+/// `print(s)` writes the string to stdout.
+const PRINT_BUILTIN: &str = "print";
+
+/// Returns the signature of a built-in function for the types of the arguments.
+/// A function of the program hides the built-in function with the same name.
+pub(super) fn get_builtin_signature(
+    name: &str,
+    arguments_ty: &[DataType],
+) -> Option<FunctionSignature> {
+    match name {
+        LEN_BUILTIN => {
+            let ty = match arguments_ty {
+                [ty @ DataType::Array(_, _)] => ty.clone(),
+                _ => DataType::Str,
+            };
+
+            Some(FunctionSignature::new(vec![ty], Some(DataType::Int)))
+        }
+        PRINT_BUILTIN => Some(FunctionSignature::new(vec![DataType::Str], None)),
+        _ => None,
+    }
+}
+
+/**
+ * Code for strings and the built-in functions.
+ *
+ * String literals are private constants of the module. A `str` is passed
+ * by value and never owns its bytes, so it is not traced by the collector.
+ */
+impl Codegen {
+    pub(super) fn get_str_type(&self) -> LLVMTypeRef {
+        unsafe {
+            let ty = LLVMGetTypeByName2(self.context, c_str!(STR));
+            if !ty.is_null() {
+                return ty;
+            }
+
+            let ty = LLVMStructCreateNamed(self.context, c_str!(STR));
+            let mut fields_ty = [self.get_ptr_type(), LLVMInt64TypeInContext(self.context)];
+            LLVMStructSetBody(ty, fields_ty.as_mut_ptr(), fields_ty.len() as u32, 0);
+
+            ty
+        }
+    }
+
+    /// Emits the bytes of the literal as a constant and returns the `str` of it.
+    pub(super) fn build_str_constant(&self, value: &str) -> LLVMValueRef {
+        unsafe {
+            let bytes = LLVMConstStringInContext(
+                self.context,
+                value.as_ptr() as *const i8,
+                value.len() as u32,
+                1,
+            );
+            let global = self.add_constant("str", bytes);
+            LLVMSetUnnamedAddress(global, llvm_sys::LLVMUnnamedAddr::LLVMGlobalUnnamedAddr);
+
+            let mut fields = [
+                LLVMConstBitCast(global, self.get_ptr_type()),
+                LLVMConstInt(LLVMInt64TypeInContext(self.context), value.len() as u64, 0),
+            ];
+
+            LLVMConstNamedStruct(self.get_str_type(), fields.as_mut_ptr(), 2)
+        }
+    }
+
+    /// Strings are equal when they have the same bytes.
+    pub(super) fn build_str_comparison(
+        &self,
+        op: &Opcode,
+        a: LLVMValueRef,
+        b: LLVMValueRef,
+    ) -> Result<LLVMValueRef> {
+        let predicate = match op {
+            Opcode::Cmp => LLVMIntPredicate::LLVMIntNE,
+            Opcode::Neq => LLVMIntPredicate::LLVMIntEQ,
+            _ => bail!("Opcode {:?} cannot be applied to strings", op),
+        };
+
+        unsafe {
+            let int_ty = LLVMInt64TypeInContext(self.context);
+            let function = self.get_runtime_function(
+                STR_EQ,
+                Some(LLVMInt32TypeInContext(self.context)),
+                &[self.get_ptr_type(), int_ty, self.get_ptr_type(), int_ty],
+            );
+
+            let mut args = [
+                LLVMBuildExtractValue(self.builder, a, 0, c_str!("")),
+                LLVMBuildExtractValue(self.builder, a, 1, c_str!("")),
+                LLVMBuildExtractValue(self.builder, b, 0, c_str!("")),
+                LLVMBuildExtractValue(self.builder, b, 1, c_str!("")),
+            ];
+            let equal = LLVMBuildCall(
+                self.builder,
+                function,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(""),
+            );
+
+            Ok(LLVMBuildICmp(
+                self.builder,
+                predicate,
+                equal,
+                LLVMConstInt(LLVMInt32TypeInContext(self.context), 0, 0),
+                c_str!(""),
+            ))
+        }
+    }
+
+    /// Returns a pointer to the byte at the index, which is an `i64`.
+    /// The program exits when the index is out of bounds.
+    pub(super) fn build_str_element_ptr(
+        &self,
+        value: LLVMValueRef,
+        index: LLVMValueRef,
+        pos: usize,
+    ) -> LLVMValueRef {
+        unsafe {
+            let len = LLVMBuildExtractValue(self.builder, value, 1, c_str!(""));
+            self.build_bounds_check(index, len, pos);
+
+            let ptr = LLVMBuildExtractValue(self.builder, value, 0, c_str!(""));
+            let mut indices = [index];
+
+            LLVMBuildGEP(self.builder, ptr, indices.as_mut_ptr(), 1, c_str!(""))
+        }
+    }
+
+    /// Calls the built-in function, whose arguments were already checked.
+    pub(super) fn translate_builtin(
+        &self,
+        id: &Identifier,
+        args: &mut [LLVMValueRef],
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            match id.get_name().as_str() {
+                LEN_BUILTIN => {
+                    let ty = LLVMTypeOf(args[0]);
+
+                    // The length of an array is part of its type.
+                    if LLVMGetTypeKind(ty) == LLVMTypeKind::LLVMArrayTypeKind {
+                        let len = LLVMGetArrayLength(ty) as u64;
+                        return Ok(LLVMConstInt(LLVMInt64TypeInContext(self.context), len, 0));
+                    }
+
+                    Ok(LLVMBuildExtractValue(self.builder, args[0], 1, c_str!("")))
+                }
+                PRINT_BUILTIN => {
+                    let function = self.get_runtime_function(
+                        PRINT,
+                        None,
+                        &[self.get_ptr_type(), LLVMInt64TypeInContext(self.context)],
+                    );
+                    let mut args = [
+                        LLVMBuildExtractValue(self.builder, args[0], 0, c_str!("")),
+                        LLVMBuildExtractValue(self.builder, args[0], 1, c_str!("")),
+                    ];
+
+                    Ok(LLVMBuildCall(
+                        self.builder,
+                        function,
+                        args.as_mut_ptr(),
+                        args.len() as u32,
+                        c_str!(""),
+                    ))
+                }
+                _ => bail!("Function {} is not defined", id),
+            }
+        }
+    }
+}
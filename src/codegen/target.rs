@@ -0,0 +1,164 @@
+use super::OptLevel;
+use crate::c_str;
+use crate::utils::LLVMString;
+use anyhow::{bail, Context, Result};
+use llvm_sys::target::*;
+use llvm_sys::target_machine::*;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::Once;
+
+static INITIALIZE_TARGETS: Once = Once::new();
+
+// The wrapper of `llvm-wrapper/target.cpp`, which is compiled by `build.rs`.
+extern "C" {
+    fn mill_target_has_cpu(machine: LLVMTargetMachineRef, cpu: *const c_char) -> i32;
+    fn mill_target_has_feature(machine: LLVMTargetMachineRef, feature: *const c_char) -> i32;
+}
+
+/// The machine which the code is generated for.
+#[derive(Debug, Clone)]
+pub struct Target {
+    triple: CString,
+    cpu: CString,
+    features: CString,
+}
+
+impl Target {
+    /// Creates a target for the given triple. When no triple is given,
+    /// then the triple of the host is used.
+    pub fn new(triple: Option<&str>, cpu: Option<&str>, features: &[String]) -> Result<Self> {
+        initialize_targets();
+
+        let triple = unsafe {
+            match triple {
+                Some(triple) => {
+                    let triple = CString::new(triple).context("Invalid target triple")?;
+                    LLVMString::new(LLVMNormalizeTargetTriple(triple.as_ptr()))
+                }
+                None => LLVMString::new(LLVMGetDefaultTargetTriple()),
+            }
+        };
+
+        let target = Self {
+            triple: CString::from(&*triple),
+            cpu: CString::new(cpu.unwrap_or("")).context("Invalid target cpu")?,
+            features: CString::new(features.join(",")).context("Invalid target features")?,
+        };
+
+        // Fails when LLVM does not support the triple.
+        target.get_llvm_target()?;
+        target.check_cpu_and_features(features)?;
+
+        Ok(target)
+    }
+
+    pub fn get_triple(&self) -> &CString {
+        &self.triple
+    }
+
+    /// Returns true when the code runs on the machine of the compiler.
+    pub fn is_host(&self) -> bool {
+        unsafe {
+            let host = LLVMString::new(LLVMGetDefaultTargetTriple());
+            let host = LLVMString::new(LLVMNormalizeTargetTriple(host.as_ptr()));
+
+            self.triple.as_c_str() == &*host
+        }
+    }
+
+    fn get_llvm_target(&self) -> Result<LLVMTargetRef> {
+        unsafe {
+            let mut target = std::ptr::null_mut();
+            let mut message = std::ptr::null_mut();
+
+            if LLVMGetTargetFromTriple(self.triple.as_ptr(), &mut target, &mut message) != 0 {
+                bail!(
+                    "Target {} is not supported: {}",
+                    self.triple.to_string_lossy(),
+                    LLVMString::new(message)
+                );
+            }
+
+            Ok(target)
+        }
+    }
+
+    /// Fails when the target has not the cpu or a feature. LLVM would only warn
+    /// about them, or abort when it generates code.
+    fn check_cpu_and_features(&self, features: &[String]) -> Result<()> {
+        let target = self.get_llvm_target()?;
+
+        unsafe {
+            // The machine for the lookups has the default cpu, so it does not warn.
+            let machine = LLVMCreateTargetMachine(
+                target,
+                self.triple.as_ptr(),
+                c_str!(""),
+                c_str!(""),
+                LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+                LLVMRelocMode::LLVMRelocDefault,
+                LLVMCodeModel::LLVMCodeModelDefault,
+            );
+            let result = self.check_with_machine(machine, features);
+            LLVMDisposeTargetMachine(machine);
+
+            result
+        }
+    }
+
+    unsafe fn check_with_machine(
+        &self,
+        machine: LLVMTargetMachineRef,
+        features: &[String],
+    ) -> Result<()> {
+        let triple = self.triple.to_string_lossy();
+
+        if !self.cpu.as_bytes().is_empty() && mill_target_has_cpu(machine, self.cpu.as_ptr()) == 0 {
+            bail!(
+                "Cpu {} is not supported by target {}",
+                self.cpu.to_string_lossy(),
+                triple
+            );
+        }
+
+        for feature in features {
+            let name = match feature.strip_prefix(['+', '-']) {
+                Some(name) => CString::new(name).context("Invalid target feature")?,
+                None => bail!("Target feature {} has to start with + or -", feature),
+            };
+            if mill_target_has_feature(machine, name.as_ptr()) == 0 {
+                bail!("Feature {} is not supported by target {}", feature, triple);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The caller has to dispose the target machine.
+    pub fn create_target_machine(&self, opt_level: OptLevel) -> Result<LLVMTargetMachineRef> {
+        let target = self.get_llvm_target()?;
+
+        unsafe {
+            Ok(LLVMCreateTargetMachine(
+                target,
+                self.triple.as_ptr(),
+                self.cpu.as_ptr(),
+                self.features.as_ptr(),
+                opt_level.get_codegen_level(),
+                LLVMRelocMode::LLVMRelocPIC,
+                LLVMCodeModel::LLVMCodeModelDefault,
+            ))
+        }
+    }
+}
+
+fn initialize_targets() {
+    INITIALIZE_TARGETS.call_once(|| unsafe {
+        LLVM_InitializeAllTargetInfos();
+        LLVM_InitializeAllTargets();
+        LLVM_InitializeAllTargetMCs();
+        LLVM_InitializeAllAsmPrinters();
+        LLVM_InitializeAllAsmParsers();
+    });
+}
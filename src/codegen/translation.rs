@@ -0,0 +1,1539 @@
+use super::strings::get_builtin_signature;
+use super::Codegen;
+use crate::ast::{DataType, Identifier, Literal, Opcode};
+use crate::ast::{Enum, Struct};
+use crate::c_str;
+use crate::lir::tree::*;
+use crate::symbol_table::{BasicValue, BasicValueType, FunctionSignature, LLVMSymbolTable};
+use anyhow::{bail, Context, Result};
+use llvm_sys::core::*;
+use llvm_sys::prelude::*;
+use llvm_sys::{LLVMIntPredicate, LLVMOpcode, LLVMRealPredicate};
+
+/**
+ * Translates the lowered program into LLVM IR.
+ */
+impl Codegen {
+    pub fn translate(&mut self, program: &LoweredProgram) -> Result<()> {
+        self.declare_types(program.get_structs(), program.get_enums())?;
+        // Enums are declared first, because fields of structs can refer to them.
+        self.declare_debug_enums(program.get_enums())?;
+        self.declare_debug_structs(program.get_structs())?;
+
+        // All functions are declared first, because calls can refer
+        // to functions which are defined later.
+        for function in program.get_entries() {
+            self.declare_function(function)
+                .with_context(|| format!("Cannot declare function {}", function.id))?;
+        }
+
+        for function in program.get_entries() {
+            self.translate_function(function)
+                .with_context(|| format!("Cannot translate function {}", function.id))?;
+        }
+
+        self.finalize_debug_info();
+
+        Ok(())
+    }
+
+    pub(super) fn get_llvm_type(&self, ty: &DataType) -> Result<LLVMTypeRef> {
+        unsafe {
+            match ty {
+                DataType::F32 => Ok(LLVMFloatTypeInContext(self.context)),
+                DataType::F64 => Ok(LLVMDoubleTypeInContext(self.context)),
+                DataType::Str => Ok(self.get_str_type()),
+                DataType::Array(element, len) => {
+                    Ok(LLVMArrayType(self.get_llvm_type(element)?, *len as u32))
+                }
+                // Structs are passed by reference.
+                DataType::Struct(name) => Ok(LLVMPointerType(self.get_struct(name)?.1, 0)),
+                DataType::Enum(name) => Ok(LLVMPointerType(self.get_enum(name)?.1, 0)),
+                DataType::List(element) => {
+                    Ok(LLVMPointerType(self.get_list_cell_type(element)?, 0))
+                }
+                DataType::Function(_, _) => Ok(self.get_closure_type()),
+                ty => Ok(LLVMIntTypeInContext(
+                    self.context,
+                    ty.get_bits().expect("Integers have a size"),
+                )),
+            }
+        }
+    }
+
+    /// Creates the LLVM types of the structs and enums. All types are created
+    /// before their fields are set, because fields can refer to other types.
+    fn declare_types(&mut self, structs: &[Struct], enums: &[Enum]) -> Result<()> {
+        unsafe {
+            for s in structs {
+                let ty = LLVMStructCreateNamed(self.context, c_str!(s.name));
+                self.struct_table
+                    .insert(s.name.get_name(), (s.clone(), ty))?;
+            }
+
+            for e in enums {
+                let ty = LLVMStructCreateNamed(self.context, c_str!(e.name));
+                self.enum_table
+                    .insert(e.name.get_name().clone(), (e.clone(), ty));
+            }
+
+            for s in structs {
+                let mut fields_ty = s
+                    .fields
+                    .iter()
+                    .map(|field| self.get_llvm_type(&field.ty))
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("Cannot declare struct {}", s.name))?;
+
+                let ty = self.get_struct(&s.name)?.1;
+                LLVMStructSetBody(ty, fields_ty.as_mut_ptr(), fields_ty.len() as u32, 0);
+            }
+        }
+
+        for e in enums {
+            self.define_enum(e)
+                .with_context(|| format!("Cannot declare enum {}", e.name))?;
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn get_struct(&self, name: &Identifier) -> Result<&(Struct, LLVMTypeRef)> {
+        self.struct_table
+            .get(name.get_name())
+            .with_context(|| format!("Struct {} is not defined", name))
+    }
+
+    fn declare_function(&mut self, function: &LoweredFunction) -> Result<()> {
+        let signature = function.get_signature();
+
+        let mut args_ty = signature
+            .get_args_ty()
+            .iter()
+            .map(|ty| self.get_llvm_type(ty))
+            .collect::<Result<Vec<_>>>()?;
+        // Closures get their environment first.
+        if function.captures.is_some() {
+            args_ty.insert(0, self.get_ptr_type());
+        }
+
+        unsafe {
+            let ret_ty = match signature.get_ret_ty() {
+                Some(ty) => self.get_llvm_type(ty)?,
+                None => LLVMVoidTypeInContext(self.context),
+            };
+
+            let function_ty =
+                LLVMFunctionType(ret_ty, args_ty.as_mut_ptr(), args_ty.len() as u32, 0);
+            let value = LLVMAddFunction(self.module, c_str!(function.id), function_ty);
+
+            self.function_table
+                .insert(function.id.get_name(), signature, value)?;
+
+            self.declare_debug_function(function, value)
+        }
+    }
+
+    fn translate_function(&mut self, function: &LoweredFunction) -> Result<()> {
+        let llvm_function = self
+            .function_table
+            .get(function.id.get_name())
+            .with_context(|| format!("Function {} was not declared", function.id))?
+            .1;
+
+        self.block_table.clear();
+        self.gc_roots.clear();
+        self.symbol_tables
+            .insert(function.id.clone(), LLVMSymbolTable::default());
+
+        unsafe {
+            // The blocks are created upfront, because jumps can refer
+            // to blocks which have not been translated yet.
+            for block in function.blocks.iter() {
+                let name = format!("b{}", block.get_id().get_value());
+                let llvm_block =
+                    LLVMAppendBasicBlockInContext(self.context, llvm_function, c_str!(name));
+                self.block_table.insert(&name, llvm_block)?;
+            }
+
+            let entry = self.get_block(&function.entry)?;
+            LLVMPositionBuilderAtEnd(self.builder, entry);
+            self.enter_debug_function(function)?;
+
+            let offset = function.captures.is_some() as u32;
+            for (i, par) in function.pars.iter().enumerate() {
+                let value = LLVMGetParam(llvm_function, i as u32 + offset);
+                let ty = LLVMTypeOf(value);
+                let ptr = self.build_entry_alloca(llvm_function, par.get_name(), ty)?;
+
+                LLVMBuildStore(self.builder, value, ptr.value);
+                if par.ty.as_ref().is_some_and(DataType::has_references) {
+                    self.add_gc_root(ptr.value);
+                }
+                self.declare_debug_variable(par, Some(i as u32 + 1), ptr.value)?;
+                self.get_symbol_table(function)?
+                    .insert(par.get_name(), (par.clone(), ptr))?;
+            }
+
+            // The captured values are copied into variables of the closure.
+            if let Some(captures) = function.captures.as_ref().filter(|x| !x.is_empty()) {
+                let env = LLVMGetParam(llvm_function, 0);
+                let values = self.load_captures(&function.id, env)?;
+
+                for (capture, (ty, value)) in captures.iter().zip(values) {
+                    let id = capture.clone().update_ty(ty.clone());
+                    let ptr =
+                        self.build_entry_alloca(llvm_function, id.get_name(), LLVMTypeOf(value))?;
+
+                    LLVMBuildStore(self.builder, value, ptr.value);
+                    if ty.has_references() {
+                        self.add_gc_root(ptr.value);
+                    }
+                    self.declare_debug_variable(&id, None, ptr.value)?;
+                    self.get_symbol_table(function)?
+                        .insert(id.get_name(), (id.clone(), ptr))?;
+                }
+            }
+
+            // A function with a return type must not fall off its end. Only the blocks
+            // after a `return`, `break` or `continue` can end without a terminator.
+            let reachable = function.get_reachable_blocks();
+            for block in function.blocks.iter() {
+                LLVMPositionBuilderAtEnd(self.builder, self.get_block(block.get_id())?);
+
+                for stmt in block.get_statements() {
+                    self.translate_statement(function, llvm_function, stmt)?;
+                }
+
+                if !block.is_terminated() {
+                    if function.ret_ty.is_none() {
+                        LLVMBuildRetVoid(self.builder);
+                    } else if reachable.contains(block.get_id()) {
+                        bail!("Function {} may end without returning a value", function.id);
+                    } else {
+                        LLVMBuildUnreachable(self.builder);
+                    }
+                }
+            }
+        }
+
+        self.build_gc_frame(llvm_function)
+    }
+
+    fn translate_statement(
+        &mut self,
+        function: &LoweredFunction,
+        llvm_function: LLVMValueRef,
+        stmt: &LoweredStatement,
+    ) -> Result<()> {
+        unsafe {
+            match stmt {
+                LoweredStatement::Definition(variable, expr) => {
+                    self.set_debug_location(variable.get_ident())?;
+                    let ty = self.get_expected_expr_ty(
+                        function,
+                        expr,
+                        variable.get_ident().ty.as_ref(),
+                    )?;
+                    let value = self.translate_expected_expr(function, expr, &ty)?;
+
+                    if let Some(declared) = &variable.get_ident().ty {
+                        if *declared != ty {
+                            bail!(
+                                "Symbol {} has type {}, but the value has type {}",
+                                variable.get_ident(),
+                                declared,
+                                ty
+                            );
+                        }
+                    }
+                    let id = &variable.get_ident().clone().update_ty(ty.clone());
+
+                    // A variable can be defined again in its block, but only with its type.
+                    let ptr = match self.get_symbol_table(function)?.get_both(id.get_name()) {
+                        Some((existing, _)) if existing.ty.as_ref() != Some(&ty) => {
+                            bail!("Symbol {} is already defined with another type", id)
+                        }
+                        Some((_, ptr)) => ptr.clone(),
+                        None => {
+                            let ptr = self.build_entry_alloca(
+                                llvm_function,
+                                id.get_name(),
+                                LLVMTypeOf(value),
+                            )?;
+                            if ty.has_references() {
+                                self.add_gc_root(ptr.value);
+                            }
+                            if !variable.is_generated() {
+                                self.declare_debug_variable(id, None, ptr.value)?;
+                            }
+                            self.get_symbol_table(function)?
+                                .insert(id.get_name(), (id.clone(), ptr.clone()))?;
+                            ptr
+                        }
+                    };
+
+                    LLVMBuildStore(self.builder, value, ptr.value);
+                }
+                LoweredStatement::Assignment(variable, expr) => {
+                    self.set_debug_location(variable.get_ident())?;
+                    let expected = self.get_path_ty(function, variable.get_ident())?;
+                    let ty = self.get_expected_expr_ty(function, expr, Some(&expected))?;
+                    let value = self.translate_expected_expr(function, expr, &ty)?;
+                    let (ptr, _) = self.get_path(function, variable.get_ident())?;
+
+                    if expected != ty {
+                        bail!(
+                            "Cannot assign a value of type {} to {} of type {}",
+                            ty,
+                            variable.get_ident(),
+                            expected
+                        );
+                    }
+
+                    LLVMBuildStore(self.builder, value, ptr);
+                }
+                LoweredStatement::IndexAssignment(variable, subscripts, expr) => {
+                    self.set_debug_location(variable.get_ident())?;
+                    let place = LoweredTerm::Id(variable.clone());
+                    let expected = self.get_index_ty(function, &place, subscripts)?;
+                    let ty = self.get_expected_expr_ty(function, expr, Some(&expected))?;
+
+                    let container =
+                        self.get_index_ty(function, &place, &subscripts[..subscripts.len() - 1])?;
+                    if container == DataType::Str {
+                        bail!(
+                            "Cannot assign to the bytes of {}, because strings are immutable",
+                            variable.get_ident()
+                        );
+                    }
+                    if expected != ty {
+                        bail!(
+                            "Cannot assign a value of type {} to an element of {} of type {}",
+                            ty,
+                            variable.get_ident(),
+                            expected
+                        );
+                    }
+
+                    let value = self.translate_expected_expr(function, expr, &ty)?;
+                    let ptr = self.get_element_ptr(function, &place, subscripts)?;
+
+                    LLVMBuildStore(self.builder, value, ptr);
+                }
+                LoweredStatement::UnconditionalJump(block) => {
+                    LLVMBuildBr(self.builder, self.get_block(block)?);
+                }
+                LoweredStatement::ConditionalJump(condition, then_block, else_block) => {
+                    let ty = self.get_expr_ty(function, condition)?;
+                    if ty != DataType::Bool {
+                        bail!("Condition must have type bool, but has type {}", ty);
+                    }
+
+                    let condition = self.translate_expr(function, condition)?;
+
+                    LLVMBuildCondBr(
+                        self.builder,
+                        condition,
+                        self.get_block(then_block)?,
+                        self.get_block(else_block)?,
+                    );
+                }
+                LoweredStatement::Ret(expr) => {
+                    if function.ret_ty.is_none() {
+                        bail!("Function {} cannot return a value", function.id);
+                    }
+
+                    let ty = self.get_expected_expr_ty(function, expr, function.ret_ty.as_ref())?;
+                    if function.ret_ty.as_ref() != Some(&ty) {
+                        bail!(
+                            "Function {} cannot return a value of type {}",
+                            function.id,
+                            ty
+                        );
+                    }
+
+                    let value = self.translate_expected_expr(function, expr, &ty)?;
+                    LLVMBuildRet(self.builder, value);
+                }
+                LoweredStatement::RetVoid => {
+                    if function.ret_ty.is_some() {
+                        bail!("Function {} has to return a value", function.id);
+                    }
+
+                    LLVMBuildRetVoid(self.builder);
+                }
+                LoweredStatement::Expression(expr) => {
+                    self.translate_expr(function, expr)?;
+                }
+                LoweredStatement::Switch(term, name, cases, default) => {
+                    let ty = self.get_term_ty(function, term)?;
+                    if !matches!(&ty, DataType::Enum(e) if e.get_name() == name.get_name()) {
+                        bail!(
+                            "Cannot match {} of type {} with the variants of {}",
+                            term,
+                            ty,
+                            name
+                        );
+                    }
+
+                    let value = self.translate_term(function, term)?;
+                    let cases = cases
+                        .iter()
+                        .map(|(tag, block)| Ok((*tag, self.get_block(block)?)))
+                        .collect::<Result<Vec<_>>>()?;
+
+                    self.build_switch(value, &cases, self.get_block(default)?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn translate_expr(
+        &mut self,
+        function: &LoweredFunction,
+        expr: &LoweredExpression,
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            match expr {
+                LoweredExpression::Term(term) => self.translate_term(function, term),
+                LoweredExpression::Binary(Opcode::Cons, a, b) => {
+                    let ty = self.get_cons_ty(function, a, b, None)?;
+                    self.translate_cons(function, a, b, &ty)
+                }
+                LoweredExpression::Binary(op, a, b) => {
+                    let ty = self.get_operands_ty(function, op, &[a, b])?;
+                    let a = self.translate_operand(function, a, &ty)?;
+                    let b = self.translate_operand(function, b, &ty)?;
+
+                    self.translate_binary(op, &ty, a, b)
+                }
+                LoweredExpression::Unary(op, a) => {
+                    let ty = self.get_operands_ty(function, op, &[a])?;
+                    let a = self.translate_operand(function, a, &ty)?;
+
+                    self.translate_unary(op, &ty, a)
+                }
+                LoweredExpression::Cast(a, ty) => {
+                    let from = self.get_term_ty(function, a)?;
+                    let value = self.translate_term(function, a)?;
+
+                    self.translate_cast(value, &from, ty)
+                }
+                LoweredExpression::Index(a, subscripts) => {
+                    self.get_index_ty(function, a, subscripts)?;
+                    let ptr = self.get_element_ptr(function, a, subscripts)?;
+
+                    Ok(LLVMBuildLoad(self.builder, ptr, c_str!("")))
+                }
+                LoweredExpression::Array(_) | LoweredExpression::ArrayRepeat(_, _) => {
+                    let ty = self.get_expr_ty(function, expr)?;
+                    self.translate_array(function, expr, &ty)
+                }
+                LoweredExpression::Call(id, arguments) => {
+                    self.translate_call(function, id, arguments)
+                }
+                LoweredExpression::MethodCall(method, arguments) => {
+                    let id = self.get_method_symbol(function, method, arguments)?;
+                    self.translate_call(function, &id, arguments)
+                }
+                LoweredExpression::Struct(name, Allocation::Stack) => {
+                    let llvm_function = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+                    if self.uses_gc() {
+                        return self.build_gc_stack_object(llvm_function, name);
+                    }
+
+                    let ty = self.get_struct(name)?.1;
+                    let ptr = self.build_entry_alloca(llvm_function, name.get_name(), ty)?;
+
+                    // The fields are zeroed every time `new` is evaluated.
+                    LLVMBuildStore(self.builder, LLVMConstNull(ty), ptr.value);
+
+                    Ok(ptr.value)
+                }
+                LoweredExpression::Struct(name, Allocation::Heap) => {
+                    if self.uses_gc() {
+                        return self.build_gc_alloc(name);
+                    }
+
+                    let ty = self.get_struct(name)?.1;
+
+                    // The runtime zeroes the memory.
+                    Ok(self.build_heap_alloc(ty, name.get_name()))
+                }
+                LoweredExpression::Variant(name, variant, fields) => {
+                    let (tag, fields_ty) = self.get_variant_ty(function, name, variant, fields)?;
+
+                    // The variant is allocated before the fields are loaded, because
+                    // the collector would not see them while it allocates.
+                    let (value, ptr) = self.build_variant(name, tag)?;
+                    for (i, (field, ty)) in fields.iter().zip(fields_ty.iter()).enumerate() {
+                        let field = self.translate_operand(function, field, ty)?;
+                        self.build_payload_store(ptr, i, field);
+                    }
+
+                    Ok(value)
+                }
+                LoweredExpression::Payload(term, tag, index) => {
+                    let name = self.get_payload_enum(function, term)?;
+                    let value = self.translate_term(function, term)?;
+
+                    self.build_payload_load(value, &name, *tag, *index)
+                }
+                LoweredExpression::Closure(lifted, captures) => {
+                    let captures = captures
+                        .iter()
+                        .map(|capture| self.get_path(function, capture.get_ident()))
+                        .collect::<Result<Vec<_>>>()?;
+
+                    self.build_closure(lifted, &captures)
+                }
+            }
+        }
+    }
+
+    /// Translates the call of the function, which is declared in the module, built in
+    /// or the value of a variable.
+    fn translate_call(
+        &mut self,
+        function: &LoweredFunction,
+        id: &Identifier,
+        arguments: &[LoweredExpression],
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            let (signature, callee) = self.get_function(function, id, arguments)?;
+
+            if signature.get_args_ty().len() != arguments.len() {
+                bail!(
+                    "Function {} expects {} arguments, but {} were given",
+                    id,
+                    signature.get_args_ty().len(),
+                    arguments.len()
+                );
+            }
+
+            for (i, (expected, argument)) in
+                signature.get_args_ty().iter().zip(arguments).enumerate()
+            {
+                let ty = self.get_expected_expr_ty(function, argument, Some(expected))?;
+                if *expected != ty {
+                    bail!(
+                        "Argument {} of function {} has type {}, but a value of type {} was given",
+                        i + 1,
+                        id,
+                        expected,
+                        ty
+                    );
+                }
+            }
+
+            let mut args = Vec::with_capacity(arguments.len());
+            let mut spilled = Vec::new();
+
+            for (i, argument) in arguments.iter().enumerate() {
+                let value =
+                    self.translate_expected_expr(function, argument, &signature.get_args_ty()[i])?;
+
+                // Structs must stay reachable while the other arguments are evaluated.
+                if self.uses_gc() && signature.get_args_ty()[i].has_references() {
+                    spilled.push((i, self.spill_gc_root(value)?));
+                }
+
+                args.push(value);
+            }
+
+            for (i, slot) in spilled {
+                args[i] = LLVMBuildLoad(self.builder, slot, c_str!(""));
+            }
+
+            self.set_debug_location(id)?;
+
+            // Calls to void functions must not have a name.
+            let name = match signature.get_ret_ty() {
+                Some(_) => id.get_name().as_str(),
+                None => "",
+            };
+
+            let llvm_function = match callee {
+                Callee::Function(llvm_function) => llvm_function,
+                Callee::Value(slot) => {
+                    return self.build_closure_call(slot, &signature, &args, name)
+                }
+                Callee::Builtin => return self.translate_builtin(id, &mut args),
+            };
+
+            Ok(LLVMBuildCall(
+                self.builder,
+                llvm_function,
+                args.as_mut_ptr(),
+                args.len() as u32,
+                c_str!(name),
+            ))
+        }
+    }
+
+    fn get_call_ty(
+        &mut self,
+        function: &LoweredFunction,
+        id: &Identifier,
+        arguments: &[LoweredExpression],
+    ) -> Result<DataType> {
+        self.get_function(function, id, arguments)?
+            .0
+            .get_ret_ty()
+            .cloned()
+            .with_context(|| format!("Function {} does not return a value", id))
+    }
+
+    /// Returns the qualified name of the method of the struct of the receiver,
+    /// which is the first argument.
+    fn get_method_symbol(
+        &mut self,
+        function: &LoweredFunction,
+        method: &Identifier,
+        arguments: &[LoweredExpression],
+    ) -> Result<Identifier> {
+        let receiver = arguments.first().context("Method call has no receiver")?;
+
+        let name = match self.get_expr_ty(function, receiver)? {
+            DataType::Struct(name) => name,
+            ty => bail!("Cannot call method {} on a value of type {}", method, ty),
+        };
+
+        let definition = self.get_struct(&name)?.0.get_method(method)?;
+        if !definition.has_self {
+            bail!(
+                "Function {}::{} has no self parameter and is called like {}::{}()",
+                name,
+                method,
+                name,
+                method
+            );
+        }
+
+        // The qualified name is located at the call for the debug information.
+        Ok(method.qualify(&name))
+    }
+
+    /// Returns the type of the value of the expression.
+    fn get_expr_ty(
+        &mut self,
+        function: &LoweredFunction,
+        expr: &LoweredExpression,
+    ) -> Result<DataType> {
+        match expr {
+            LoweredExpression::Term(term) => self.get_term_ty(function, term),
+            LoweredExpression::Binary(Opcode::Cons, a, b) => self.get_cons_ty(function, a, b, None),
+            LoweredExpression::Binary(op, a, b) => self.get_operator_ty(function, op, &[a, b]),
+            LoweredExpression::Unary(op, a) => self.get_operator_ty(function, op, &[a]),
+            LoweredExpression::Cast(a, ty) => {
+                let from = self.get_term_ty(function, a)?;
+                let castable = from == *ty
+                    || (from.is_numeric() && ty.is_numeric())
+                    || (from == DataType::Bool && ty.is_integer());
+
+                if !castable {
+                    bail!("Cannot cast {} of type {} to {}", a, from, ty);
+                }
+
+                Ok(ty.clone())
+            }
+            LoweredExpression::MethodCall(method, arguments) => {
+                let id = self.get_method_symbol(function, method, arguments)?;
+                self.get_call_ty(function, &id, arguments)
+            }
+            LoweredExpression::Call(id, arguments) => self.get_call_ty(function, id, arguments),
+            LoweredExpression::Index(a, subscripts) => self.get_index_ty(function, a, subscripts),
+            LoweredExpression::Array(elements) => {
+                let elements = elements.iter().collect::<Vec<_>>();
+                let ty = self.get_elements_ty(function, &elements, None)?;
+
+                Ok(DataType::Array(Box::new(ty), elements.len()))
+            }
+            LoweredExpression::ArrayRepeat(value, len) => {
+                let ty = self.get_elements_ty(function, &[value], None)?;
+
+                Ok(DataType::Array(Box::new(ty), *len))
+            }
+            LoweredExpression::Struct(name, _) => {
+                self.get_struct(name)?;
+                Ok(DataType::Struct(Box::new(name.clone())))
+            }
+            LoweredExpression::Variant(name, variant, fields) => {
+                self.get_variant_ty(function, name, variant, fields)?;
+                Ok(DataType::Enum(Box::new(name.clone())))
+            }
+            LoweredExpression::Payload(term, tag, index) => {
+                let name = self.get_payload_enum(function, term)?;
+                let e = &self.get_enum(&name)?.0;
+
+                e.variants
+                    .get(*tag)
+                    .and_then(|variant| variant.fields.get(*index))
+                    .cloned()
+                    .with_context(|| {
+                        format!("Enum {} has no field {} in variant {}", name, index, tag)
+                    })
+            }
+            LoweredExpression::Closure(lifted, _) => {
+                let (signature, _) = self
+                    .function_table
+                    .get(lifted.get_name())
+                    .with_context(|| format!("Closure {} was not declared", lifted))?;
+
+                Ok(get_function_ty(signature))
+            }
+        }
+    }
+
+    /// Returns the tag of the variant and the types of its fields, which the values must have.
+    /// Constants take the numeric type and `nil` takes the list type of their field.
+    fn get_variant_ty(
+        &mut self,
+        function: &LoweredFunction,
+        name: &Identifier,
+        variant: &Identifier,
+        fields: &[LoweredTerm],
+    ) -> Result<(usize, Vec<DataType>)> {
+        let (tag, fields_ty) = {
+            let (tag, v) = self.get_enum(name)?.0.get_variant(variant)?;
+            (tag, v.fields.clone())
+        };
+
+        if fields_ty.len() != fields.len() {
+            bail!(
+                "Variant {}::{} has {} fields, but {} values were given",
+                name,
+                variant,
+                fields_ty.len(),
+                fields.len()
+            );
+        }
+
+        for (i, (field, expected)) in fields.iter().zip(fields_ty.iter()).enumerate() {
+            let ty = match field {
+                LoweredTerm::Constant(_) if expected.is_numeric() => expected.clone(),
+                LoweredTerm::Nil if matches!(expected, DataType::List(_)) => expected.clone(),
+                field => self.get_term_ty(function, field)?,
+            };
+
+            if ty != *expected {
+                bail!(
+                    "Field {} of {}::{} has type {}, but a value of type {} was given",
+                    i + 1,
+                    name,
+                    variant,
+                    expected,
+                    ty
+                );
+            }
+        }
+
+        Ok((tag, fields_ty))
+    }
+
+    /// Returns the enum of the matched value, whose payload is loaded.
+    fn get_payload_enum(
+        &mut self,
+        function: &LoweredFunction,
+        term: &LoweredTerm,
+    ) -> Result<Identifier> {
+        match self.get_term_ty(function, term)? {
+            DataType::Enum(name) => Ok(*name),
+            ty => bail!("Cannot load the payload of {} of type {}", term, ty),
+        }
+    }
+
+    fn get_term_ty(&mut self, function: &LoweredFunction, term: &LoweredTerm) -> Result<DataType> {
+        match term {
+            LoweredTerm::Constant(_) => Ok(DataType::Int),
+            LoweredTerm::Literal(literal) => Ok(literal.get_ty().clone()),
+            LoweredTerm::Bool(_) => Ok(DataType::Bool),
+            LoweredTerm::Str(_) => Ok(DataType::Str),
+            LoweredTerm::Id(variable) => {
+                match self.get_function_value_ty(function, variable.get_ident())? {
+                    Some(ty) => Ok(ty),
+                    None => self.get_path_ty(function, variable.get_ident()),
+                }
+            }
+            LoweredTerm::Nil => bail!("The type of nil cannot be inferred"),
+        }
+    }
+
+    /// Returns the type of the named function, when the identifier is not a variable
+    /// but a function, which is used as a value.
+    fn get_function_value_ty(
+        &mut self,
+        function: &LoweredFunction,
+        id: &Identifier,
+    ) -> Result<Option<DataType>> {
+        if id.is_field_access()
+            || self
+                .get_symbol_table(function)?
+                .lookup_symbol(id.get_name())
+        {
+            return Ok(None);
+        }
+
+        Ok(self
+            .function_table
+            .get(id.get_name())
+            .map(|(signature, _)| get_function_ty(signature)))
+    }
+
+    /// Returns the type of the expression, where a constant takes the expected numeric type
+    /// and `nil` the expected list type.
+    fn get_expected_expr_ty(
+        &mut self,
+        function: &LoweredFunction,
+        expr: &LoweredExpression,
+        expected: Option<&DataType>,
+    ) -> Result<DataType> {
+        match (expr, expected) {
+            (LoweredExpression::Term(LoweredTerm::Constant(_)), Some(ty)) if ty.is_numeric() => {
+                Ok(ty.clone())
+            }
+            (LoweredExpression::Term(LoweredTerm::Nil), Some(ty @ DataType::List(_))) => {
+                Ok(ty.clone())
+            }
+            (LoweredExpression::Binary(Opcode::Cons, a, b), Some(ty @ DataType::List(_))) => {
+                self.get_cons_ty(function, a, b, Some(ty))
+            }
+            (LoweredExpression::Array(elements), Some(DataType::Array(expected, _))) => {
+                let elements = elements.iter().collect::<Vec<_>>();
+                let ty = self.get_elements_ty(function, &elements, Some(expected))?;
+
+                Ok(DataType::Array(Box::new(ty), elements.len()))
+            }
+            (LoweredExpression::ArrayRepeat(value, len), Some(DataType::Array(expected, _))) => {
+                let ty = self.get_elements_ty(function, &[value], Some(expected))?;
+
+                Ok(DataType::Array(Box::new(ty), *len))
+            }
+            _ => self.get_expr_ty(function, expr),
+        }
+    }
+
+    /// Returns the type of the elements of an array, which must all have the same type.
+    /// Constants take the type of the other elements or the expected numeric type.
+    fn get_elements_ty(
+        &mut self,
+        function: &LoweredFunction,
+        elements: &[&LoweredTerm],
+        expected: Option<&DataType>,
+    ) -> Result<DataType> {
+        let ty = match elements
+            .iter()
+            .find(|term| !matches!(term, LoweredTerm::Constant(_)))
+        {
+            Some(term) => self.get_term_ty(function, term)?,
+            None => match expected {
+                Some(ty) if elements.is_empty() || ty.is_numeric() => ty.clone(),
+                None if elements.is_empty() => {
+                    bail!("The type of an empty array must be declared")
+                }
+                _ => DataType::Int,
+            },
+        };
+
+        for element in elements {
+            let element_ty = match element {
+                LoweredTerm::Constant(_) if ty.is_numeric() => ty.clone(),
+                element => self.get_term_ty(function, element)?,
+            };
+
+            if element_ty != ty {
+                bail!(
+                    "Element {} of the array has type {}, but the array has elements of type {}",
+                    element,
+                    element_ty,
+                    ty
+                );
+            }
+        }
+
+        Ok(ty)
+    }
+
+    /// Translates the array, whose type `ty` was returned by `get_expected_expr_ty`.
+    fn translate_array(
+        &mut self,
+        function: &LoweredFunction,
+        expr: &LoweredExpression,
+        ty: &DataType,
+    ) -> Result<LLVMValueRef> {
+        let element_ty = match ty {
+            DataType::Array(element_ty, _) => element_ty,
+            ty => bail!("Array cannot have type {}", ty),
+        };
+
+        let mut values = match expr {
+            LoweredExpression::Array(elements) => elements
+                .iter()
+                .map(|element| self.translate_operand(function, element, element_ty))
+                .collect::<Result<Vec<_>>>()?,
+            LoweredExpression::ArrayRepeat(value, len) => {
+                vec![self.translate_operand(function, value, element_ty)?; *len]
+            }
+            _ => bail!("Expression is not an array"),
+        };
+
+        Ok(self.build_array(self.get_llvm_type(element_ty)?, &mut values))
+    }
+
+    /// Returns the type of the element of `a` at the indices. Arrays can be indexed
+    /// by every integer type and the bytes of strings are `u8`.
+    fn get_index_ty(
+        &mut self,
+        function: &LoweredFunction,
+        a: &LoweredTerm,
+        subscripts: &[Subscript],
+    ) -> Result<DataType> {
+        let mut ty = self.get_term_ty(function, a)?;
+
+        for subscript in subscripts {
+            let index_ty = self.get_term_ty(function, &subscript.index)?;
+            if !index_ty.is_integer() {
+                bail!(
+                    "Index {} must be an integer, but has type {}",
+                    subscript.index,
+                    index_ty
+                );
+            }
+
+            ty = match ty {
+                DataType::Array(element, _) => *element,
+                DataType::Str => DataType::U8,
+                ty => bail!("Cannot index {} of type {}", a, ty),
+            };
+        }
+
+        Ok(ty)
+    }
+
+    /// Returns a pointer to the element of `a` at the indices, which were checked by
+    /// `get_index_ty`. Arrays are indexed through the pointer of the variable, so
+    /// they are not copied. The program exits when an index is out of bounds.
+    fn get_element_ptr(
+        &mut self,
+        function: &LoweredFunction,
+        a: &LoweredTerm,
+        subscripts: &[Subscript],
+    ) -> Result<LLVMValueRef> {
+        let (mut ptr, mut ty) = match a {
+            LoweredTerm::Id(variable) => self.get_path(function, variable.get_ident())?,
+            term => unsafe {
+                // Literals are stored, so they can be indexed like variables.
+                let ty = self.get_term_ty(function, term)?;
+                let value = self.translate_term(function, term)?;
+                let llvm_function = LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.builder));
+                let ptr = self
+                    .build_entry_alloca(llvm_function, "literal", LLVMTypeOf(value))?
+                    .value;
+                LLVMBuildStore(self.builder, value, ptr);
+
+                (ptr, ty)
+            },
+        };
+
+        for subscript in subscripts {
+            let index_ty = self.get_term_ty(function, &subscript.index)?;
+            let index = self.translate_operand(function, &subscript.index, &index_ty)?;
+            let index = self.build_index_cast(index, &index_ty);
+
+            (ptr, ty) = match ty {
+                DataType::Array(element, len) => (
+                    self.build_element_ptr(ptr, index, len, subscript.pos),
+                    *element,
+                ),
+                DataType::Str => unsafe {
+                    let value = LLVMBuildLoad(self.builder, ptr, c_str!(""));
+                    (
+                        self.build_str_element_ptr(value, index, subscript.pos),
+                        DataType::U8,
+                    )
+                },
+                ty => bail!("Cannot index {} of type {}", a, ty),
+            };
+        }
+
+        Ok(ptr)
+    }
+
+    /// Translates the expression, whose type `ty` was returned by `get_expected_expr_ty`.
+    fn translate_expected_expr(
+        &mut self,
+        function: &LoweredFunction,
+        expr: &LoweredExpression,
+        ty: &DataType,
+    ) -> Result<LLVMValueRef> {
+        match expr {
+            LoweredExpression::Term(term) => self.translate_operand(function, term, ty),
+            LoweredExpression::Array(_) | LoweredExpression::ArrayRepeat(_, _) => {
+                self.translate_array(function, expr, ty)
+            }
+            LoweredExpression::Binary(Opcode::Cons, a, b) => {
+                self.translate_cons(function, a, b, ty)
+            }
+            _ => self.translate_expr(function, expr),
+        }
+    }
+
+    /// Returns the type of the list, which is created by prepending `head` to `tail`.
+    /// A constant head takes the numeric type of the elements and a `nil` tail
+    /// takes the expected type or the type of a list of the head.
+    fn get_cons_ty(
+        &mut self,
+        function: &LoweredFunction,
+        head: &LoweredTerm,
+        tail: &LoweredTerm,
+        expected: Option<&DataType>,
+    ) -> Result<DataType> {
+        let ty = match (tail, expected) {
+            (LoweredTerm::Nil, Some(ty)) => ty.clone(),
+            (LoweredTerm::Nil, None) => match head {
+                LoweredTerm::Constant(_) => DataType::List(Box::new(DataType::Int)),
+                head => DataType::List(Box::new(self.get_term_ty(function, head)?)),
+            },
+            (tail, _) => self.get_term_ty(function, tail)?,
+        };
+
+        let element_ty = match &ty {
+            DataType::List(element_ty) => element_ty,
+            ty => bail!("Cannot prepend {} to {} of type {}", head, tail, ty),
+        };
+
+        let head_ty = match head {
+            LoweredTerm::Constant(_) if element_ty.is_numeric() => *element_ty.clone(),
+            LoweredTerm::Nil if matches!(**element_ty, DataType::List(_)) => *element_ty.clone(),
+            head => self.get_term_ty(function, head)?,
+        };
+
+        if head_ty != **element_ty {
+            bail!(
+                "Cannot prepend {} of type {} to a list of type {}",
+                head,
+                head_ty,
+                ty
+            );
+        }
+
+        Ok(ty)
+    }
+
+    /// Translates `head : tail`, whose type `ty` was returned by `get_cons_ty`.
+    fn translate_cons(
+        &mut self,
+        function: &LoweredFunction,
+        head: &LoweredTerm,
+        tail: &LoweredTerm,
+        ty: &DataType,
+    ) -> Result<LLVMValueRef> {
+        let element_ty = match ty {
+            DataType::List(element_ty) => element_ty,
+            ty => bail!("List cannot have type {}", ty),
+        };
+
+        // The cell is allocated before the operands are loaded, because
+        // the collector would not see them while it allocates.
+        let cell = self.build_list_cell(element_ty)?;
+        let head = self.translate_operand(function, head, element_ty)?;
+        let tail = self.translate_operand(function, tail, ty)?;
+        self.build_list_init(cell, head, tail);
+
+        Ok(cell)
+    }
+
+    /// Returns the type of the result. Comparisons return a `bool`, `head` returns
+    /// the element of the list and the other operators keep the type of their operands.
+    fn get_operator_ty(
+        &mut self,
+        function: &LoweredFunction,
+        op: &Opcode,
+        terms: &[&LoweredTerm],
+    ) -> Result<DataType> {
+        let ty = self.get_operands_ty(function, op, terms)?;
+
+        match op {
+            Opcode::Lt | Opcode::Leq | Opcode::Gt | Opcode::Geq | Opcode::Cmp | Opcode::Neq => {
+                Ok(DataType::Bool)
+            }
+            Opcode::Head => match ty {
+                DataType::List(element) => Ok(*element),
+                ty => bail!("Cannot take the head of {} of type {}", terms[0], ty),
+            },
+            _ => Ok(ty),
+        }
+    }
+
+    /// Checks that all operands have the same type, which the operator accepts, and returns it.
+    /// Constants take the type of the other operand, when it is numeric, and `nil` takes
+    /// the type of the other list. Lists can only be compared with `nil`.
+    fn get_operands_ty(
+        &mut self,
+        function: &LoweredFunction,
+        op: &Opcode,
+        terms: &[&LoweredTerm],
+    ) -> Result<DataType> {
+        let ty = match terms
+            .iter()
+            .find(|term| !matches!(term, LoweredTerm::Constant(_) | LoweredTerm::Nil))
+        {
+            Some(term) => self.get_term_ty(function, term)?,
+            None => DataType::Int,
+        };
+
+        for term in terms {
+            let term_ty = match term {
+                LoweredTerm::Constant(_) if ty.is_numeric() => ty.clone(),
+                LoweredTerm::Nil if matches!(ty, DataType::List(_)) => ty.clone(),
+                term => self.get_term_ty(function, term)?,
+            };
+
+            if term_ty != ty {
+                bail!(
+                    "Operator {:?} cannot be applied to {} of type {}",
+                    op,
+                    term,
+                    term_ty
+                );
+            }
+        }
+
+        if matches!(ty, DataType::List(_))
+            && matches!(op, Opcode::Cmp | Opcode::Neq)
+            && !terms.iter().any(|term| matches!(term, LoweredTerm::Nil))
+        {
+            bail!(
+                "Lists can only be compared with nil, but {} was compared with {}",
+                terms[0],
+                terms[1]
+            );
+        }
+
+        let accepted = match op {
+            Opcode::Cmp | Opcode::Neq => {
+                ty.is_numeric()
+                    || ty == DataType::Bool
+                    || ty == DataType::Str
+                    || matches!(ty, DataType::List(_))
+            }
+            Opcode::Head | Opcode::Tail => matches!(ty, DataType::List(_)),
+            Opcode::Not | Opcode::And | Opcode::Or => ty == DataType::Bool,
+            Opcode::BitAnd | Opcode::BitOr | Opcode::BitXor | Opcode::Shl | Opcode::Shr => {
+                ty.is_integer()
+            }
+            Opcode::Neg => ty.is_signed() || ty.is_float(),
+            _ => ty.is_numeric(),
+        };
+
+        if !accepted {
+            bail!(
+                "Operator {:?} cannot be applied to {} of type {}",
+                op,
+                terms[0],
+                ty
+            );
+        }
+
+        Ok(ty)
+    }
+
+    /// Translates the operator on integers or floats. Unsigned integers
+    /// use the unsigned division, shift and comparisons.
+    fn translate_binary(
+        &mut self,
+        op: &Opcode,
+        ty: &DataType,
+        a: LLVMValueRef,
+        b: LLVMValueRef,
+    ) -> Result<LLVMValueRef> {
+        if ty.is_float() {
+            return self.translate_float_binary(op, a, b);
+        }
+        if *ty == DataType::Str {
+            return self.build_str_comparison(op, a, b);
+        }
+
+        let signed = ty.is_signed();
+
+        unsafe {
+            let value = match op {
+                Opcode::Add => LLVMBuildAdd(self.builder, a, b, c_str!("")),
+                Opcode::Sub => LLVMBuildSub(self.builder, a, b, c_str!("")),
+                Opcode::Mul => LLVMBuildMul(self.builder, a, b, c_str!("")),
+                Opcode::Div if signed => LLVMBuildSDiv(self.builder, a, b, c_str!("")),
+                Opcode::Div => LLVMBuildUDiv(self.builder, a, b, c_str!("")),
+                Opcode::Rem if signed => LLVMBuildSRem(self.builder, a, b, c_str!("")),
+                Opcode::Rem => LLVMBuildURem(self.builder, a, b, c_str!("")),
+                Opcode::BitAnd => LLVMBuildAnd(self.builder, a, b, c_str!("")),
+                Opcode::BitOr => LLVMBuildOr(self.builder, a, b, c_str!("")),
+                Opcode::BitXor => LLVMBuildXor(self.builder, a, b, c_str!("")),
+                Opcode::Shl => LLVMBuildShl(self.builder, a, b, c_str!("")),
+                Opcode::Shr if signed => LLVMBuildAShr(self.builder, a, b, c_str!("")),
+                Opcode::Shr => LLVMBuildLShr(self.builder, a, b, c_str!("")),
+                Opcode::Cmp => self.translate_comparison(LLVMIntPredicate::LLVMIntEQ, a, b),
+                Opcode::Neq => self.translate_comparison(LLVMIntPredicate::LLVMIntNE, a, b),
+                Opcode::Lt if signed => {
+                    self.translate_comparison(LLVMIntPredicate::LLVMIntSLT, a, b)
+                }
+                Opcode::Lt => self.translate_comparison(LLVMIntPredicate::LLVMIntULT, a, b),
+                Opcode::Leq if signed => {
+                    self.translate_comparison(LLVMIntPredicate::LLVMIntSLE, a, b)
+                }
+                Opcode::Leq => self.translate_comparison(LLVMIntPredicate::LLVMIntULE, a, b),
+                Opcode::Gt if signed => {
+                    self.translate_comparison(LLVMIntPredicate::LLVMIntSGT, a, b)
+                }
+                Opcode::Gt => self.translate_comparison(LLVMIntPredicate::LLVMIntUGT, a, b),
+                Opcode::Geq if signed => {
+                    self.translate_comparison(LLVMIntPredicate::LLVMIntSGE, a, b)
+                }
+                Opcode::Geq => self.translate_comparison(LLVMIntPredicate::LLVMIntUGE, a, b),
+                Opcode::And | Opcode::Or => {
+                    bail!("Opcode {:?} must be lowered into jumps", op)
+                }
+                Opcode::Cons => bail!("Opcode {:?} must be translated into a cell", op),
+                Opcode::Neg | Opcode::Not | Opcode::Head | Opcode::Tail => {
+                    bail!("Opcode {:?} is not a binary operator", op)
+                }
+            };
+
+            Ok(value)
+        }
+    }
+
+    /// Comparisons are ordered, so they do not hold for NaN, except for `!=`.
+    fn translate_float_binary(
+        &mut self,
+        op: &Opcode,
+        a: LLVMValueRef,
+        b: LLVMValueRef,
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            let predicate = match op {
+                Opcode::Add => return Ok(LLVMBuildFAdd(self.builder, a, b, c_str!(""))),
+                Opcode::Sub => return Ok(LLVMBuildFSub(self.builder, a, b, c_str!(""))),
+                Opcode::Mul => return Ok(LLVMBuildFMul(self.builder, a, b, c_str!(""))),
+                Opcode::Div => return Ok(LLVMBuildFDiv(self.builder, a, b, c_str!(""))),
+                Opcode::Rem => return Ok(LLVMBuildFRem(self.builder, a, b, c_str!(""))),
+                Opcode::Cmp => LLVMRealPredicate::LLVMRealOEQ,
+                Opcode::Neq => LLVMRealPredicate::LLVMRealUNE,
+                Opcode::Lt => LLVMRealPredicate::LLVMRealOLT,
+                Opcode::Leq => LLVMRealPredicate::LLVMRealOLE,
+                Opcode::Gt => LLVMRealPredicate::LLVMRealOGT,
+                Opcode::Geq => LLVMRealPredicate::LLVMRealOGE,
+                _ => bail!("Opcode {:?} cannot be applied to floats", op),
+            };
+
+            Ok(LLVMBuildFCmp(self.builder, predicate, a, b, c_str!("")))
+        }
+    }
+
+    fn translate_unary(
+        &mut self,
+        op: &Opcode,
+        ty: &DataType,
+        a: LLVMValueRef,
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            let value = match op {
+                Opcode::Neg if ty.is_float() => LLVMBuildFNeg(self.builder, a, c_str!("")),
+                Opcode::Neg => LLVMBuildNeg(self.builder, a, c_str!("")),
+                Opcode::Not => LLVMBuildNot(self.builder, a, c_str!("")),
+                Opcode::Head | Opcode::Tail => self.build_list_access(op, a)?,
+                _ => bail!("Opcode {:?} is not a unary operator", op),
+            };
+
+            Ok(value)
+        }
+    }
+
+    /// Converts the value, which was checked by `get_expr_ty`. Integers are
+    /// truncated or extended with the sign of the source type.
+    fn translate_cast(
+        &mut self,
+        value: LLVMValueRef,
+        from: &DataType,
+        to: &DataType,
+    ) -> Result<LLVMValueRef> {
+        if from == to {
+            return Ok(value);
+        }
+
+        let ty = self.get_llvm_type(to)?;
+
+        unsafe {
+            let value = if from.is_float() && to.is_float() {
+                LLVMBuildFPCast(self.builder, value, ty, c_str!(""))
+            } else if from.is_float() && to.is_signed() {
+                LLVMBuildFPToSI(self.builder, value, ty, c_str!(""))
+            } else if from.is_float() {
+                LLVMBuildFPToUI(self.builder, value, ty, c_str!(""))
+            } else if to.is_float() && from.is_signed() {
+                LLVMBuildSIToFP(self.builder, value, ty, c_str!(""))
+            } else if to.is_float() {
+                LLVMBuildUIToFP(self.builder, value, ty, c_str!(""))
+            } else {
+                LLVMBuildIntCast2(
+                    self.builder,
+                    value,
+                    ty,
+                    from.is_signed() as LLVMBool,
+                    c_str!(""),
+                )
+            };
+
+            Ok(value)
+        }
+    }
+
+    unsafe fn translate_comparison(
+        &mut self,
+        predicate: LLVMIntPredicate,
+        a: LLVMValueRef,
+        b: LLVMValueRef,
+    ) -> LLVMValueRef {
+        LLVMBuildICmp(self.builder, predicate, a, b, c_str!(""))
+    }
+
+    fn translate_term(
+        &mut self,
+        function: &LoweredFunction,
+        term: &LoweredTerm,
+    ) -> Result<LLVMValueRef> {
+        unsafe {
+            match term {
+                LoweredTerm::Constant(num) => Ok(LLVMConstInt(
+                    LLVMInt64TypeInContext(self.context),
+                    *num as u64,
+                    1,
+                )),
+                LoweredTerm::Literal(Literal::Int(value, ty)) => Ok(LLVMConstInt(
+                    self.get_llvm_type(ty)?,
+                    *value as u64,
+                    ty.is_signed() as LLVMBool,
+                )),
+                LoweredTerm::Literal(Literal::Float(value, ty)) => {
+                    Ok(LLVMConstReal(self.get_llvm_type(ty)?, *value))
+                }
+                LoweredTerm::Bool(value) => Ok(LLVMConstInt(
+                    LLVMInt1TypeInContext(self.context),
+                    *value as u64,
+                    0,
+                )),
+                LoweredTerm::Str(value) => Ok(self.build_str_constant(value)),
+                LoweredTerm::Nil => bail!("The type of nil cannot be inferred"),
+                LoweredTerm::Id(variable) => {
+                    let id = variable.get_ident();
+                    self.set_debug_location(id)?;
+                    if self.get_function_value_ty(function, id)?.is_some() {
+                        return self.build_function_value(id);
+                    }
+                    let (ptr, _) = self.get_path(function, id)?;
+
+                    Ok(LLVMBuildLoad(self.builder, ptr, c_str!(id)))
+                }
+            }
+        }
+    }
+
+    /// Translates the operand, where a constant has the type of the other operands
+    /// and `nil` is the null pointer of the list type.
+    /// Fails when the constant does not fit into the integer type.
+    fn translate_operand(
+        &mut self,
+        function: &LoweredFunction,
+        term: &LoweredTerm,
+        ty: &DataType,
+    ) -> Result<LLVMValueRef> {
+        match term {
+            LoweredTerm::Constant(num) if ty.is_float() => unsafe {
+                Ok(LLVMConstReal(self.get_llvm_type(ty)?, *num as f64))
+            },
+            LoweredTerm::Constant(num) if ty.is_integer() => {
+                let (min, max) = ty.get_int_range().context("Type has no range")?;
+                if (*num as i128) < min || (*num as i128) > max {
+                    bail!("Literal {} is out of range for {}", num, ty);
+                }
+
+                unsafe { Ok(LLVMConstInt(self.get_llvm_type(ty)?, *num as u64, 1)) }
+            }
+            LoweredTerm::Nil if matches!(ty, DataType::List(_)) => unsafe {
+                Ok(LLVMConstNull(self.get_llvm_type(ty)?))
+            },
+            term => self.translate_term(function, term),
+        }
+    }
+
+    /// Returns the signature of the function and what the call calls.
+    /// Variables with a function value hide the functions with their name.
+    fn get_function(
+        &mut self,
+        function: &LoweredFunction,
+        id: &Identifier,
+        arguments: &[LoweredExpression],
+    ) -> Result<(FunctionSignature, Callee)> {
+        if let Some((variable, value)) = self.get_symbol_table(function)?.get_both(id.get_name()) {
+            if let Some(DataType::Function(pars, ret_ty)) = &variable.ty {
+                let signature = FunctionSignature::new(pars.clone(), ret_ty.as_deref().cloned());
+                return Ok((signature, Callee::Value(value.value)));
+            }
+        }
+
+        if let Some((signature, value)) = self.function_table.get(id.get_name()) {
+            return Ok((signature.clone(), Callee::Function(*value)));
+        }
+
+        // Built-in functions can accept arguments of different types. Invalid
+        // arguments are reported when they are checked against the signature.
+        let arguments_ty = arguments
+            .iter()
+            .map(|argument| self.get_expr_ty(function, argument))
+            .collect::<Result<Vec<_>>>()
+            .unwrap_or_default();
+
+        get_builtin_signature(id.get_name(), &arguments_ty)
+            .map(|signature| (signature, Callee::Builtin))
+            .with_context(|| format!("Function {} is not defined", id))
+    }
+
+    fn get_symbol_table(&mut self, function: &LoweredFunction) -> Result<&mut LLVMSymbolTable> {
+        self.symbol_tables
+            .get_mut(&function.id)
+            .with_context(|| format!("Cannot find symbol table of {}", function.id))
+    }
+
+    /// Returns the variable and the type of the value which is stored in it.
+    fn get_variable(
+        &mut self,
+        function: &LoweredFunction,
+        id: &Identifier,
+    ) -> Result<(BasicValue, DataType)> {
+        let (variable, value) = self
+            .get_symbol_table(function)?
+            .get_both(id.get_name())
+            .cloned()
+            .with_context(|| format!("Symbol {} is not defined", id.get_name()))?;
+
+        Ok((value, variable.ty.unwrap_or(DataType::Int)))
+    }
+
+    /// Returns the struct and the index of the field, which is accessed on a value of `ty`.
+    fn get_field(&self, ty: &DataType, field: &Identifier) -> Result<(&Struct, usize)> {
+        let name = match ty {
+            DataType::Struct(name) => name,
+            ty => bail!("Cannot access field {} of {}", field.get_name(), ty),
+        };
+
+        let (s, _) = self.get_struct(name)?;
+        let index = s
+            .get_id_by_field_name(field.get_name())
+            .with_context(|| format!("Struct {} has no field {}", name, field.get_name()))?;
+
+        Ok((s, index))
+    }
+
+    /// Returns the type of the variable or field access.
+    fn get_path_ty(&mut self, function: &LoweredFunction, id: &Identifier) -> Result<DataType> {
+        let (_, mut ty) = self.get_variable(function, id)?;
+
+        let mut field = id.get_field();
+        while let Some(next) = field {
+            let (s, index) = self.get_field(&ty, next)?;
+            ty = s.fields[index].ty.clone();
+            field = next.get_field();
+        }
+
+        Ok(ty)
+    }
+
+    /// Returns a pointer to the variable or field and the type of its value.
+    /// Every struct on the path is loaded to get the pointer of the next field.
+    fn get_path(
+        &mut self,
+        function: &LoweredFunction,
+        id: &Identifier,
+    ) -> Result<(LLVMValueRef, DataType)> {
+        let (variable, mut ty) = self.get_variable(function, id)?;
+        let mut ptr = variable.value;
+
+        let mut field = id.get_field();
+        while let Some(next) = field {
+            let (s, index) = self.get_field(&ty, next)?;
+            let next_ty = s.fields[index].ty.clone();
+
+            unsafe {
+                let object = LLVMBuildLoad(self.builder, ptr, c_str!(""));
+                ptr = LLVMBuildStructGEP(self.builder, object, index as u32, c_str!(""));
+            }
+
+            ty = next_ty;
+            field = next.get_field();
+        }
+
+        Ok((ptr, ty))
+    }
+
+    fn get_block(&self, block: &BasicBlockId) -> Result<LLVMBasicBlockRef> {
+        self.block_table
+            .get(&format!("b{}", block.get_value()))
+            .copied()
+            .with_context(|| format!("Block b{} does not exist", block.get_value()))
+    }
+
+    /// Allocates the variable in the entry block of the function, so
+    /// the stack does not grow when the block is executed multiple times.
+    pub(super) fn build_entry_alloca(
+        &self,
+        function: LLVMValueRef,
+        name: &str,
+        ty: LLVMTypeRef,
+    ) -> Result<BasicValue> {
+        unsafe {
+            let builder = LLVMCreateBuilderInContext(self.context);
+            let entry = LLVMGetEntryBasicBlock(function);
+
+            let mut instruction = LLVMGetFirstInstruction(entry);
+            while !instruction.is_null()
+                && LLVMGetInstructionOpcode(instruction) == LLVMOpcode::LLVMAlloca
+            {
+                instruction = LLVMGetNextInstruction(instruction);
+            }
+
+            if instruction.is_null() {
+                LLVMPositionBuilderAtEnd(builder, entry);
+            } else {
+                LLVMPositionBuilderBefore(builder, instruction);
+            }
+
+            let value = LLVMBuildAlloca(builder, ty, c_str!(name));
+
+            LLVMDisposeBuilder(builder);
+
+            Ok(BasicValue {
+                ty: BasicValueType::Pointer,
+                value,
+            })
+        }
+    }
+}
+
+/// What a call calls.
+enum Callee {
+    /// A function, which is declared in the module.
+    Function(LLVMValueRef),
+    /// The function value in the variable, which is loaded after the arguments.
+    Value(LLVMValueRef),
+    Builtin,
+}
+
+/// Returns the type of the values of a function with the signature.
+fn get_function_ty(signature: &FunctionSignature) -> DataType {
+    DataType::Function(
+        signature.get_args_ty().to_vec(),
+        signature.get_ret_ty().cloned().map(Box::new),
+    )
+}
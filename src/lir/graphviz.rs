@@ -20,15 +20,13 @@ impl LoweredProgram {
         let mut stmts: Vec<_> = function
             .blocks
             .iter()
-            .map(|x| self.map_block(x))
-            .flatten()
+            .flat_map(|x| self.map_block(x))
             .collect();
         let mut edges: Vec<_> = function
             .blocks
             .iter()
-            .map(|x| self.map_edges(x))
-            .flatten()
-            .map(|x| Stmt::Edge(x))
+            .flat_map(|x| self.map_edges(x))
+            .map(Stmt::Edge)
             .collect();
 
         stmts.append(&mut edges);
@@ -58,19 +56,52 @@ impl LoweredProgram {
                 }
                 LoweredStatement::Assignment(ident, expr) => {
                     content.push_str(
-                        format!("{} = {:?}", ident.get_ident().get_name(), expr).as_str(),
+                        format!("{} = {:?}", ident.get_ident(), expr).as_str(),
                     );
                 }
-                LoweredStatement::ConditionalJump(condition, block) => {
+                LoweredStatement::IndexAssignment(ident, subscripts, expr) => {
+                    let indices = subscripts
+                        .iter()
+                        .map(|subscript| format!("[{}]", subscript.index))
+                        .collect::<String>();
                     content.push_str(
-                        format!("if {:?} -> jump {:?}", condition, block.get_id()).as_str(),
+                        format!("{}{} = {:?}", ident.get_ident(), indices, expr).as_str(),
                     );
                 }
+                LoweredStatement::UnconditionalJump(block) => {
+                    content.push_str(format!("jump b{}", block.get_value()).as_str());
+                }
+                LoweredStatement::ConditionalJump(condition, then_block, else_block) => {
+                    content.push_str(
+                        format!(
+                            "if {:?} -> jump b{} else jump b{}",
+                            condition,
+                            then_block.get_value(),
+                            else_block.get_value()
+                        )
+                        .as_str(),
+                    );
+                }
+                LoweredStatement::Switch(value, _, cases, default) => {
+                    let cases = cases
+                        .iter()
+                        .map(|(tag, block)| format!(" {} -> jump b{}", tag, block.get_value()))
+                        .collect::<String>();
+                    content.push_str(
+                        format!("switch {}{} else jump b{}", value, cases, default.get_value())
+                            .as_str(),
+                    );
+                }
+                LoweredStatement::Ret(expr) => {
+                    content.push_str(format!("return {:?}", expr).as_str())
+                }
                 LoweredStatement::RetVoid => content.push_str("return (void)"),
-                _ => unimplemented!(),
+                LoweredStatement::Expression(expr) => {
+                    content.push_str(format!("{:?}", expr).as_str())
+                }
             }
 
-            content.push_str("\n");
+            content.push('\n');
         }
 
         let name = format!("b{}", block.get_id().get_value());
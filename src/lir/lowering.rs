@@ -1,6 +1,9 @@
-use crate::ast::{Expr, Func, Program, Statement, Struct, Term};
+use crate::ast::{
+    check_num, Arm, DataType, Enum, Expr, Func, Guard, Identifier, Opcode, Pattern, Program,
+    Statement, Struct, Term,
+};
 use anyhow::{bail, Context, Result};
-use log::info;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use super::tree::*;
 
@@ -9,196 +12,2135 @@ use super::tree::*;
  */
 pub struct LoweringPass {
     basic_block_counter: BasicBlockId,
+    /// Variables of the current function which escape it.
+    escaping: HashSet<String>,
+    /// Blocks which `break` and `continue` jump to. The innermost `match` or loop is last.
+    jump_targets: Vec<JumpTarget>,
+    temporary_counter: usize,
+    /// Position of the last identifier, which temporaries are located at.
+    position: usize,
+    /// The enums of the program, which `match` has to cover.
+    enums: HashMap<String, Enum>,
+    /// The structs of the program, whose functions are called like `Point::origin()`.
+    structs: HashMap<String, Struct>,
+    /// Name of the current function, which prefixes the names of its closures.
+    function_name: String,
+    /// Variables of the current function, which its closures can capture.
+    locals: HashSet<String>,
+    /// Number of closures of the current function, which names the next one.
+    closure_counter: usize,
+    /// Closures with their captured variables, which are lifted into functions
+    /// after the functions that create them.
+    closures: VecDeque<(Func, Vec<Identifier>)>,
+}
+
+/// What a `for` loop iterates over.
+enum Iteration<'a> {
+    /// The integers from the start to the excluded end.
+    Range(&'a Expr, &'a Expr),
+    /// The elements of an array or the bytes of a string.
+    Elements(&'a Expr),
+}
+
+/// The blocks of an enclosing `match` or loop.
+struct JumpTarget {
+    label: Option<String>,
+    /// Block after the `match` or loop
+    exit: BasicBlockId,
+    /// Block which starts the next iteration. `match` has none.
+    next_iteration: Option<BasicBlockId>,
 }
 
 impl LoweringPass {
     pub fn default() -> Self {
         Self {
             basic_block_counter: BasicBlockId::default(),
+            escaping: HashSet::new(),
+            jump_targets: Vec::new(),
+            temporary_counter: 0,
+            position: 0,
+            enums: HashMap::new(),
+            structs: HashMap::new(),
+            function_name: String::new(),
+            locals: HashSet::new(),
+            closure_counter: 0,
+            closures: VecDeque::new(),
         }
     }
 
     pub fn lower(&mut self, program: &Program) -> Result<LoweredProgram> {
-        let (functions, errors): (Vec<_>, Vec<_>) = program
+        self.enums = program
+            .enums
+            .iter()
+            .map(|e| (e.name.get_name().clone(), e.clone()))
+            .collect();
+        self.structs = program
+            .structs
+            .iter()
+            .map(|s| (s.name.get_name().clone(), s.clone()))
+            .collect();
+
+        let mut results = program
             .functions
             .iter()
-            .map(|x| self.map_function(x).context("Mapping failed"))
-            .partition(Result::is_ok);
+            .map(|x| self.map_function(x, None).context("Mapping failed"))
+            .collect::<Vec<_>>();
+
+        // Closures can create closures, which are queued while they are lifted.
+        while let Some((closure, captures)) = self.closures.pop_front() {
+            results.push(
+                self.map_function(&closure, Some(captures))
+                    .context("Mapping failed"),
+            );
+        }
+
+        let (functions, errors): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
 
-        if errors.len() > 0 {
+        if !errors.is_empty() {
             bail!("Lowering functions has failed {:?}", errors);
         }
 
         Ok(LoweredProgram::new(
             "Program".to_string(),
             functions.into_iter().map(Result::unwrap).collect(),
+            program.structs.clone(),
+            program.enums.clone(),
         ))
     }
 
-    fn map_function(&mut self, function: &Func) -> Result<LoweredFunction> {
+    /// Maps the function or the lifted closure, which gets the captured variables
+    /// from its environment.
+    fn map_function(
+        &mut self,
+        function: &Func,
+        captures: Option<Vec<Identifier>>,
+    ) -> Result<LoweredFunction> {
         let mut blocks = vec![BasicBlock::empty(
             self.basic_block_counter.fetch_and_increment(),
         )];
-        for stmt in &function.statements {
-            let current_block = blocks.last_mut().context("There is no basic block")?;
 
-            if let Some(next_block) = self.map_block(stmt, current_block)? {
-                current_block.add_successor(next_block.get_id());
-                blocks.push(next_block);
-            }
-        }
+        self.escaping.clear();
+        collect_escaping(&function.statements, &mut self.escaping);
+
+        self.function_name = function.id.get_name().clone();
+        self.closure_counter = 0;
+        self.locals.clear();
+        collect_names(&function.statements, &mut HashSet::new(), &mut self.locals);
+        self.locals.extend(
+            function
+                .pars
+                .iter()
+                .chain(captures.iter().flatten())
+                .map(|id| id.get_name().clone()),
+        );
+
+        self.map_stmts(&function.statements, &mut blocks)?;
 
         Ok(LoweredFunction {
             id: function.id.clone(),
             pars: function.pars.clone(),
-            entry: blocks
-                .first()
-                .context("Function has no blocks")?
-                .get_id()
-                .clone(),
-            blocks: blocks,
+            ret_ty: function.get_ret_ty(),
+            captures,
+            entry: *blocks.first().context("Function has no blocks")?.get_id(),
+            blocks,
         })
     }
 
-    fn map_stmts(
-        &mut self,
-        stmts: &[Box<Statement>],
-        current_block: &mut BasicBlock,
-    ) -> Result<()> {
+    /// Maps the statements into the last block of `blocks`.
+    /// New blocks are appended when the control flow splits.
+    fn map_stmts(&mut self, stmts: &[Statement], blocks: &mut Vec<BasicBlock>) -> Result<()> {
         for stmt in stmts {
-            self.map_block(stmt, current_block)?;
+            self.map_block(stmt, blocks)?;
         }
 
         Ok(())
     }
 
-    /// Maps a block and returns a BasicBlock if a new block has been created and should be continued.
-    fn map_block(
-        &mut self,
-        stmt: &Box<Statement>,
-        current_block: &mut BasicBlock,
-    ) -> Result<Option<BasicBlock>> {
-        match stmt.as_ref() {
+    /// Returns the block which statements should be appended to.
+    /// When the last block has already been terminated, then the following
+    /// statements are unreachable and a new block without predecessors is created.
+    fn current_block<'a>(&mut self, blocks: &'a mut Vec<BasicBlock>) -> Result<&'a mut BasicBlock> {
+        if blocks
+            .last()
+            .context("There is no basic block")?
+            .is_terminated()
+        {
+            blocks.push(BasicBlock::empty(
+                self.basic_block_counter.fetch_and_increment(),
+            ));
+        }
+
+        blocks.last_mut().context("There is no basic block")
+    }
+
+    /// Terminates the last block with a jump to `target`, unless
+    /// the block has already been terminated.
+    fn jump_to(&mut self, blocks: &mut [BasicBlock], target: BasicBlockId) -> Result<()> {
+        let current_block = blocks.last_mut().context("There is no basic block")?;
+
+        if !current_block.is_terminated() {
+            current_block.add_to_bottom(LoweredStatement::UnconditionalJump(target))?;
+            current_block.add_successor(&target);
+        }
+
+        Ok(())
+    }
+
+    /// Maps a statement into the last block of `blocks`.
+    fn map_block(&mut self, stmt: &Statement, blocks: &mut Vec<BasicBlock>) -> Result<()> {
+        match stmt {
             Statement::Definition(a, b) => {
-                let stmt = LoweredStatement::Definition(
-                    Variable::new(a.clone(), false),
-                    self.map_expr(b)?,
-                );
-                current_block.add_to_bottom(stmt)?;
+                let value = match **b {
+                    // The struct can live on the stack, when it is only
+                    // accessed through the fields of the variable.
+                    Expr::Struct(ref name) if !self.escaping.contains(a.get_name()) => {
+                        LoweredExpression::Struct(name.clone(), Allocation::Stack)
+                    }
+                    _ => self.map_expr(b, blocks)?,
+                };
+                let stmt = LoweredStatement::Definition(Variable::new(a.clone(), false), value);
+                self.current_block(blocks)?.add_to_bottom(stmt)?;
             }
             Statement::Assign(id, ref value) => {
                 let stmt = LoweredStatement::Assignment(
                     Variable::new(id.clone(), false),
-                    self.map_expr(value)?,
+                    self.map_expr(value, blocks)?,
+                );
+
+                self.current_block(blocks)?.add_to_bottom(stmt)?;
+            }
+            Statement::AssignIndex(place, ref value) => {
+                let id = place
+                    .get_indexed_path()
+                    .context("Only elements of variables and fields can be assigned")?;
+                let (_, indices) = split_indices(place);
+
+                // The indices are computed before the calls of the value.
+                let subscripts = self.map_subscripts(&indices, has_calls(value), blocks)?;
+                let stmt = LoweredStatement::IndexAssignment(
+                    Variable::new(id.clone(), false),
+                    subscripts,
+                    self.map_expr(value, blocks)?,
                 );
 
-                current_block.add_to_bottom(stmt)?;
+                self.current_block(blocks)?.add_to_bottom(stmt)?;
+            }
+            Statement::Ret(value) => {
+                let stmt = LoweredStatement::Ret(self.map_expr(value, blocks)?);
+                self.current_block(blocks)?.add_to_bottom(stmt)?;
+            }
+            Statement::RetVoid => self
+                .current_block(blocks)?
+                .add_to_bottom(LoweredStatement::RetVoid)?,
+            Statement::Call(id, arguments) => {
+                let call = Expr::Call(id.clone(), arguments.clone());
+                let stmt = LoweredStatement::Expression(self.map_expr(&call, blocks)?);
+                self.current_block(blocks)?.add_to_bottom(stmt)?;
+            }
+            Statement::Expression(expr) => {
+                let stmt = LoweredStatement::Expression(self.map_expr(expr, blocks)?);
+                self.current_block(blocks)?.add_to_bottom(stmt)?;
             }
-            Statement::RetVoid => current_block.add_to_bottom(LoweredStatement::RetVoid)?,
             Statement::Conditional(condition, statements) => {
-                let mut if_block =
-                    BasicBlock::empty(self.basic_block_counter.fetch_and_increment());
-                let then_block = BasicBlock::empty(self.basic_block_counter.fetch_and_increment());
-
-                self.map_stmts(statements, &mut if_block)?;
-                let stmt = LoweredStatement::ConditionalJump(
-                    self.map_expr(condition)?,
-                    Box::new(if_block),
+                let condition = self.map_expr(condition, blocks)?;
+                let then_id = self.basic_block_counter.fetch_and_increment();
+                let resume_id = self.basic_block_counter.fetch_and_increment();
+
+                let current_block = self.current_block(blocks)?;
+                current_block.add_to_bottom(LoweredStatement::ConditionalJump(
+                    condition, then_id, resume_id,
+                ))?;
+                current_block.add_successor(&then_id);
+                current_block.add_successor(&resume_id);
+
+                blocks.push(BasicBlock::empty(then_id));
+                let statements = self.scope_block(then_id, statements);
+                self.map_stmts(&statements, blocks)?;
+                self.jump_to(blocks, resume_id)?;
+
+                blocks.push(BasicBlock::empty(resume_id));
+            }
+            Statement::IfElse(condition, then_statements, else_statements) => {
+                let condition = self.map_expr(condition, blocks)?;
+                let then_id = self.basic_block_counter.fetch_and_increment();
+                let else_id = self.basic_block_counter.fetch_and_increment();
+                let resume_id = self.basic_block_counter.fetch_and_increment();
+
+                let current_block = self.current_block(blocks)?;
+                current_block.add_to_bottom(LoweredStatement::ConditionalJump(
+                    condition, then_id, else_id,
+                ))?;
+                current_block.add_successor(&then_id);
+                current_block.add_successor(&else_id);
+
+                // Both branches join in the resume block, unless they return.
+                blocks.push(BasicBlock::empty(then_id));
+                let then_statements = self.scope_block(then_id, then_statements);
+                self.map_stmts(&then_statements, blocks)?;
+                self.jump_to(blocks, resume_id)?;
+
+                blocks.push(BasicBlock::empty(else_id));
+                let else_statements = self.scope_block(else_id, else_statements);
+                self.map_stmts(&else_statements, blocks)?;
+                self.jump_to(blocks, resume_id)?;
+
+                blocks.push(BasicBlock::empty(resume_id));
+            }
+            Statement::Match(guards) => self.map_match(guards, blocks)?,
+            Statement::MatchEnum(value, arms) => self.map_match_enum(value, arms, blocks)?,
+            Statement::While(label, condition, statements) => {
+                let header_id = self.basic_block_counter.fetch_and_increment();
+                let body_id = self.basic_block_counter.fetch_and_increment();
+                let exit_id = self.basic_block_counter.fetch_and_increment();
+
+                self.current_block(blocks)?;
+                self.jump_to(blocks, header_id)?;
+
+                blocks.push(BasicBlock::empty(header_id));
+                let condition = self.map_expr(condition, blocks)?;
+                let header = self.current_block(blocks)?;
+                header.add_to_bottom(LoweredStatement::ConditionalJump(
+                    condition, body_id, exit_id,
+                ))?;
+                header.add_successor(&body_id);
+                header.add_successor(&exit_id);
+
+                blocks.push(BasicBlock::empty(body_id));
+                let statements = self.scope_block(body_id, statements);
+                self.map_loop_body(label, &statements, header_id, exit_id, blocks)?;
+                blocks.push(BasicBlock::empty(exit_id));
+            }
+            Statement::Loop(label, statements) => {
+                let body_id = self.basic_block_counter.fetch_and_increment();
+                let exit_id = self.basic_block_counter.fetch_and_increment();
+
+                self.current_block(blocks)?;
+                self.jump_to(blocks, body_id)?;
+
+                blocks.push(BasicBlock::empty(body_id));
+                let statements = self.scope_block(body_id, statements);
+                self.map_loop_body(label, &statements, body_id, exit_id, blocks)?;
+                blocks.push(BasicBlock::empty(exit_id));
+            }
+            Statement::For(label, id, start, end, statements) => {
+                self.map_for(label, id, Iteration::Range(start, end), statements, blocks)?
+            }
+            Statement::ForEach(label, id, value, statements) => {
+                self.map_for(label, id, Iteration::Elements(value), statements, blocks)?
+            }
+            Statement::Break(label) => {
+                let target = match label {
+                    Some(label) => self.get_labelled_target(label)?,
+                    None => self
+                        .jump_targets
+                        .last()
+                        .context("break is only allowed in match or loops")?,
+                }
+                .exit;
+
+                let current_block = self.current_block(blocks)?;
+                current_block.add_to_bottom(LoweredStatement::UnconditionalJump(target))?;
+                current_block.add_successor(&target);
+            }
+            Statement::Continue(label) => {
+                let target = match label {
+                    Some(label) => self.get_labelled_target(label)?.next_iteration,
+                    None => self
+                        .jump_targets
+                        .iter()
+                        .rev()
+                        .find_map(|target| target.next_iteration),
+                }
+                .context("continue is only allowed in loops")?;
+
+                let current_block = self.current_block(blocks)?;
+                current_block.add_to_bottom(LoweredStatement::UnconditionalJump(target))?;
+                current_block.add_successor(&target);
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Maps every arm into its own block. The guards are checked in order and an arm,
+    /// which does not `break`, falls through to the check of the next guard.
+    fn map_match(&mut self, guards: &[Guard], blocks: &mut Vec<BasicBlock>) -> Result<()> {
+        let resume_id = self.basic_block_counter.fetch_and_increment();
+
+        for (i, guard) in guards.iter().enumerate() {
+            let arm_id = self.basic_block_counter.fetch_and_increment();
+            let next_id = if i + 1 == guards.len() {
+                resume_id
+            } else {
+                self.basic_block_counter.fetch_and_increment()
+            };
+
+            match &guard.condition {
+                Some(condition) => {
+                    let condition = self.map_expr(condition, blocks)?;
+                    let current_block = self.current_block(blocks)?;
+                    current_block.add_to_bottom(LoweredStatement::ConditionalJump(
+                        condition, arm_id, next_id,
+                    ))?;
+                    current_block.add_successor(&arm_id);
+                    current_block.add_successor(&next_id);
+                }
+                None => {
+                    self.current_block(blocks)?;
+                    self.jump_to(blocks, arm_id)?;
+                }
+            }
+
+            blocks.push(BasicBlock::empty(arm_id));
+            self.jump_targets.push(JumpTarget {
+                label: None,
+                exit: resume_id,
+                next_iteration: None,
+            });
+            let result = self.map_stmts(&guard.statements, blocks);
+            self.jump_targets.pop();
+            result?;
+            self.jump_to(blocks, next_id)?;
+
+            blocks.push(BasicBlock::empty(next_id));
+        }
+
+        Ok(())
+    }
+
+    /// Maps every arm into its own block, which the switch over the tag of the value
+    /// jumps to. The arms have to cover every variant of the enum, unless there is a `_` arm.
+    fn map_match_enum(
+        &mut self,
+        value: &Expr,
+        arms: &[Arm],
+        blocks: &mut Vec<BasicBlock>,
+    ) -> Result<()> {
+        let name = arms
+            .iter()
+            .find_map(|arm| match &arm.pattern {
+                Pattern::Variant(name, _, _) => Some(name),
+                Pattern::Wildcard => None,
+            })
+            .context("match over an enum needs an arm with a variant")?;
+        let e = self
+            .enums
+            .get(name.get_name())
+            .with_context(|| format!("Enum {} is not defined", name))?
+            .clone();
+
+        let value = self.map_operand(value, blocks)?;
+        let arm_ids = arms
+            .iter()
+            .map(|_| self.basic_block_counter.fetch_and_increment())
+            .collect::<Vec<_>>();
+        let resume_id = self.basic_block_counter.fetch_and_increment();
+
+        let mut cases = Vec::with_capacity(arms.len());
+        let mut default = None;
+        for (i, arm) in arms.iter().enumerate() {
+            match &arm.pattern {
+                Pattern::Variant(enum_name, variant, bindings) => {
+                    if enum_name.get_name() != name.get_name() {
+                        bail!(
+                            "Arm {}::{} does not belong to enum {}",
+                            enum_name,
+                            variant,
+                            name
+                        );
+                    }
+
+                    let (tag, definition) = e.get_variant(variant)?;
+                    if definition.fields.len() != bindings.len() {
+                        bail!(
+                            "Variant {}::{} has {} fields, but the arm binds {}",
+                            name,
+                            variant,
+                            definition.fields.len(),
+                            bindings.len()
+                        );
+                    }
+                    if cases.iter().any(|(case, _)| *case == tag) {
+                        bail!("Variant {}::{} has more than one arm", name, variant);
+                    }
+
+                    cases.push((tag, arm_ids[i]));
+                }
+                Pattern::Wildcard if i + 1 != arms.len() => {
+                    bail!("The arm _ has to be the last arm")
+                }
+                Pattern::Wildcard => default = Some(arm_ids[i]),
+            }
+        }
+
+        let default = match default {
+            Some(default) => default,
+            None => {
+                let missing = e
+                    .variants
+                    .iter()
+                    .enumerate()
+                    .filter(|(tag, _)| !cases.iter().any(|(case, _)| case == tag))
+                    .map(|(_, variant)| format!("{}::{}", name, variant.name))
+                    .collect::<Vec<_>>();
+                if !missing.is_empty() {
+                    bail!("match does not cover {}", missing.join(", "));
+                }
+
+                // Every variant has an arm, so the last arm does not need a case.
+                cases.pop().context("match has no arms")?.1
+            }
+        };
+
+        let current_block = self.current_block(blocks)?;
+        current_block.add_to_bottom(LoweredStatement::Switch(
+            value.clone(),
+            (**name).clone(),
+            cases,
+            default,
+        ))?;
+        for arm_id in arm_ids.iter() {
+            current_block.add_successor(arm_id);
+        }
+
+        for (arm, arm_id) in arms.iter().zip(arm_ids) {
+            blocks.push(BasicBlock::empty(arm_id));
+
+            // The bindings are renamed, so they are only visible in their arm.
+            let mut statements = arm.statements.clone();
+            if let Pattern::Variant(_, variant, bindings) = &arm.pattern {
+                let (tag, _) = e.get_variant(variant)?;
+
+                for (i, binding) in bindings.iter().enumerate() {
+                    let name = format!("arm{}.{}", arm_id.get_value(), binding.get_name());
+                    statements = self.rename_scoped(binding, &name, &statements);
+                    let mut variable = binding.clone();
+                    variable.rename(&name);
+
+                    self.current_block(blocks)?
+                        .add_to_bottom(LoweredStatement::Definition(
+                            Variable::new(variable, false),
+                            LoweredExpression::Payload(value.clone(), tag, i),
+                        ))?;
+                }
+            }
+
+            self.map_stmts(&statements, blocks)?;
+            self.jump_to(blocks, resume_id)?;
+        }
+
+        blocks.push(BasicBlock::empty(resume_id));
+
+        Ok(())
+    }
+
+    /// Maps the body of a loop into the last block of `blocks`. The body jumps back
+    /// to `next_iteration` and `break` jumps to `exit_id`.
+    fn map_loop_body(
+        &mut self,
+        label: &Option<String>,
+        statements: &[Statement],
+        next_iteration: BasicBlockId,
+        exit_id: BasicBlockId,
+        blocks: &mut Vec<BasicBlock>,
+    ) -> Result<()> {
+        if let Some(label) = label {
+            if self.get_labelled_target(label).is_ok() {
+                bail!("Label '{} is already used by an enclosing loop", label);
+            }
+        }
+
+        self.jump_targets.push(JumpTarget {
+            label: label.clone(),
+            exit: exit_id,
+            next_iteration: Some(next_iteration),
+        });
+        let result = self.map_stmts(statements, blocks);
+        self.jump_targets.pop();
+        result?;
+
+        self.jump_to(blocks, next_iteration)
+    }
+
+    /// Desugars `for i in start..end` into a hidden counter, which is compared with
+    /// the end in the header and incremented in the latch block. `continue` jumps to
+    /// the latch block and `i` is defined with the counter at the start of the body.
+    /// `for x in a` copies `a` into a hidden variable, counts from 0 to its length
+    /// and defines `x` with the element at the counter.
+    /// The variable is renamed in the body, so it does not overwrite a variable
+    /// with the same name outside of the loop.
+    fn map_for(
+        &mut self,
+        label: &Option<String>,
+        id: &Identifier,
+        iteration: Iteration,
+        statements: &[Statement],
+        blocks: &mut Vec<BasicBlock>,
+    ) -> Result<()> {
+        let header_id = self.basic_block_counter.fetch_and_increment();
+        let body_id = self.basic_block_counter.fetch_and_increment();
+        let latch_id = self.basic_block_counter.fetch_and_increment();
+        let exit_id = self.basic_block_counter.fetch_and_increment();
+
+        // The names contain the header, so nested loops do not share them.
+        let generated = |name: String| {
+            Variable::new(
+                Identifier::new(name, id.get_pos(), id.get_pos(), Some(DataType::Int)),
+                true,
+            )
+        };
+        let counter = generated(format!("for{}.counter", header_id.get_value()));
+        let end_value = generated(format!("for{}.end", header_id.get_value()));
+
+        let (start, end, values) = match iteration {
+            Iteration::Range(start, end) => (
+                self.map_expr(start, blocks)?,
+                self.map_expr(end, blocks)?,
+                None,
+            ),
+            Iteration::Elements(value) => {
+                let values = Variable::new(
+                    Identifier::new(
+                        format!("for{}.values", header_id.get_value()),
+                        id.get_pos(),
+                        id.get_pos(),
+                        None,
+                    ),
+                    true,
+                );
+                let value = self.map_expr(value, blocks)?;
+                self.current_block(blocks)?
+                    .add_to_bottom(LoweredStatement::Definition(values.clone(), value))?;
+
+                // `len` is the built-in function of the code generator.
+                let len = Identifier::new("len".to_string(), id.get_pos(), id.get_pos(), None);
+                let end = LoweredExpression::Call(
+                    len,
+                    vec![LoweredExpression::Term(LoweredTerm::Id(values.clone()))],
                 );
 
-                current_block.add_to_bottom(stmt)?;
-                return Ok(Some(then_block));
+                (
+                    LoweredExpression::Term(LoweredTerm::Constant(0)),
+                    end,
+                    Some(values),
+                )
             }
-            _ => panic!(),
         };
+        let current_block = self.current_block(blocks)?;
+        current_block.add_to_bottom(LoweredStatement::Definition(counter.clone(), start))?;
+        current_block.add_to_bottom(LoweredStatement::Definition(end_value.clone(), end))?;
+        self.jump_to(blocks, header_id)?;
+
+        let mut header = BasicBlock::empty(header_id);
+        header.add_to_bottom(LoweredStatement::ConditionalJump(
+            LoweredExpression::Binary(
+                Opcode::Geq,
+                LoweredTerm::Id(counter.clone()),
+                LoweredTerm::Id(end_value),
+            ),
+            exit_id,
+            body_id,
+        ))?;
+        header.add_successor(&exit_id);
+        header.add_successor(&body_id);
+        blocks.push(header);
+
+        let value = match values {
+            Some(values) => LoweredExpression::Index(
+                LoweredTerm::Id(values),
+                vec![Subscript {
+                    index: LoweredTerm::Id(counter.clone()),
+                    pos: id.get_pos(),
+                }],
+            ),
+            None => LoweredExpression::Term(LoweredTerm::Id(counter.clone())),
+        };
+        let name = format!("for{}.{}", header_id.get_value(), id.get_name());
+        let statements = self.rename_scoped(id, &name, statements);
+        let statements = self.scope_block(body_id, &statements);
+        let mut variable = id.clone();
+        variable.rename(&name);
+
+        let mut body = BasicBlock::empty(body_id);
+        body.add_to_bottom(LoweredStatement::Definition(
+            Variable::new(variable, false),
+            value,
+        ))?;
+        blocks.push(body);
+        self.map_loop_body(label, &statements, latch_id, exit_id, blocks)?;
+
+        let mut latch = BasicBlock::empty(latch_id);
+        latch.add_to_bottom(LoweredStatement::Assignment(
+            counter.clone(),
+            LoweredExpression::Binary(
+                Opcode::Add,
+                LoweredTerm::Id(counter),
+                LoweredTerm::Constant(1),
+            ),
+        ))?;
+        blocks.push(latch);
+        self.jump_to(blocks, header_id)?;
+
+        blocks.push(BasicBlock::empty(exit_id));
+
+        Ok(())
+    }
+
+    /// Returns the statements, which use `name` instead of the variable `id`,
+    /// whose scope they are. The escaping and local variables are renamed too.
+    fn rename_scoped(
+        &mut self,
+        id: &Identifier,
+        name: &str,
+        statements: &[Statement],
+    ) -> Vec<Statement> {
+        if self.escaping.contains(id.get_name()) {
+            self.escaping.insert(name.to_string());
+        }
+        if self.locals.contains(id.get_name()) {
+            self.locals.insert(name.to_string());
+        }
+
+        let mut statements = statements.to_vec();
+        rename_variable(&mut statements, id.get_name(), name);
+
+        statements
+    }
+
+    /// Returns the statements of a block, whose variables are renamed to
+    /// `block{id}.{name}`, so they are only visible in the block. A variable
+    /// is renamed from its definition on, whose value can still use the
+    /// variable of the enclosing block.
+    fn scope_block(&mut self, block_id: BasicBlockId, statements: &[Statement]) -> Vec<Statement> {
+        let mut statements = statements.to_vec();
+
+        for i in 0..statements.len() {
+            let (id, value) = match &statements[i] {
+                // Renamed variables contain a dot, which identifiers cannot contain.
+                Statement::Definition(id, value) if !id.get_name().contains('.') => {
+                    (id.clone(), value.clone())
+                }
+                _ => continue,
+            };
+
+            let name = format!("block{}.{}", block_id.get_value(), id.get_name());
+            let mut scoped = self.rename_scoped(&id, &name, &statements[i..]);
+            if let Statement::Definition(_, renamed) = &mut scoped[0] {
+                *renamed = value;
+            }
+            statements.splice(i.., scoped);
+        }
+
+        statements
+    }
 
-        Ok(None)
+    fn get_labelled_target(&self, label: &str) -> Result<&JumpTarget> {
+        self.jump_targets
+            .iter()
+            .rev()
+            .find(|target| target.label.as_deref() == Some(label))
+            .with_context(|| format!("Label '{} is not defined", label))
     }
 
-    fn map_expr(&mut self, expr: &Box<Expr>) -> Result<LoweredExpression> {
-        Ok(match *expr.as_ref() {
+    /// Maps the expression into three-address form. Nested operands are
+    /// computed into temporaries, which are defined in the last block of `blocks`.
+    fn map_expr(&mut self, expr: &Expr, blocks: &mut Vec<BasicBlock>) -> Result<LoweredExpression> {
+        Ok(match *expr {
             Expr::Term(ref term) => {
                 LoweredExpression::Term(self.map_term(term).context("Cannot map the term")?)
             }
-            Expr::Binary(ref op, ref a, ref b) => LoweredExpression::Binary(
-                op.clone(),
-                self.map_term(a).context("Cannot map the term")?,
-                self.map_term(b).context("Cannot map the term")?,
-            ),
-            Expr::Call(ref function_name, ref parameters) => LoweredExpression::Call(
-                function_name.clone(),
-                parameters
+            Expr::Binary(ref op @ (Opcode::And | Opcode::Or), ref a, ref b) => {
+                self.map_short_circuit(op, a, b, blocks)?
+            }
+            Expr::Binary(ref op, ref a, ref b) => {
+                // The left operand is read before the calls of the right one.
+                let a = if has_calls(b) {
+                    self.map_temporary(a, blocks)?
+                } else {
+                    self.map_operand(a, blocks)?
+                };
+                let b = self.map_operand(b, blocks)?;
+
+                LoweredExpression::Binary(op.clone(), a, b)
+            }
+            Expr::Unary(ref op, ref a) => {
+                LoweredExpression::Unary(op.clone(), self.map_operand(a, blocks)?)
+            }
+            Expr::Index(_, _, _) => {
+                let (a, indices) = split_indices(expr);
+
+                // The value is read before the calls of the indices.
+                let a = if indices.iter().any(|(index, _)| has_calls(index)) {
+                    self.map_temporary(a, blocks)?
+                } else {
+                    self.map_operand(a, blocks)?
+                };
+
+                LoweredExpression::Index(a, self.map_subscripts(&indices, false, blocks)?)
+            }
+            Expr::Array(ref elements) => {
+                LoweredExpression::Array(self.map_elements(elements, blocks)?)
+            }
+            Expr::Variant(ref name, ref variant, ref payload)
+                if self.enums.contains_key(name.get_name()) =>
+            {
+                LoweredExpression::Variant(
+                    name.clone(),
+                    variant.clone(),
+                    self.map_elements(payload, blocks)?,
+                )
+            }
+            Expr::Variant(ref name, ref function_name, ref parameters) => {
+                let s = self
+                    .structs
+                    .get(name.get_name())
+                    .with_context(|| format!("Enum or struct {} is not defined", name))?;
+                s.get_method(function_name)?;
+
+                self.position = function_name.get_pos();
+                let id = function_name.qualify(name);
+                LoweredExpression::Call(id, self.map_arguments(parameters, blocks)?)
+            }
+            Expr::MethodCall(ref receiver, ref method, ref parameters) => {
+                self.position = method.get_pos();
+                let mut arguments = vec![Expr::Term(Box::new(Term::Id(receiver.clone())))];
+                arguments.extend(parameters.iter().cloned());
+
+                LoweredExpression::MethodCall(
+                    method.clone(),
+                    self.map_arguments(&arguments, blocks)?,
+                )
+            }
+            Expr::ArrayRepeat(ref a, len) => {
+                LoweredExpression::ArrayRepeat(self.map_operand(a, blocks)?, len)
+            }
+            Expr::Cast(ref a, ref ty) => {
+                LoweredExpression::Cast(self.map_operand(a, blocks)?, ty.clone())
+            }
+            Expr::Call(ref function_name, ref parameters) => {
+                self.position = function_name.get_pos();
+                LoweredExpression::Call(
+                    function_name.clone(),
+                    self.map_arguments(parameters, blocks)?,
+                )
+            }
+            Expr::Struct(ref name) | Expr::Boxed(ref name) => {
+                LoweredExpression::Struct(name.clone(), Allocation::Heap)
+            }
+            Expr::Closure(ref closure) => {
+                let pos = closure.id.get_pos();
+                self.position = pos;
+
+                // The closure captures the variables of the function, which it uses
+                // without defining them. The other names refer to functions.
+                let captures = get_free_names(closure)
+                    .into_iter()
+                    .filter(|name| self.locals.contains(name))
+                    .map(|name| Identifier::new(name, pos, pos, None))
+                    .collect::<Vec<_>>();
+                let variables = captures
                     .iter()
-                    .map(|x| Box::new(self.map_expr(x).unwrap()))
-                    .collect::<Vec<_>>(),
-            ),
-            Expr::Struct(_) => unimplemented!(),
+                    .map(|id| Variable::new(id.clone(), false))
+                    .collect();
+
+                let mut lifted = (**closure).clone();
+                lifted.id = Identifier::new(
+                    format!("{}.closure{}", self.function_name, self.closure_counter),
+                    pos,
+                    pos,
+                    None,
+                );
+                self.closure_counter += 1;
+
+                let id = lifted.id.clone();
+                self.closures.push_back((lifted, captures));
+
+                LoweredExpression::Closure(id, variables)
+            }
         })
     }
 
-    fn map_term(&mut self, term: &Box<Term>) -> Result<LoweredTerm> {
-        Ok(match *term.as_ref() {
-            Term::Num(num) => LoweredTerm::Constant(num),
-            Term::Id(ref id) => LoweredTerm::Id(Variable::new(id.clone(), false)),
-        })
+    /// Maps the arguments of a call in order. An argument is computed into a temporary
+    /// when a later argument calls a function in a temporary.
+    fn map_arguments(
+        &mut self,
+        parameters: &[Expr],
+        blocks: &mut Vec<BasicBlock>,
+    ) -> Result<Vec<LoweredExpression>> {
+        let mut arguments = Vec::with_capacity(parameters.len());
+
+        for (i, parameter) in parameters.iter().enumerate() {
+            let argument = if parameters[i + 1..]
+                .iter()
+                .any(|x| creates_temporaries(x) && has_calls(x))
+            {
+                LoweredExpression::Term(self.map_temporary(parameter, blocks)?)
+            } else {
+                self.map_expr(parameter, blocks)?
+            };
+
+            arguments.push(argument);
+        }
+
+        Ok(arguments)
     }
 
-    fn map_struct(&mut self, struc: &Struct) -> Result<()> {
-        Ok(())
+    /// Maps an operand of a binary expression. Only terms can be used
+    /// directly, the other expressions are computed into a temporary.
+    fn map_operand(&mut self, expr: &Expr, blocks: &mut Vec<BasicBlock>) -> Result<LoweredTerm> {
+        match expr {
+            Expr::Term(term) => self.map_term(term).context("Cannot map the term"),
+            _ => self.map_temporary(expr, blocks),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::ast::Identifier;
+    /// Maps the elements of an array or a payload in order. Every element is
+    /// read before the calls of the later elements.
+    fn map_elements(
+        &mut self,
+        elements: &[Expr],
+        blocks: &mut Vec<BasicBlock>,
+    ) -> Result<Vec<LoweredTerm>> {
+        let mut terms = Vec::with_capacity(elements.len());
 
-    use super::*;
+        for (i, element) in elements.iter().enumerate() {
+            let term = if elements[i + 1..].iter().any(has_calls) {
+                self.map_temporary(element, blocks)?
+            } else {
+                self.map_operand(element, blocks)?
+            };
 
-    fn create_identifier() -> Identifier {
-        Identifier::new("test".to_string(), 0, 100, None)
+            terms.push(term);
+        }
+
+        Ok(terms)
     }
 
-    #[test]
-    fn lower_assignment() {
-        let mut pass = LoweringPass::default();
-        let instruction = Statement::Assign(
-            create_identifier(),
-            Box::new(Expr::Term(Box::new(Term::Num(1)))),
-        );
-        let program = Program {
-            structs: Vec::new(),
-            functions: vec![Func {
-                id: create_identifier(),
-                pars: vec![create_identifier()],
-                statements: vec![Box::new(instruction)],
-                ret_ty: None,
-            }],
+    /// Maps the indices of an element access in order. An index is computed into a
+    /// temporary, when the later indices call functions or when `spill` is set.
+    fn map_subscripts(
+        &mut self,
+        indices: &[(&Expr, usize)],
+        spill: bool,
+        blocks: &mut Vec<BasicBlock>,
+    ) -> Result<Vec<Subscript>> {
+        let mut subscripts = Vec::with_capacity(indices.len());
+
+        for (i, (index, pos)) in indices.iter().enumerate() {
+            let index = if spill || indices[i + 1..].iter().any(|(x, _)| has_calls(x)) {
+                self.map_temporary(index, blocks)?
+            } else {
+                self.map_operand(index, blocks)?
+            };
+
+            subscripts.push(Subscript { index, pos: *pos });
+        }
+
+        Ok(subscripts)
+    }
+
+    fn new_temporary(&mut self) -> Variable {
+        let name = format!("tmp.{}", self.temporary_counter);
+        self.temporary_counter += 1;
+
+        Variable::new(
+            Identifier::new(name, self.position, self.position, None),
+            true,
+        )
+    }
+
+    /// Lowers `a && b` and `a || b` into jumps, so `b` is only evaluated when
+    /// `a` does not decide the result.
+    fn map_short_circuit(
+        &mut self,
+        op: &Opcode,
+        a: &Expr,
+        b: &Expr,
+        blocks: &mut Vec<BasicBlock>,
+    ) -> Result<LoweredExpression> {
+        let a = self.map_operand(a, blocks)?;
+        let result = self.new_temporary();
+        let rhs_id = self.basic_block_counter.fetch_and_increment();
+        let update_id = self.basic_block_counter.fetch_and_increment();
+        let resume_id = self.basic_block_counter.fetch_and_increment();
+
+        // Both operands are conditions. The result is only updated
+        // when the right operand decides it.
+        let (initial, then_id, else_id) = match op {
+            Opcode::And => (false, rhs_id, resume_id),
+            _ => (true, resume_id, rhs_id),
         };
 
-        // When
-        let result = pass.lower(&program).unwrap();
+        let current_block = self.current_block(blocks)?;
+        current_block.add_to_bottom(LoweredStatement::Definition(
+            result.clone(),
+            LoweredExpression::Term(LoweredTerm::Bool(initial)),
+        ))?;
+        current_block.add_to_bottom(LoweredStatement::ConditionalJump(
+            LoweredExpression::Term(a),
+            then_id,
+            else_id,
+        ))?;
+        current_block.add_successor(&then_id);
+        current_block.add_successor(&else_id);
 
-        // Then
-        assert_eq!(result.get_entries().len(), 1);
-        assert_eq!(result.get_entries().get(0).unwrap().id.get_name(), "test");
-        assert_eq!(result.get_entries().get(0).unwrap().pars.len(), 1);
-        assert_eq!(
-            result.get_entries().get(0).unwrap().entry,
-            BasicBlockId::default()
-        );
-        assert_eq!(
-            result
-                .get_entries()
-                .get(0)
-                .unwrap()
-                .blocks
-                .get(0)
-                .unwrap()
-                .get_statements(),
-            vec![LoweredStatement::Assignment(
-                Variable::new(create_identifier(), false),
-                LoweredExpression::Term(LoweredTerm::Constant(1))
-            )]
+        blocks.push(BasicBlock::empty(rhs_id));
+        let b = self.map_operand(b, blocks)?;
+        let (then_id, else_id) = match op {
+            Opcode::And => (update_id, resume_id),
+            _ => (resume_id, update_id),
+        };
+        let current_block = self.current_block(blocks)?;
+        current_block.add_to_bottom(LoweredStatement::ConditionalJump(
+            LoweredExpression::Term(b),
+            then_id,
+            else_id,
+        ))?;
+        current_block.add_successor(&then_id);
+        current_block.add_successor(&else_id);
+
+        let mut update = BasicBlock::empty(update_id);
+        update.add_to_bottom(LoweredStatement::Assignment(
+            result.clone(),
+            LoweredExpression::Term(LoweredTerm::Bool(!initial)),
+        ))?;
+        blocks.push(update);
+        self.jump_to(blocks, resume_id)?;
+
+        blocks.push(BasicBlock::empty(resume_id));
+
+        Ok(LoweredExpression::Term(LoweredTerm::Id(result)))
+    }
+
+    /// Computes the expression into a new temporary. Constants are used directly.
+    fn map_temporary(&mut self, expr: &Expr, blocks: &mut Vec<BasicBlock>) -> Result<LoweredTerm> {
+        if let Expr::Term(term) = expr {
+            if !matches!(**term, Term::Id(_)) {
+                return self.map_term(term);
+            }
+        }
+
+        let value = self.map_expr(expr, blocks)?;
+
+        // The result of `&&` and `||` is already a temporary.
+        if let LoweredExpression::Term(LoweredTerm::Id(variable)) = &value {
+            if variable.is_generated() {
+                return Ok(LoweredTerm::Id(variable.clone()));
+            }
+        }
+
+        let temporary = self.new_temporary();
+
+        self.current_block(blocks)?
+            .add_to_bottom(LoweredStatement::Definition(temporary.clone(), value))?;
+
+        Ok(LoweredTerm::Id(temporary))
+    }
+
+    fn map_term(&mut self, term: &Term) -> Result<LoweredTerm> {
+        Ok(match *term {
+            Term::Num(num) => LoweredTerm::Constant(check_num(num)?),
+            Term::Literal(ref literal) => LoweredTerm::Literal(literal.clone()),
+            Term::Bool(value) => LoweredTerm::Bool(value),
+            Term::Str(ref value) => LoweredTerm::Str(value.clone()),
+            Term::Nil => LoweredTerm::Nil,
+            Term::Id(ref id) => {
+                self.position = id.get_pos();
+                LoweredTerm::Id(Variable::new(id.clone(), false))
+            }
+        })
+    }
+}
+
+/// Collects the variables which are used as values instead of accessing
+/// their fields. The values of these variables can outlive the function,
+/// because they can be returned, passed to functions or stored in fields.
+fn collect_escaping(stmts: &[Statement], escaping: &mut HashSet<String>) {
+    for stmt in stmts {
+        match stmt {
+            Statement::Definition(_, expr) | Statement::Assign(_, expr) | Statement::Ret(expr) => {
+                collect_escaping_expr(expr, escaping)
+            }
+            Statement::AssignIndex(place, expr) => {
+                // The indexed variable itself does not escape.
+                for (index, _) in split_indices(place).1 {
+                    collect_escaping_expr(index, escaping);
+                }
+                collect_escaping_expr(expr, escaping);
+            }
+            Statement::Conditional(condition, statements) => {
+                collect_escaping_expr(condition, escaping);
+                collect_escaping(statements, escaping);
+            }
+            Statement::IfElse(condition, then_statements, else_statements) => {
+                collect_escaping_expr(condition, escaping);
+                collect_escaping(then_statements, escaping);
+                collect_escaping(else_statements, escaping);
+            }
+            Statement::Match(guards) => {
+                for guard in guards {
+                    if let Some(condition) = &guard.condition {
+                        collect_escaping_expr(condition, escaping);
+                    }
+                    collect_escaping(&guard.statements, escaping);
+                }
+            }
+            Statement::While(_, condition, statements) => {
+                collect_escaping_expr(condition, escaping);
+                collect_escaping(statements, escaping);
+            }
+            Statement::Loop(_, statements) => collect_escaping(statements, escaping),
+            Statement::For(_, _, start, end, statements) => {
+                collect_escaping_expr(start, escaping);
+                collect_escaping_expr(end, escaping);
+                collect_escaping(statements, escaping);
+            }
+            Statement::ForEach(_, _, value, statements) => {
+                collect_escaping_expr(value, escaping);
+                collect_escaping(statements, escaping);
+            }
+            Statement::Call(_, arguments) => {
+                for argument in arguments {
+                    collect_escaping_expr(argument, escaping);
+                }
+            }
+            Statement::Expression(expr) => collect_escaping_expr(expr, escaping),
+            Statement::MatchEnum(value, arms) => {
+                collect_escaping_expr(value, escaping);
+                for arm in arms {
+                    collect_escaping(&arm.statements, escaping);
+                }
+            }
+            Statement::RetVoid | Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+}
+
+/// Splits `a[i][j]` into `a` and the indices `i` and `j` with the positions of their brackets.
+fn split_indices(expr: &Expr) -> (&Expr, Vec<(&Expr, usize)>) {
+    let mut indices = Vec::new();
+    let mut value = expr;
+
+    while let Expr::Index(inner, index, pos) = value {
+        indices.push((&**index, *pos));
+        value = inner;
+    }
+    indices.reverse();
+
+    (value, indices)
+}
+
+/// Returns true when mapping the expression defines temporaries.
+fn creates_temporaries(expr: &Expr) -> bool {
+    match expr {
+        Expr::Binary(Opcode::And | Opcode::Or, _, _) => true,
+        Expr::Binary(_, a, b) | Expr::Index(a, b, _) => {
+            !matches!(**a, Expr::Term(_)) || !matches!(**b, Expr::Term(_))
+        }
+        Expr::Unary(_, a) | Expr::Cast(a, _) | Expr::ArrayRepeat(a, _) => {
+            !matches!(**a, Expr::Term(_))
+        }
+        Expr::Call(_, arguments) | Expr::MethodCall(_, _, arguments) => {
+            arguments.iter().any(creates_temporaries)
+        }
+        Expr::Array(elements) | Expr::Variant(_, _, elements) => {
+            elements.iter().any(|x| !matches!(x, Expr::Term(_)))
+        }
+        Expr::Term(_) | Expr::Struct(_) | Expr::Boxed(_) | Expr::Closure(_) => false,
+    }
+}
+
+/// Returns true when the expression calls a function, which could change variables.
+fn has_calls(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call(_, _) | Expr::MethodCall(_, _, _) => true,
+        Expr::Binary(_, a, b) | Expr::Index(a, b, _) => has_calls(a) || has_calls(b),
+        Expr::Unary(_, a) | Expr::Cast(a, _) | Expr::ArrayRepeat(a, _) => has_calls(a),
+        Expr::Array(elements) | Expr::Variant(_, _, elements) => elements.iter().any(has_calls),
+        Expr::Term(_) | Expr::Struct(_) | Expr::Boxed(_) | Expr::Closure(_) => false,
+    }
+}
+
+fn collect_escaping_expr(expr: &Expr, escaping: &mut HashSet<String>) {
+    let mut collect_term = |term: &Term| {
+        if let Term::Id(id) = term {
+            if !id.is_field_access() {
+                escaping.insert(id.get_name().clone());
+            }
+        }
+    };
+
+    match expr {
+        Expr::Term(term) => collect_term(term),
+        Expr::Binary(_, a, b) | Expr::Index(a, b, _) => {
+            collect_escaping_expr(a, escaping);
+            collect_escaping_expr(b, escaping);
+        }
+        Expr::Unary(_, a) | Expr::Cast(a, _) | Expr::ArrayRepeat(a, _) => {
+            collect_escaping_expr(a, escaping)
+        }
+        Expr::Call(_, arguments) | Expr::Array(arguments) | Expr::Variant(_, _, arguments) => {
+            for argument in arguments {
+                collect_escaping_expr(argument, escaping);
+            }
+        }
+        Expr::MethodCall(receiver, _, arguments) => {
+            collect_term(&Term::Id(receiver.clone()));
+            for argument in arguments {
+                collect_escaping_expr(argument, escaping);
+            }
+        }
+        // The captured values can outlive the function in the environment of the closure.
+        Expr::Closure(closure) => escaping.extend(get_free_names(closure)),
+        Expr::Struct(_) | Expr::Boxed(_) => {}
+    }
+}
+
+/// Returns the names, which the closure uses without defining them, in alphabetical order.
+fn get_free_names(closure: &Func) -> Vec<String> {
+    let mut used = HashSet::new();
+    let mut defined = closure
+        .pars
+        .iter()
+        .map(|par| par.get_name().clone())
+        .collect();
+    collect_names(&closure.statements, &mut used, &mut defined);
+
+    let mut free = used.difference(&defined).cloned().collect::<Vec<_>>();
+    free.sort();
+
+    free
+}
+
+/// Collects the names of the variables and functions, which the statements use,
+/// and the names of the variables, which they define.
+fn collect_names(stmts: &[Statement], used: &mut HashSet<String>, defined: &mut HashSet<String>) {
+    for stmt in stmts {
+        match stmt {
+            Statement::Definition(id, expr) => {
+                defined.insert(id.get_name().clone());
+                collect_names_expr(expr, used);
+            }
+            Statement::Assign(id, expr) => {
+                used.insert(id.get_name().clone());
+                collect_names_expr(expr, used);
+            }
+            Statement::AssignIndex(place, expr) => {
+                collect_names_expr(place, used);
+                collect_names_expr(expr, used);
+            }
+            Statement::Ret(expr) | Statement::Expression(expr) => collect_names_expr(expr, used),
+            Statement::Conditional(condition, statements)
+            | Statement::While(_, condition, statements) => {
+                collect_names_expr(condition, used);
+                collect_names(statements, used, defined);
+            }
+            Statement::IfElse(condition, then_statements, else_statements) => {
+                collect_names_expr(condition, used);
+                collect_names(then_statements, used, defined);
+                collect_names(else_statements, used, defined);
+            }
+            Statement::Match(guards) => {
+                for guard in guards {
+                    if let Some(condition) = &guard.condition {
+                        collect_names_expr(condition, used);
+                    }
+                    collect_names(&guard.statements, used, defined);
+                }
+            }
+            Statement::Loop(_, statements) => collect_names(statements, used, defined),
+            Statement::For(_, id, start, end, statements) => {
+                defined.insert(id.get_name().clone());
+                collect_names_expr(start, used);
+                collect_names_expr(end, used);
+                collect_names(statements, used, defined);
+            }
+            Statement::ForEach(_, id, value, statements) => {
+                defined.insert(id.get_name().clone());
+                collect_names_expr(value, used);
+                collect_names(statements, used, defined);
+            }
+            Statement::Call(id, arguments) => {
+                used.insert(id.get_name().clone());
+                for argument in arguments {
+                    collect_names_expr(argument, used);
+                }
+            }
+            Statement::MatchEnum(value, arms) => {
+                collect_names_expr(value, used);
+                for arm in arms {
+                    if let Pattern::Variant(_, _, bindings) = &arm.pattern {
+                        defined.extend(bindings.iter().map(|id| id.get_name().clone()));
+                    }
+                    collect_names(&arm.statements, used, defined);
+                }
+            }
+            Statement::RetVoid | Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+}
+
+fn collect_names_expr(expr: &Expr, used: &mut HashSet<String>) {
+    match expr {
+        Expr::Term(term) => {
+            if let Term::Id(id) = &**term {
+                used.insert(id.get_name().clone());
+            }
+        }
+        Expr::Binary(_, a, b) | Expr::Index(a, b, _) => {
+            collect_names_expr(a, used);
+            collect_names_expr(b, used);
+        }
+        Expr::Unary(_, a) | Expr::Cast(a, _) | Expr::ArrayRepeat(a, _) => {
+            collect_names_expr(a, used)
+        }
+        Expr::Call(id, arguments) | Expr::MethodCall(id, _, arguments) => {
+            used.insert(id.get_name().clone());
+            for argument in arguments {
+                collect_names_expr(argument, used);
+            }
+        }
+        Expr::Array(arguments) | Expr::Variant(_, _, arguments) => {
+            for argument in arguments {
+                collect_names_expr(argument, used);
+            }
+        }
+        Expr::Closure(closure) => used.extend(get_free_names(closure)),
+        Expr::Struct(_) | Expr::Boxed(_) => {}
+    }
+}
+
+/// Renames the variable `from` to `to` in the statements. Nested blocks, loops, arms
+/// and closures, which define their own variable `from`, keep it.
+fn rename_variable(stmts: &mut [Statement], from: &str, to: &str) {
+    let rename = |id: &mut Identifier| {
+        if id.get_name() == from {
+            id.rename(to);
+        }
+    };
+
+    for stmt in stmts {
+        match stmt {
+            Statement::Definition(id, expr) | Statement::Assign(id, expr) => {
+                rename(id);
+                rename_variable_expr(expr, from, to);
+            }
+            Statement::AssignIndex(place, expr) => {
+                rename_variable_expr(place, from, to);
+                rename_variable_expr(expr, from, to);
+            }
+            Statement::Ret(expr) | Statement::Expression(expr) => {
+                rename_variable_expr(expr, from, to)
+            }
+            Statement::Conditional(condition, statements) => {
+                rename_variable_expr(condition, from, to);
+                rename_block(statements, from, to);
+            }
+            Statement::While(_, condition, statements) => {
+                rename_variable_expr(condition, from, to);
+                rename_block(statements, from, to);
+            }
+            Statement::IfElse(condition, then_statements, else_statements) => {
+                rename_variable_expr(condition, from, to);
+                rename_block(then_statements, from, to);
+                rename_block(else_statements, from, to);
+            }
+            Statement::Match(guards) => {
+                for guard in guards {
+                    if let Some(condition) = &mut guard.condition {
+                        rename_variable_expr(condition, from, to);
+                    }
+                    rename_variable(&mut guard.statements, from, to);
+                }
+            }
+            Statement::Loop(_, statements) => rename_block(statements, from, to),
+            Statement::For(_, id, start, end, statements) => {
+                rename_variable_expr(start, from, to);
+                rename_variable_expr(end, from, to);
+                if id.get_name() != from {
+                    rename_block(statements, from, to);
+                }
+            }
+            Statement::ForEach(_, id, value, statements) => {
+                rename_variable_expr(value, from, to);
+                if id.get_name() != from {
+                    rename_block(statements, from, to);
+                }
+            }
+            Statement::Call(id, arguments) => {
+                rename(id);
+                for argument in arguments {
+                    rename_variable_expr(argument, from, to);
+                }
+            }
+            Statement::MatchEnum(value, arms) => {
+                rename_variable_expr(value, from, to);
+                for arm in arms {
+                    let shadows = match &arm.pattern {
+                        Pattern::Variant(_, _, bindings) => {
+                            bindings.iter().any(|id| id.get_name() == from)
+                        }
+                        Pattern::Wildcard => false,
+                    };
+                    if !shadows {
+                        rename_variable(&mut arm.statements, from, to);
+                    }
+                }
+            }
+            Statement::RetVoid | Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+}
+
+/// Renames the variable `from` to `to` in a nested block, until the block
+/// defines its own variable `from`.
+fn rename_block(stmts: &mut [Statement], from: &str, to: &str) {
+    let end = stmts
+        .iter()
+        .position(|stmt| matches!(stmt, Statement::Definition(id, _) if id.get_name() == from))
+        .unwrap_or(stmts.len());
+
+    if let Some(Statement::Definition(_, value)) = stmts.get_mut(end) {
+        rename_variable_expr(value, from, to);
+    }
+    rename_variable(&mut stmts[..end], from, to);
+}
+
+fn rename_variable_expr(expr: &mut Expr, from: &str, to: &str) {
+    match expr {
+        Expr::Term(term) => {
+            if let Term::Id(id) = &mut **term {
+                if id.get_name() == from {
+                    id.rename(to);
+                }
+            }
+        }
+        Expr::Binary(_, a, b) | Expr::Index(a, b, _) => {
+            rename_variable_expr(a, from, to);
+            rename_variable_expr(b, from, to);
+        }
+        Expr::Unary(_, a) | Expr::Cast(a, _) | Expr::ArrayRepeat(a, _) => {
+            rename_variable_expr(a, from, to)
+        }
+        Expr::Call(id, arguments) | Expr::MethodCall(id, _, arguments) => {
+            if id.get_name() == from {
+                id.rename(to);
+            }
+            for argument in arguments {
+                rename_variable_expr(argument, from, to);
+            }
+        }
+        Expr::Array(arguments) | Expr::Variant(_, _, arguments) => {
+            for argument in arguments {
+                rename_variable_expr(argument, from, to);
+            }
+        }
+        Expr::Closure(closure) => {
+            if closure.pars.iter().all(|par| par.get_name() != from) {
+                rename_variable(&mut closure.statements, from, to);
+            }
+        }
+        Expr::Struct(_) | Expr::Boxed(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Variant;
+
+    fn create_identifier() -> Identifier {
+        Identifier::new("test".to_string(), 0, 100, None)
+    }
+
+    #[test]
+    fn lower_assignment() {
+        let mut pass = LoweringPass::default();
+        let instruction = Statement::Assign(
+            create_identifier(),
+            Box::new(Expr::Term(Box::new(Term::Num(1)))),
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: vec![create_identifier()],
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        assert_eq!(result.get_entries().len(), 1);
+        assert_eq!(result.get_entries().first().unwrap().id.get_name(), "test");
+        assert_eq!(result.get_entries().first().unwrap().pars.len(), 1);
+        assert_eq!(
+            result.get_entries().first().unwrap().entry,
+            BasicBlockId::default()
+        );
+        assert_eq!(
+            result
+                .get_entries()
+                .first()
+                .unwrap()
+                .blocks
+                .first()
+                .unwrap()
+                .get_statements(),
+            vec![LoweredStatement::Assignment(
+                Variable::new(create_identifier(), false),
+                LoweredExpression::Term(LoweredTerm::Constant(1))
+            )]
+        );
+    }
+
+    #[test]
+    fn lower_conditional() {
+        let mut pass = LoweringPass::default();
+        let instruction = Statement::Conditional(
+            Box::new(Expr::Term(Box::new(Term::Num(1)))),
+            vec![Statement::RetVoid],
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            blocks[0].get_statements(),
+            vec![LoweredStatement::ConditionalJump(
+                LoweredExpression::Term(LoweredTerm::Constant(1)),
+                *blocks[1].get_id(),
+                *blocks[2].get_id()
+            )]
+        );
+        assert_eq!(
+            blocks[0].get_next(),
+            [*blocks[1].get_id(), *blocks[2].get_id()]
+        );
+        assert_eq!(blocks[1].get_statements(), vec![LoweredStatement::RetVoid]);
+        assert!(blocks[1].get_next().is_empty());
+    }
+
+    #[test]
+    fn lower_if_else() {
+        let mut pass = LoweringPass::default();
+        let instruction = Statement::IfElse(
+            Box::new(Expr::Term(Box::new(Term::Num(1)))),
+            vec![Statement::Assign(
+                create_identifier(),
+                Box::new(Expr::Term(Box::new(Term::Num(2)))),
+            )],
+            vec![Statement::RetVoid],
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        assert_eq!(blocks.len(), 4);
+        let (then_id, else_id, resume_id) = (
+            *blocks[1].get_id(),
+            *blocks[2].get_id(),
+            *blocks[3].get_id(),
+        );
+        assert_eq!(
+            blocks[0].get_statements(),
+            vec![LoweredStatement::ConditionalJump(
+                LoweredExpression::Term(LoweredTerm::Constant(1)),
+                then_id,
+                else_id
+            )]
+        );
+        assert_eq!(blocks[0].get_next(), [then_id, else_id]);
+        assert_eq!(blocks[1].get_next(), [resume_id]);
+        assert_eq!(
+            blocks[1].get_statements()[1],
+            LoweredStatement::UnconditionalJump(resume_id)
+        );
+        assert_eq!(blocks[2].get_statements(), vec![LoweredStatement::RetVoid]);
+        assert!(blocks[2].get_next().is_empty());
+    }
+
+    #[test]
+    fn lower_match() {
+        let mut pass = LoweringPass::default();
+        let instruction = Statement::Match(vec![
+            Guard {
+                condition: Some(Box::new(Expr::Term(Box::new(Term::Num(1))))),
+                statements: vec![Statement::Break(None)],
+            },
+            Guard {
+                condition: None,
+                statements: vec![Statement::RetVoid],
+            },
+        ]);
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        let ids = blocks.iter().map(|x| *x.get_id()).collect::<Vec<_>>();
+        // entry, first arm, second guard, second arm, resume
+        assert_eq!(blocks.len(), 5);
+        assert_eq!(blocks[0].get_next(), [ids[1], ids[2]]);
+        assert_eq!(
+            blocks[1].get_statements(),
+            vec![LoweredStatement::UnconditionalJump(ids[4])]
+        );
+        assert_eq!(blocks[2].get_next(), [ids[3]]);
+        assert_eq!(blocks[3].get_statements(), vec![LoweredStatement::RetVoid]);
+        assert!(blocks[4].get_statements().is_empty());
+    }
+
+    #[test]
+    fn lower_match_enum() {
+        let mut pass = LoweringPass::default();
+        let shape = Identifier::new("Shape".to_string(), 0, 100, None);
+        let circle = Identifier::new("Circle".to_string(), 0, 100, None);
+        let empty = Identifier::new("Empty".to_string(), 0, 100, None);
+        let radius = Identifier::new("r".to_string(), 0, 100, None);
+        let instruction = Statement::MatchEnum(
+            Box::new(Expr::Term(Box::new(Term::Id(create_identifier())))),
+            vec![
+                Arm {
+                    pattern: Pattern::Variant(
+                        Box::new(shape.clone()),
+                        Box::new(circle.clone()),
+                        vec![radius],
+                    ),
+                    statements: vec![Statement::RetVoid],
+                },
+                Arm {
+                    pattern: Pattern::Variant(
+                        Box::new(shape.clone()),
+                        Box::new(empty.clone()),
+                        Vec::new(),
+                    ),
+                    statements: vec![Statement::RetVoid],
+                },
+            ],
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: vec![Enum::new(
+                shape,
+                vec![
+                    Variant::new(circle, vec![DataType::Int]),
+                    Variant::new(empty, Vec::new()),
+                ],
+            )
+            .unwrap()],
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        let ids = blocks.iter().map(|x| *x.get_id()).collect::<Vec<_>>();
+        // entry, first arm, second arm, resume
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0].get_next(), [ids[1], ids[2]]);
+        // The last arm is the default, because every variant has an arm.
+        match blocks[0].get_statements().last() {
+            Some(LoweredStatement::Switch(_, _, cases, default)) => {
+                assert_eq!(cases, &vec![(0, ids[1])]);
+                assert_eq!(*default, ids[2]);
+            }
+            statement => panic!("Expected a switch, got {:?}", statement),
+        }
+        // The binding is renamed, so it is only visible in its arm.
+        match &blocks[1].get_statements()[0] {
+            LoweredStatement::Definition(variable, LoweredExpression::Payload(_, 0, 0)) => {
+                assert_eq!(
+                    variable.get_ident().get_name(),
+                    &format!("arm{}.r", ids[1].get_value())
+                );
+            }
+            stmt => panic!("Expected the definition of the binding, got {:?}", stmt),
+        }
+        assert_eq!(blocks[2].get_statements(), vec![LoweredStatement::RetVoid]);
+    }
+
+    #[test]
+    fn lower_while() {
+        let mut pass = LoweringPass::default();
+        let instruction = Statement::While(
+            None,
+            Box::new(Expr::Term(Box::new(Term::Num(1)))),
+            vec![Statement::Continue(None)],
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        let ids = blocks.iter().map(|x| *x.get_id()).collect::<Vec<_>>();
+        // entry, header, body, exit
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0].get_next(), [ids[1]]);
+        assert_eq!(blocks[1].get_next(), [ids[2], ids[3]]);
+        // The body jumps back to the header.
+        assert_eq!(
+            blocks[2].get_statements(),
+            vec![LoweredStatement::UnconditionalJump(ids[1])]
+        );
+        assert_eq!(blocks[2].get_next(), [ids[1]]);
+    }
+
+    #[test]
+    fn lower_for() {
+        let mut pass = LoweringPass::default();
+        let instruction = Statement::For(
+            None,
+            create_identifier(),
+            Box::new(Expr::Term(Box::new(Term::Num(0)))),
+            Box::new(Expr::Term(Box::new(Term::Num(10)))),
+            vec![Statement::Continue(None)],
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        let ids = blocks.iter().map(|x| *x.get_id()).collect::<Vec<_>>();
+        // entry, header, body, latch, exit
+        assert_eq!(blocks.len(), 5);
+        assert_eq!(blocks[0].get_next(), [ids[1]]);
+        assert_eq!(blocks[1].get_next(), [ids[4], ids[2]]);
+        // `continue` jumps to the latch, which jumps back to the header.
+        assert_eq!(blocks[2].get_next(), [ids[3]]);
+        assert_eq!(blocks[3].get_next(), [ids[1]]);
+        match &blocks[0].get_statements()[0] {
+            LoweredStatement::Definition(counter, _) => {
+                assert_eq!(counter.get_ident().get_name(), "for1.counter");
+                assert!(counter.is_generated());
+            }
+            stmt => panic!("Expected the definition of the counter, got {:?}", stmt),
+        }
+        // The variable is renamed, so it does not overwrite a variable outside of the loop.
+        match &blocks[2].get_statements()[0] {
+            LoweredStatement::Definition(variable, _) => {
+                assert_eq!(variable.get_ident().get_name(), "for1.test");
+                assert!(!variable.is_generated());
+            }
+            stmt => panic!("Expected the definition of the variable, got {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn lower_nested_expression() {
+        let mut pass = LoweringPass::default();
+        let term = |term| Box::new(Expr::Term(Box::new(term)));
+        // test = 1 + f() * test
+        let instruction = Statement::Assign(
+            create_identifier(),
+            Box::new(Expr::Binary(
+                Opcode::Add,
+                term(Term::Num(1)),
+                Box::new(Expr::Binary(
+                    Opcode::Mul,
+                    Box::new(Expr::Call(create_identifier(), Vec::new())),
+                    term(Term::Id(create_identifier())),
+                )),
+            )),
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let temporary =
+            |i: usize| Variable::new(Identifier::new(format!("tmp.{}", i), 0, 0, None), true);
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        assert_eq!(
+            blocks[0].get_statements(),
+            vec![
+                LoweredStatement::Definition(
+                    temporary(0),
+                    LoweredExpression::Call(create_identifier(), Vec::new())
+                ),
+                LoweredStatement::Definition(
+                    temporary(1),
+                    LoweredExpression::Binary(
+                        Opcode::Mul,
+                        LoweredTerm::Id(temporary(0)),
+                        LoweredTerm::Id(Variable::new(create_identifier(), false))
+                    )
+                ),
+                LoweredStatement::Assignment(
+                    Variable::new(create_identifier(), false),
+                    LoweredExpression::Binary(
+                        Opcode::Add,
+                        LoweredTerm::Constant(1),
+                        LoweredTerm::Id(temporary(1))
+                    )
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn lower_call_statement() {
+        let mut pass = LoweringPass::default();
+        let print = Identifier::new("print".to_string(), 0, 0, None);
+        // print("hi"[test]);
+        let instruction = Statement::Call(
+            print.clone(),
+            vec![Expr::Index(
+                Box::new(Expr::Term(Box::new(Term::Str("hi".to_string())))),
+                Box::new(Expr::Term(Box::new(Term::Id(create_identifier())))),
+                3,
+            )],
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        assert_eq!(
+            blocks[0].get_statements(),
+            vec![LoweredStatement::Expression(LoweredExpression::Call(
+                print,
+                vec![LoweredExpression::Index(
+                    LoweredTerm::Str("hi".to_string()),
+                    vec![Subscript {
+                        index: LoweredTerm::Id(Variable::new(create_identifier(), false)),
+                        pos: 3
+                    }]
+                )]
+            ))]
+        );
+    }
+
+    #[test]
+    fn lower_index_assignment() {
+        let mut pass = LoweringPass::default();
+        let term = |term| Box::new(Expr::Term(Box::new(term)));
+        // test[test][1] = f();
+        let instruction = Statement::AssignIndex(
+            Box::new(Expr::Index(
+                Box::new(Expr::Index(
+                    term(Term::Id(create_identifier())),
+                    term(Term::Id(create_identifier())),
+                    4,
+                )),
+                term(Term::Num(1)),
+                10,
+            )),
+            Box::new(Expr::Call(create_identifier(), Vec::new())),
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        // The variable index is read before the call, the constant is used directly.
+        let temporary =
+            |i: usize| Variable::new(Identifier::new(format!("tmp.{}", i), 0, 0, None), true);
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        assert_eq!(
+            blocks[0].get_statements(),
+            vec![
+                LoweredStatement::Definition(
+                    temporary(0),
+                    LoweredExpression::Term(LoweredTerm::Id(Variable::new(
+                        create_identifier(),
+                        false
+                    )))
+                ),
+                LoweredStatement::IndexAssignment(
+                    Variable::new(create_identifier(), false),
+                    vec![
+                        Subscript {
+                            index: LoweredTerm::Id(temporary(0)),
+                            pos: 4
+                        },
+                        Subscript {
+                            index: LoweredTerm::Constant(1),
+                            pos: 10
+                        }
+                    ],
+                    LoweredExpression::Call(create_identifier(), Vec::new())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn lower_short_circuit() {
+        let mut pass = LoweringPass::default();
+        let term = |term| Box::new(Expr::Term(Box::new(term)));
+        // test = false && test
+        let instruction = Statement::Assign(
+            create_identifier(),
+            Box::new(Expr::Binary(
+                Opcode::And,
+                term(Term::Bool(false)),
+                term(Term::Id(create_identifier())),
+            )),
+        );
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![instruction],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        let ids = blocks.iter().map(|x| *x.get_id()).collect::<Vec<_>>();
+        // entry, right operand, update, resume
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(
+            blocks[0].get_statements()[1],
+            LoweredStatement::ConditionalJump(
+                LoweredExpression::Term(LoweredTerm::Bool(false)),
+                ids[1],
+                ids[3]
+            )
+        );
+        assert_eq!(blocks[1].get_next(), [ids[2], ids[3]]);
+        assert!(matches!(
+            blocks[2].get_statements()[0],
+            LoweredStatement::Assignment(_, LoweredExpression::Term(LoweredTerm::Bool(true)))
+        ));
+        assert_eq!(blocks[2].get_next(), [ids[3]]);
+    }
+
+    #[test]
+    fn lower_escaping_struct() {
+        let mut pass = LoweringPass::default();
+        let local = Identifier::new("local".to_string(), 0, 5, None);
+        let escaping = Identifier::new("escaping".to_string(), 0, 8, None);
+        let point = Identifier::new("Point".to_string(), 0, 5, None);
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![
+                    Statement::Definition(local.clone(), Box::new(Expr::Struct(point.clone()))),
+                    Statement::Definition(escaping.clone(), Box::new(Expr::Struct(point.clone()))),
+                    Statement::Ret(Box::new(Expr::Term(Box::new(Term::Id(escaping.clone()))))),
+                ],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let blocks = &result.get_entries().first().unwrap().blocks;
+        assert_eq!(
+            blocks[0].get_statements()[..2],
+            [
+                LoweredStatement::Definition(
+                    Variable::new(local, false),
+                    LoweredExpression::Struct(point.clone(), Allocation::Stack)
+                ),
+                LoweredStatement::Definition(
+                    Variable::new(escaping, false),
+                    LoweredExpression::Struct(point, Allocation::Heap)
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn lower_closure() {
+        let mut pass = LoweringPass::default();
+        let id = |name: &str| Identifier::new(name.to_string(), 0, 0, None);
+        let term = |name: &str| Expr::Term(Box::new(Term::Id(id(name))));
+        let point = id("Point");
+
+        // The closure uses its parameter, its variable, a function and two captured variables.
+        let closure = Func::closure(
+            7,
+            vec![id("x").update_ty(DataType::Int)],
+            vec![
+                Statement::Definition(id("y"), Box::new(term("x"))),
+                Statement::Ret(Box::new(Expr::Binary(
+                    Opcode::Add,
+                    Box::new(term("y")),
+                    Box::new(Expr::Binary(
+                        Opcode::Add,
+                        Box::new(term("n")),
+                        Box::new(Expr::Call(id("double"), vec![term("p")])),
+                    )),
+                ))),
+            ],
+            None,
+        )
+        .unwrap();
+        let program = Program {
+            structs: Vec::new(),
+            enums: Vec::new(),
+            functions: vec![Func {
+                id: create_identifier(),
+                type_pars: Vec::new(),
+                pars: Vec::new(),
+                statements: vec![
+                    Statement::Definition(id("n"), Box::new(Expr::Term(Box::new(Term::Num(1))))),
+                    Statement::Definition(id("p"), Box::new(Expr::Struct(point.clone()))),
+                    Statement::Definition(id("f"), Box::new(Expr::Closure(Box::new(closure)))),
+                ],
+                ret_ty: None,
+            }],
+        };
+
+        // When
+        let result = pass.lower(&program).unwrap();
+
+        // Then
+        let lifted = Identifier::new("test.closure0".to_string(), 7, 7, None);
+        let captures = vec![
+            Identifier::new("n".to_string(), 7, 7, None),
+            Identifier::new("p".to_string(), 7, 7, None),
+        ];
+        let entries = result.get_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].blocks[0].get_statements()[1..],
+            [
+                // The struct escapes into the environment of the closure.
+                LoweredStatement::Definition(
+                    Variable::new(id("p"), false),
+                    LoweredExpression::Struct(point, Allocation::Heap)
+                ),
+                LoweredStatement::Definition(
+                    Variable::new(id("f"), false),
+                    LoweredExpression::Closure(
+                        lifted.clone(),
+                        captures
+                            .iter()
+                            .map(|capture| Variable::new(capture.clone(), false))
+                            .collect()
+                    )
+                )
+            ]
         );
+        assert_eq!(entries[1].id, lifted);
+        assert_eq!(entries[1].captures, Some(captures));
+        assert_eq!(entries[1].ret_ty, Some(DataType::Int));
     }
 }
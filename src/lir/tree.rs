@@ -1,7 +1,10 @@
-use crate::ast::{Identifier, Opcode};
+use crate::ast::{DataType, Enum, Identifier, Literal, Opcode, Struct};
+use crate::symbol_table::FunctionSignature;
 use anyhow::Result;
+use std::collections::HashSet;
+use std::fmt;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct BasicBlockId(usize);
 
 impl BasicBlockId {
@@ -25,19 +28,36 @@ impl BasicBlockId {
 pub struct LoweredProgram {
     name: String,
     entries: Vec<LoweredFunction>,
+    structs: Vec<Struct>,
+    enums: Vec<Enum>,
 }
 
 #[derive(Debug)]
 pub struct LoweredFunction {
     pub id: Identifier,
     pub pars: Vec<Identifier>,
+    /// Return type of the function. This is None when void.
+    pub ret_ty: Option<DataType>,
+    /// The variables, which a closure captures from the function that creates it.
+    /// This is None for named functions, which have no environment.
+    pub captures: Option<Vec<Identifier>>,
     pub entry: BasicBlockId,
     pub blocks: Vec<BasicBlock>,
 }
 
 impl LoweredProgram {
-    pub fn new(name: String, entries: Vec<LoweredFunction>) -> Self {
-        Self { name, entries }
+    pub fn new(
+        name: String,
+        entries: Vec<LoweredFunction>,
+        structs: Vec<Struct>,
+        enums: Vec<Enum>,
+    ) -> Self {
+        Self {
+            name,
+            entries,
+            structs,
+            enums,
+        }
     }
 
     pub(crate) fn get_name(&self) -> &String {
@@ -47,11 +67,42 @@ impl LoweredProgram {
     pub(crate) fn get_entries(&self) -> &[LoweredFunction] {
         &self.entries
     }
+
+    pub(crate) fn get_structs(&self) -> &[Struct] {
+        &self.structs
+    }
+
+    pub(crate) fn get_enums(&self) -> &[Enum] {
+        &self.enums
+    }
 }
 
 impl LoweredFunction {
-    pub fn new(id: Identifier, pars: Vec<Identifier>, entry: BasicBlockId, blocks: Vec<BasicBlock>) -> Self {
-        Self { id, pars, entry, blocks }
+    pub fn get_signature(&self) -> FunctionSignature {
+        let arguments_ty = self
+            .pars
+            .iter()
+            .map(|x| x.ty.clone().expect("Argument must have type"))
+            .collect();
+
+        FunctionSignature::new(arguments_ty, self.ret_ty.clone())
+    }
+
+    /// Returns the blocks, which can be reached from the entry of the function.
+    /// The other blocks follow a `return`, `break` or `continue`.
+    pub fn get_reachable_blocks(&self) -> HashSet<BasicBlockId> {
+        let mut reachable = HashSet::new();
+        let mut pending = vec![self.entry];
+
+        while let Some(id) = pending.pop() {
+            if reachable.insert(id) {
+                if let Some(block) = self.blocks.iter().find(|block| block.id == id) {
+                    pending.extend_from_slice(&block.next);
+                }
+            }
+        }
+
+        reachable
     }
 }
 
@@ -94,10 +145,23 @@ impl BasicBlock {
         self.next.push(*succ);
     }
 
-
+    /// Returns true when the block already ends with a statement
+    /// which leaves the block.
+    pub fn is_terminated(&self) -> bool {
+        matches!(
+            self.statements.last(),
+            Some(
+                LoweredStatement::Ret(_)
+                    | LoweredStatement::RetVoid
+                    | LoweredStatement::UnconditionalJump(_)
+                    | LoweredStatement::ConditionalJump(_, _, _)
+                    | LoweredStatement::Switch(_, _, _, _)
+            )
+        )
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Variable {
     ident: Identifier,
     /// Was this variable generated by the compiler
@@ -112,27 +176,100 @@ impl Variable {
     pub fn get_ident(&self) -> &Identifier {
         &self.ident
     }
+
+    pub fn is_generated(&self) -> bool {
+        self.generated
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum LoweredExpression {
     Term(LoweredTerm),
     Binary(Opcode, LoweredTerm, LoweredTerm),
-    Call(Identifier, Vec<Box<LoweredExpression>>),
+    Unary(Opcode, LoweredTerm),
+    Cast(LoweredTerm, DataType),
+    /// Reads the element at the indices, like `a[i][j]`.
+    Index(LoweredTerm, Vec<Subscript>),
+    Call(Identifier, Vec<LoweredExpression>),
+    /// Calls the method of the struct of the first argument, which is the receiver.
+    MethodCall(Identifier, Vec<LoweredExpression>),
+    /// Allocates a new instance of the struct.
+    Struct(Identifier, Allocation),
+    /// Creates an array with the elements.
+    Array(Vec<LoweredTerm>),
+    /// Creates an array, which repeats the value.
+    ArrayRepeat(LoweredTerm, usize),
+    /// Allocates the variant of the enum with the payload.
+    Variant(Identifier, Identifier, Vec<LoweredTerm>),
+    /// Reads the field of the payload of the enum value, which has the variant with the tag.
+    Payload(LoweredTerm, usize, usize),
+    /// Creates the closure of the lifted function, whose environment holds
+    /// the values of the captured variables.
+    Closure(Identifier, Vec<Variable>),
 }
 
+/// An index of an element access like `i` in `a[i]`.
 #[derive(Debug, PartialEq)]
+pub struct Subscript {
+    pub index: LoweredTerm,
+    /// Offset of the bracket in the program, which is reported when the index is out of bounds.
+    pub pos: usize,
+}
+
+/// Where a struct is allocated.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Allocation {
+    /// The struct lives until the function returns.
+    Stack,
+    /// The struct is allocated by the runtime and can outlive the function.
+    Heap,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum LoweredTerm {
     Constant(i64),
+    Literal(Literal),
+    Bool(bool),
+    Str(String),
     Id(Variable),
+    /// The empty list.
+    Nil,
+}
+
+impl fmt::Display for LoweredTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoweredTerm::Constant(num) => write!(f, "{}", num),
+            LoweredTerm::Literal(literal) => write!(f, "{}", literal),
+            LoweredTerm::Bool(value) => write!(f, "{}", value),
+            LoweredTerm::Str(value) => write!(f, "{:?}", value),
+            LoweredTerm::Id(variable) => write!(f, "{}", variable.get_ident()),
+            LoweredTerm::Nil => write!(f, "nil"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum LoweredStatement {
     Definition(Variable, LoweredExpression),
     Assignment(Variable, LoweredExpression),
-    Phi(Variable, Vec<Variable>),
-    UnconditionalJump(Box<BasicBlock>),
-    ConditionalJump(LoweredExpression, Box<BasicBlock>),
+    /// Writes the element of the variable or field at the indices.
+    IndexAssignment(Variable, Vec<Subscript>, LoweredExpression),
+    /// Jump to the given block.
+    UnconditionalJump(BasicBlockId),
+    /// Jump to the first block when the condition holds,
+    /// otherwise jump to the second block.
+    ConditionalJump(LoweredExpression, BasicBlockId, BasicBlockId),
+    /// Jumps to the block of the tag of the enum value, or to the default block
+    /// when no block has the tag.
+    Switch(
+        LoweredTerm,
+        Identifier,
+        Vec<(usize, BasicBlockId)>,
+        BasicBlockId,
+    ),
+    Ret(LoweredExpression),
     RetVoid,
+    /// Evaluates the expression and ignores its value, like a call of a void function.
+    Expression(LoweredExpression),
 }
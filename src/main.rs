@@ -4,26 +4,30 @@ extern crate core;
 
 use std::fs::File;
 use std::io::Read;
+use std::path::PathBuf;
 
 mod ast;
 mod codegen;
+mod lir;
 mod runner;
+mod source_map;
 mod symbol_table;
 mod utils;
-mod lir;
 
+use clap::{Parser, Subcommand};
 use runner::Runner;
-use clap::Parser;
 
-use llvm_sys::bit_writer::LLVMWriteBitcodeToFile;
 use llvm_sys::core::*;
 
 use log::info;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use llvm_sys::analysis::{LLVMVerifierFailureAction, LLVMVerifyModule};
+use llvm_sys::prelude::LLVMModuleRef;
 
-use crate::lir::lowering::LoweringPass;
+use crate::codegen::{Codegen, EmitKind, GcStrategy, OptLevel, Target};
+use crate::source_map::SourceMap;
+use crate::utils::LLVMString;
 
 #[macro_export]
 macro_rules! c_str {
@@ -35,16 +39,59 @@ macro_rules! c_str {
 #[cfg(test)]
 mod tests;
 
-lalrpop_mod!(pub grammar);
+lalrpop_mod!(#[allow(clippy::all)] pub grammar);
 
 /// A simple compiler
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    #[arg(short, long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(short, long, global = true)]
     print_lowering: bool,
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     files: Vec<String>,
+    /// Path of the output. The default depends on the kind of output.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Kind of the output
+    #[arg(long, value_enum, default_value_t = EmitKind::LlvmBc)]
+    emit: EmitKind,
+    /// Target triple. The default is the triple of the host.
+    #[arg(long, global = true)]
+    target: Option<String>,
+    /// Target cpu
+    #[arg(long, global = true)]
+    cpu: Option<String>,
+    /// Target features, e.g. `+avx2`
+    #[arg(long = "target-feature", value_delimiter = ',', global = true)]
+    target_features: Vec<String>,
+    /// Optimization level
+    #[arg(short = 'O', value_enum, default_value_t = OptLevel::O0, global = true)]
+    opt_level: OptLevel,
+    /// Print the LLVM IR after the optimization
+    #[arg(long, global = true)]
+    print_after_opt: bool,
+    /// Emit DWARF debug information
+    #[arg(short = 'g', global = true)]
+    debug_info: bool,
+    /// Garbage collector for the structs, lists, enums and closures on the heap
+    #[arg(long, value_enum, default_value_t = GcStrategy::None, global = true)]
+    gc: GcStrategy,
+    /// Do not check that indices are in bounds
+    #[arg(long, global = true)]
+    unchecked_indexing: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile the program in memory and run `main`.
+    /// The value of `main` is the exit code.
+    Run {
+        /// Arguments for the parameters of `main`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
 }
 
 fn main() {
@@ -53,23 +100,55 @@ fn main() {
 
     info!("=> Running compiler with {:?}", args);
 
+    let result = parse(&args.files).and_then(|(ast, source_map)| run(ast, source_map, &args));
+
+    match result {
+        Ok(exit_code) => std::process::exit(exit_code),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            err.chain()
+                .skip(1)
+                .for_each(|cause| eprintln!("because: {}", cause));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads all files and parses them as one program.
+/// The source map locates the positions of the program in the files.
+fn parse(files: &[String]) -> Result<(ast::Program, SourceMap)> {
+    let directory = std::env::current_dir().context("Cannot read the current directory")?;
+    let mut source_map = SourceMap::default();
     let mut content = String::new();
-    for file in args.files.into_iter() {
+    for file in files.iter() {
         let mut file_content = String::new();
-        let mut fs = File::open(file).expect("Cannot find file");
+        let mut fs = File::open(file).with_context(|| format!("Cannot open file {}", file))?;
 
         fs.read_to_string(&mut file_content)
-            .expect("Cannot read into string");
+            .with_context(|| format!("Cannot read file {}", file))?;
+        source_map.add_file(
+            file.clone(),
+            directory.to_string_lossy().to_string(),
+            &file_content,
+        );
         content.push_str(&file_content);
     }
 
-    let ast = grammar::ProgramParser::new().parse(&content).unwrap();
+    let ast = grammar::ProgramParser::new()
+        .parse(&content)
+        .map_err(|err| anyhow!("Cannot parse the program: {}", err))?;
 
     info!("=> Program parsed");
 
+    Ok((ast, source_map))
+}
+
+/// Compiles the program and returns the exit code of the compiler.
+fn run(ast: ast::Program, source_map: SourceMap, args: &Args) -> Result<i32> {
+    let mut runner = Runner;
+
     info!("=> Staring lowering");
-    let mut lowering_pass = LoweringPass::default();
-    let lowered = lowering_pass.lower(&ast).unwrap();
+    let lowered = runner.lowering(&ast)?;
 
     if args.print_lowering {
         use graphviz_rust::printer::{DotPrinter, PrinterContext};
@@ -80,48 +159,78 @@ fn main() {
 
     info!("=> Starting codegen");
 
-    let result = run(ast);
-
-    if let Err(err) = result {
-        eprintln!("ERROR: {}", err);
-        err.chain()
-            .skip(1)
-            .for_each(|cause| eprintln!("because: {}", cause));
-        std::process::exit(1);
+    if args.command.is_some() && args.target.is_some() {
+        bail!("Programs for other targets cannot be run");
     }
-}
 
-fn run(mut ast: ast::Program) -> Result<()> {
-    let mut runner = Runner;
+    let target = Target::new(
+        args.target.as_deref(),
+        args.cpu.as_deref(),
+        &args.target_features,
+    )?;
+
+    if args.command.is_none() && args.emit == EmitKind::Exe && !target.is_host() {
+        bail!(
+            "Executables cannot be linked for the target {}, because the system C toolchain links for the host",
+            target.get_triple().to_string_lossy()
+        );
+    }
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.emit.default_output());
 
     unsafe {
         let context = LLVMContextCreate();
-        let module = LLVMModuleCreateWithName(c_str!("main"));
+        let module = LLVMModuleCreateWithNameInContext(c_str!("main"), context);
         let builder = LLVMCreateBuilderInContext(context);
 
-        //let mut codegen = Codegen::new(context, module, builder);
+        let result = Codegen::new(context, module, builder, target).and_then(|mut codegen| {
+            codegen.set_gc(args.gc);
+            codegen.set_unchecked_indexing(args.unchecked_indexing);
+            codegen.set_source_map(source_map.clone());
+            if args.debug_info {
+                codegen.enable_debug_info(source_map, args.opt_level != OptLevel::O0)?;
+            }
 
-        /*
-        runner
-            .run_visitors(&mut passes, &mut ast)
-            .context("Running visitors failed")?;
-        */
-        LLVMVerifyModule(
-            module,
-            LLVMVerifierFailureAction::LLVMAbortProcessAction,
-            std::ptr::null_mut(),
-        );
+            runner.run_codegen(&mut codegen, &lowered)?;
+            verify_module(module)?;
+            runner.optimize(&mut codegen, args.opt_level)?;
 
-        info!("=> Starting writing file");
+            if args.print_after_opt {
+                println!("{}", LLVMString::new(LLVMPrintModuleToString(module)));
+            }
 
-        LLVMWriteBitcodeToFile(module, c_str!("main.bc"));
+            match &args.command {
+                Some(Command::Run { args }) => runner.run_main(&mut codegen, args),
+                None => runner.emit(&mut codegen, args.emit, &output).map(|_| 0),
+            }
+        });
 
         LLVMDisposeBuilder(builder);
         LLVMDisposeModule(module);
         LLVMContextDispose(context);
 
         info!("=> Finished");
+
+        result
+    }
+}
+
+fn verify_module(module: LLVMModuleRef) -> Result<()> {
+    unsafe {
+        let mut message = std::ptr::null_mut();
+        let failed = LLVMVerifyModule(
+            module,
+            LLVMVerifierFailureAction::LLVMReturnStatusAction,
+            &mut message,
+        );
+        let message = LLVMString::new(message);
+
+        if failed != 0 {
+            bail!("Module is not valid: {}", message);
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,33 +1,38 @@
 use crate::ast::*;
-use anyhow::{Result, Context};
+use crate::codegen::{Codegen, EmitKind, OptLevel};
+use crate::lir::tree::LoweredProgram;
+use anyhow::{Context, Result};
+use std::path::Path;
 
 pub struct Runner;
 
 impl Runner {
-    pub fn lowering(&mut self, program: &Program) -> Result<crate::lir::tree::LoweredProgram> {
+    /// Instantiates the generic structs and functions and lowers the program.
+    pub fn lowering(&mut self, program: &Program) -> Result<LoweredProgram> {
+        let program = crate::ast::monomorphization::MonomorphizationPass::default()
+            .run(program)
+            .context("Monomorphization failed")?;
+
         let mut pass = crate::lir::lowering::LoweringPass::default();
-        pass.lower(program).context("Lowering failed")
+        pass.lower(&program).context("Lowering failed")
     }
 
-    /*
-    pub fn run_visitors(&mut self, passes: &mut [Pass], program: &mut Program) -> Result<()> {
-        for pass in passes.iter_mut() {
-            pass.run(program)?;
-        }
+    pub fn run_codegen(&mut self, codegen: &mut Codegen, program: &LoweredProgram) -> Result<()> {
+        codegen.translate(program).context("Codegen failed")
+    }
 
-        Ok(())
+    pub fn optimize(&mut self, codegen: &mut Codegen, level: OptLevel) -> Result<()> {
+        codegen.optimize(level).context("Optimization failed")
     }
 
-    pub fn run_codegen(
-        &mut self,
-        visitor: &mut impl CodegenVisitorTrait,
-        codegen: &mut Codegen,
-        mut traversal: CodegenTraversal,
-        program: &mut Program,
-    ) -> Result<()> {
-        let _ = traversal.traverse(visitor, program, codegen)?;
+    /// Runs `main` and returns its value as exit code.
+    pub fn run_main(&mut self, codegen: &mut Codegen, arguments: &[String]) -> Result<i32> {
+        let value = codegen.run_main(arguments).context("Running main failed")?;
+
+        Ok(value as i32)
+    }
 
-        Ok(())
+    pub fn emit(&mut self, codegen: &mut Codegen, kind: EmitKind, output: &Path) -> Result<()> {
+        codegen.emit(kind, output).context("Emitting output failed")
     }
-    */
 }
@@ -0,0 +1,95 @@
+/// A file of the program.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// Name of the file, as it was given to the compiler.
+    pub name: String,
+    /// Directory which the name is relative to.
+    pub directory: String,
+    /// Offset of the file in the concatenated program.
+    start: usize,
+    /// Offsets of the lines in the concatenated program.
+    line_starts: Vec<usize>,
+}
+
+/// A position in a file. Lines and columns start with 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub file: usize,
+    pub line: u32,
+    pub column: u32,
+}
+
+/**
+ * Maps the byte offsets of the concatenated program to files, lines and columns.
+ */
+#[derive(Debug, Default, Clone)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    len: usize,
+}
+
+impl SourceMap {
+    /// Adds the content of the file, which is appended to the program.
+    pub fn add_file(&mut self, name: String, directory: String, content: &str) {
+        let start = self.len;
+        let line_starts = std::iter::once(start)
+            .chain(
+                content
+                    .match_indices('\n')
+                    .map(|(offset, _)| start + offset + 1),
+            )
+            .collect();
+
+        self.files.push(SourceFile {
+            name,
+            directory,
+            start,
+            line_starts,
+        });
+        self.len += content.len();
+    }
+
+    pub fn get_files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Returns the location of the offset in the concatenated program.
+    pub fn lookup(&self, offset: usize) -> Option<Location> {
+        let file = self.files.iter().rposition(|x| x.start <= offset)?;
+        let line_starts = &self.files[file].line_starts;
+        let line = match line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+
+        Some(Location {
+            file,
+            line: line as u32 + 1,
+            column: (offset - line_starts[line]) as u32 + 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_offsets() {
+        let mut source_map = SourceMap::default();
+        source_map.add_file("a.mill".to_string(), "/".to_string(), "fn a() {\n}\n");
+        source_map.add_file(
+            "b.mill".to_string(),
+            "/".to_string(),
+            "fn b() {\n  return;\n}",
+        );
+
+        let location = |file, line, column| Some(Location { file, line, column });
+
+        assert_eq!(source_map.lookup(0), location(0, 1, 1));
+        assert_eq!(source_map.lookup(3), location(0, 1, 4));
+        assert_eq!(source_map.lookup(9), location(0, 2, 1));
+        assert_eq!(source_map.lookup(11), location(1, 1, 1));
+        assert_eq!(source_map.lookup(22), location(1, 2, 3));
+    }
+}
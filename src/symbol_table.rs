@@ -37,7 +37,7 @@ impl BasicValue {
             unsafe {
                 let ptr = LLVMBuildLoad(builder, self.value, c_str!(id.get_name()));
 
-                return Ok(ptr);
+                Ok(ptr)
             }
         } else {
             bail!("Loaded value has to be a pointer");
@@ -55,7 +55,7 @@ impl BasicValue {
         unsafe {
             let res = LLVMBuildStore(builder, self.value, ptr.value);
 
-            return Ok(res);
+            Ok(res)
         }
     }
 }
@@ -63,13 +63,13 @@ impl BasicValue {
 impl BasicValueType {
     pub fn alloca(
         &self,
-        context: LLVMContextRef,
+        _context: LLVMContextRef,
         builder: LLVMBuilderRef,
         id: &Identifier,
     ) -> Result<BasicValue> {
         unsafe {
             let ty = match &self {
-                BasicValueType::Int(_) => LLVMIntTypeInContext(context, 64),
+                BasicValueType::Int(ty) => *ty,
                 BasicValueType::Pointer => bail!("Cannot alloca a pointer"),
                 BasicValueType::Identifier => bail!("Cannot alloca a identifier"),
                 BasicValueType::Function => bail!("Cannot alloca a function"),
@@ -87,7 +87,7 @@ impl BasicValueType {
 
     pub fn get_ty(&self) -> LLVMTypeRef {
         match &self {
-            BasicValueType::Int(x) => x.clone(),
+            BasicValueType::Int(x) => *x,
             BasicValueType::Pointer => panic!("Cannot get ty of a pointer"),
             BasicValueType::Identifier => panic!("Cannot get ty of an identifier"),
             BasicValueType::Function => panic!("Cannot get ty of a function"),
@@ -191,7 +191,7 @@ impl LLVMSymbolTable {
 
     pub fn get_last_sym(&self) -> Option<(Identifier, BasicValue)> {
         let curr = format!("{}", self.counter);
-        let x = self.symbols.get(&curr).clone();
+        let x = self.symbols.get(&curr);
 
         if let Some((id, value)) = x {
             Some((id.clone(), value.clone()))
@@ -239,7 +239,7 @@ impl FunctionSignature {
     /// Get the return type of the function.
     /// Currently only one return type is supported.
     pub fn get_ret_ty(&self) -> Option<&DataType> {
-        self.return_ty.get(0)
+        self.return_ty.first()
     }
 }
 
@@ -284,7 +284,7 @@ pub struct LLVMBlockTable {
     counter: usize,
 }
 
-impl<'a> LLVMBlockTable {
+impl LLVMBlockTable {
     pub fn lookup_symbol(&self, sym: &Key) -> bool {
         self.symbols.contains_key(sym)
     }
@@ -0,0 +1,81 @@
+use crate::grammar;
+use crate::source_map::SourceMap;
+use crate::tests::prelude::*;
+use insta::assert_snapshot;
+
+macro_rules! compile_debug {
+    ($input:expr) => {
+        use llvm_sys::core::*;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let input = $input;
+
+        let mut source_map = SourceMap::default();
+        source_map.add_file("main.mill".to_string(), "/src".to_string(), input);
+
+        // Setup LLVM
+        unsafe {
+            let context = LLVMContextCreate();
+            let module = LLVMModuleCreateWithNameInContext(c_str!("main"), context);
+            let builder = LLVMCreateBuilderInContext(context);
+            let target = Target::new(Some("x86_64-unknown-linux-gnu"), None, &[]).unwrap();
+            let mut codegen = Codegen::new(context, module, builder, target).unwrap();
+            let mut runner = Runner;
+
+            codegen.enable_debug_info(source_map, false).unwrap();
+
+            // Parse
+            let program = grammar::ProgramParser::new().parse(&input).unwrap();
+
+            // Lower and codegen
+            let lowered = runner.lowering(&program).expect("Lowering failed");
+            runner
+                .run_codegen(&mut codegen, &lowered)
+                .expect("Codegen failed");
+            crate::verify_module(module).expect("Module is not valid");
+
+            let ir = crate::utils::LLVMString::new(LLVMPrintModuleToString(module)).to_string();
+
+            // The debug info builder has to be disposed before the module.
+            drop(codegen);
+            LLVMDisposeBuilder(builder);
+            LLVMDisposeModule(module);
+            LLVMContextDispose(context);
+
+            assert_snapshot!(ir);
+        }
+    };
+}
+
+#[test]
+fn test_debug_function() {
+    compile_debug!(
+        "fn add(a : int, b : int) -> int {
+    let c : int = a + b;
+    return c;
+}
+
+fn main() -> int {
+    let x : int = add(1, 2);
+    if x == 3 {
+        x = add(x, 1);
+    }
+    return x;
+}"
+    );
+}
+
+#[test]
+fn test_debug_struct() {
+    compile_debug!(
+        "struct Node {
+    value : int,
+    next : Node
+}
+
+fn value(node : Node) -> int {
+    return node.value;
+}"
+    );
+}
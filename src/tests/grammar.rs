@@ -122,3 +122,455 @@ fn parse_struct_with_fields() {
         .parse("struct test123 { test: int }")
         .is_ok());
 }
+
+#[test]
+fn parse_comments() {
+    assert!(grammar::ProgramParser::new()
+        .parse("// expect-exit: 1\nfn main() -> int { // first\n return 1; // second\n}")
+        .is_ok());
+}
+
+#[test]
+fn parse_new_and_field_access() {
+    assert!(grammar::ProgramParser::new()
+        .parse("fn main() { let p = new Point; p.next.x = 1; return p.next.x; }")
+        .is_ok());
+    assert!(grammar::ProgramParser::new()
+        .parse("fn main() { let p = new p.x; }")
+        .is_err());
+}
+
+#[test]
+fn parse_box() {
+    assert!(grammar::ProgramParser::new()
+        .parse("fn main() { let p = box Point; return p; }")
+        .is_ok());
+}
+
+#[test]
+fn parse_else() {
+    assert!(grammar::StatementParser::new()
+        .parse("if a == 1 { a = 2; } else if a == 2 { a = 3; } else { a = 4; }")
+        .is_ok());
+    assert!(grammar::StatementParser::new()
+        .parse("if a == 1 { a = 2; } else a = 3;")
+        .is_err());
+}
+
+#[test]
+fn parse_match() {
+    assert!(grammar::StatementParser::new()
+        .parse("match a >= b -> return a; break; _ -> b = 1; return b; end;")
+        .is_ok());
+    assert!(grammar::StatementParser::new().parse("match end;").is_err());
+}
+
+#[test]
+fn parse_loops() {
+    assert!(grammar::StatementParser::new()
+        .parse("'outer: while a >= 1 { loop { break 'outer; continue; } }")
+        .is_ok());
+    assert!(grammar::StatementParser::new()
+        .parse("while a >= 1 { continue 'outer; }")
+        .is_ok());
+    assert!(grammar::StatementParser::new()
+        .parse("outer: loop { }")
+        .is_err());
+}
+
+#[test]
+fn parse_for() {
+    assert!(grammar::StatementParser::new()
+        .parse("'outer: for i in 0..n { for j in i..p.len { continue 'outer; } }")
+        .is_ok());
+    assert!(grammar::StatementParser::new()
+        .parse("for i in 0.. { }")
+        .is_err());
+}
+
+#[test]
+fn parse_precedence() {
+    use crate::ast::{Expr, Opcode};
+
+    // a - b - c * d == e is ((a - b) - (c * d)) == e
+    let expr = grammar::ExprParser::new()
+        .parse("a - b - c * d == e")
+        .unwrap();
+    match *expr {
+        Expr::Binary(Opcode::Cmp, ref left, _) => match **left {
+            Expr::Binary(Opcode::Sub, ref left, ref right) => {
+                assert!(matches!(**left, Expr::Binary(Opcode::Sub, _, _)));
+                assert!(matches!(**right, Expr::Binary(Opcode::Mul, _, _)));
+            }
+            ref expr => panic!("Expected a subtraction, got {:?}", expr),
+        },
+        ref expr => panic!("Expected a comparison, got {:?}", expr),
+    }
+
+    assert!(grammar::ExprParser::new()
+        .parse("f(x) + (g(1, y + 2) * 3)")
+        .is_ok());
+    assert!(grammar::ExprParser::new().parse("a + * b").is_err());
+}
+
+#[test]
+fn parse_operators() {
+    use crate::ast::{Expr, Opcode};
+
+    // !a || b && c < d | e is (!a) || (b && (c < (d | e)))
+    let expr = grammar::ExprParser::new()
+        .parse("!a || b && c < d | e")
+        .unwrap();
+    match *expr {
+        Expr::Binary(Opcode::Or, ref left, ref right) => {
+            assert!(matches!(**left, Expr::Unary(Opcode::Not, _)));
+            match **right {
+                Expr::Binary(Opcode::And, _, ref right) => match **right {
+                    Expr::Binary(Opcode::Lt, _, ref right) => {
+                        assert!(matches!(**right, Expr::Binary(Opcode::BitOr, _, _)))
+                    }
+                    ref expr => panic!("Expected a comparison, got {:?}", expr),
+                },
+                ref expr => panic!("Expected &&, got {:?}", expr),
+            }
+        }
+        ref expr => panic!("Expected ||, got {:?}", expr),
+    }
+
+    for expr in [
+        "a % b",
+        "a << 2 >> 1",
+        "a ^ b & c",
+        "a != -b",
+        "a <= b",
+        "a > b",
+    ] {
+        assert!(grammar::ExprParser::new().parse(expr).is_ok(), "{}", expr);
+    }
+}
+
+#[test]
+fn parse_bool() {
+    assert!(grammar::TermParser::new().parse("true").is_ok());
+    assert!(grammar::StatementParser::new()
+        .parse("let done : bool = !false && a < b;")
+        .is_ok());
+    assert!(grammar::IdParser::new().parse("true").is_err());
+}
+
+#[test]
+fn parse_sized_types() {
+    use crate::ast::{DataType, Expr, Literal, Term};
+
+    let expr = grammar::ExprParser::new().parse("-a as u8 * 2").unwrap();
+    match *expr {
+        Expr::Binary(_, ref left, _) => match **left {
+            Expr::Cast(ref value, DataType::U8) => {
+                assert!(matches!(**value, Expr::Unary(_, _)))
+            }
+            ref expr => panic!("Expected a cast, got {:?}", expr),
+        },
+        ref expr => panic!("Expected a multiplication, got {:?}", expr),
+    }
+
+    for (literal, expected) in [
+        ("255u8", Literal::Int(255, DataType::U8)),
+        ("1i64", Literal::Int(1, DataType::Int)),
+        ("2.5", Literal::Float(2.5, DataType::F64)),
+        ("3f32", Literal::Float(3.0, DataType::F32)),
+    ] {
+        match *grammar::TermParser::new().parse(literal).unwrap() {
+            Term::Literal(ref literal) => assert_eq!(*literal, expected),
+            ref term => panic!("Expected a literal, got {:?}", term),
+        }
+    }
+
+    assert!(grammar::TermParser::new().parse("128i8").is_err());
+    // The minus is folded into the literal, so the smallest value can be written.
+    match *grammar::ExprParser::new().parse("-128i8").unwrap() {
+        Expr::Term(ref term) => match **term {
+            Term::Literal(ref literal) => assert_eq!(*literal, Literal::Int(-128, DataType::I8)),
+            ref term => panic!("Expected a literal, got {:?}", term),
+        },
+        ref expr => panic!("Expected a literal, got {:?}", expr),
+    }
+    assert!(grammar::ExprParser::new().parse("-129i8").is_err());
+    assert!(grammar::ExprParser::new().parse("-(128i8)").is_err());
+    match *grammar::ExprParser::new()
+        .parse("-9223372036854775808")
+        .unwrap()
+    {
+        Expr::Term(ref term) => match **term {
+            Term::Num(value) => assert_eq!(value, i64::MIN as i128),
+            ref term => panic!("Expected a number, got {:?}", term),
+        },
+        ref expr => panic!("Expected a number, got {:?}", expr),
+    }
+    assert!(grammar::ExprParser::new()
+        .parse("9223372036854775808")
+        .is_err());
+    assert!(grammar::TermParser::new()
+        .parse("18446744073709551615")
+        .is_err());
+    assert!(grammar::StatementParser::new()
+        .parse("for i in 0..10 { }")
+        .is_ok());
+    assert!(grammar::ProgramParser::new()
+        .parse("struct test { a: i8, b: u16, c: f32, d: u64 }")
+        .is_ok());
+}
+
+#[test]
+fn parse_strings() {
+    use crate::ast::{Expr, Term};
+
+    match *grammar::TermParser::new().parse(r#""a\t\"b\"\\""#).unwrap() {
+        Term::Str(ref value) => assert_eq!(value, "a\t\"b\"\\"),
+        ref term => panic!("Expected a string, got {:?}", term),
+    }
+
+    // Indexing binds tighter than the prefix operators.
+    let expr = grammar::ExprParser::new()
+        .parse(r#"-"abc"[i + 1]"#)
+        .unwrap();
+    match *expr {
+        Expr::Unary(_, ref value) => assert!(matches!(**value, Expr::Index(_, _, _))),
+        ref expr => panic!("Expected a negation, got {:?}", expr),
+    }
+
+    assert!(grammar::StatementParser::new()
+        .parse(r#"print("// no comment");"#)
+        .is_ok());
+    assert!(grammar::TermParser::new().parse(r#""\x""#).is_err());
+    assert!(grammar::TermParser::new().parse(r#""open"#).is_err());
+}
+
+#[test]
+fn parse_arrays() {
+    use crate::ast::{DataType, Expr, Statement};
+
+    match grammar::StatementParser::new()
+        .parse("let a : [[int; 4]; 2] = [[0; 4], [1, 2, 3, 4]];")
+        .unwrap()
+    {
+        Statement::Definition(ref id, ref value) => {
+            let row = DataType::Array(Box::new(DataType::Int), 4);
+            assert_eq!(id.ty, Some(DataType::Array(Box::new(row), 2)));
+            match **value {
+                Expr::Array(ref rows) => {
+                    assert_eq!(rows.len(), 2);
+                    assert!(matches!(rows[0], Expr::ArrayRepeat(_, 4)));
+                }
+                ref expr => panic!("Expected an array, got {:?}", expr),
+            }
+        }
+        ref stmt => panic!("Expected a definition, got {:?}", stmt),
+    }
+
+    // Indexed places are assigned through their variable or field.
+    match grammar::StatementParser::new()
+        .parse("p.cells[i][j + 1] = 2;")
+        .unwrap()
+    {
+        Statement::AssignIndex(ref place, _) => {
+            assert_eq!(place.get_indexed_path().unwrap().to_string(), "p.cells")
+        }
+        ref stmt => panic!("Expected an indexed assignment, got {:?}", stmt),
+    }
+
+    assert!(grammar::StatementParser::new()
+        .parse("f()[0] = 1;")
+        .is_err());
+    assert!(grammar::StatementParser::new()
+        .parse("for x in [1, 2, 3] { }")
+        .is_ok());
+}
+
+#[test]
+fn parse_lists() {
+    use crate::ast::{DataType, Expr, Opcode, Statement, Term};
+
+    // The cons operator is right associative and binds weaker than `+`.
+    match grammar::StatementParser::new()
+        .parse("let l : [[int]] = x + 1 : head m : nil;")
+        .unwrap()
+    {
+        Statement::Definition(ref id, ref value) => {
+            let list = DataType::List(Box::new(DataType::Int));
+            assert_eq!(id.ty, Some(DataType::List(Box::new(list))));
+            match **value {
+                Expr::Binary(Opcode::Cons, ref first, ref rest) => {
+                    assert!(matches!(**first, Expr::Binary(Opcode::Add, _, _)));
+                    match **rest {
+                        Expr::Binary(Opcode::Cons, ref second, ref nil) => {
+                            assert!(matches!(**second, Expr::Unary(Opcode::Head, _)));
+                            assert!(
+                                matches!(**nil, Expr::Term(ref term) if matches!(**term, Term::Nil))
+                            );
+                        }
+                        ref expr => panic!("Expected a cons, got {:?}", expr),
+                    }
+                }
+                ref expr => panic!("Expected a cons, got {:?}", expr),
+            }
+        }
+        ref stmt => panic!("Expected a definition, got {:?}", stmt),
+    }
+
+    // Comparisons bind weaker than the cons operator.
+    assert!(matches!(
+        *grammar::ExprParser::new()
+            .parse("tail l == 1 : nil")
+            .unwrap(),
+        Expr::Binary(Opcode::Cmp, _, _)
+    ));
+}
+
+#[test]
+fn parse_enums() {
+    use crate::ast::{DataType, Expr, Pattern, Statement};
+
+    let program = grammar::ProgramParser::new()
+        .parse("enum Shape { Circle(int), Empty } struct Box { shape: Shape } fn f(s: Shape) {}")
+        .unwrap();
+    assert_eq!(program.enums[0].variants.len(), 2);
+    assert_eq!(program.enums[0].variants[0].fields, vec![DataType::Int]);
+    // Names of enums are resolved in fields and parameters.
+    assert!(matches!(program.structs[0].fields[0].ty, DataType::Enum(_)));
+    assert!(matches!(
+        program.functions[0].pars[0].ty,
+        Some(DataType::Enum(_))
+    ));
+
+    assert!(matches!(
+        *grammar::ExprParser::new().parse("Shape::Circle(1 + 2)").unwrap(),
+        Expr::Variant(_, _, ref fields) if fields.len() == 1
+    ));
+
+    match grammar::StatementParser::new()
+        .parse("match s { Shape::Circle(r) -> { return r; } _ -> { return 0; } }")
+        .unwrap()
+    {
+        Statement::MatchEnum(_, ref arms) => {
+            assert!(
+                matches!(arms[0].pattern, Pattern::Variant(_, _, ref bindings) if bindings.len() == 1)
+            );
+            assert!(matches!(arms[1].pattern, Pattern::Wildcard));
+        }
+        ref stmt => panic!("Expected a match, got {:?}", stmt),
+    }
+
+    assert!(grammar::ProgramParser::new()
+        .parse("enum Shape { Empty, Empty }")
+        .is_err());
+}
+
+#[test]
+fn parse_methods() {
+    use crate::ast::{DataType, Expr, Statement};
+
+    let program = grammar::ProgramParser::new()
+        .parse("struct Point { x: int } impl Point { fn len(self) -> int { return self.x; } fn origin() -> Point { return box Point; } }")
+        .unwrap();
+    let methods = &program.structs[0].methods;
+    assert_eq!(methods.len(), 2);
+    assert!(methods[0].has_self && !methods[1].has_self);
+    // The functions are qualified by the struct and `self` has its type.
+    assert_eq!(program.functions[0].id.get_name(), "Point::len");
+    assert!(matches!(
+        program.functions[0].pars[0].ty,
+        Some(DataType::Struct(_))
+    ));
+
+    match *grammar::ExprParser::new().parse("l.from.len(1)").unwrap() {
+        Expr::MethodCall(ref receiver, ref method, ref arguments) => {
+            assert_eq!(receiver.to_string(), "l.from");
+            assert_eq!(method.get_name(), "len");
+            assert_eq!(arguments.len(), 1);
+        }
+        ref expr => panic!("Expected a method call, got {:?}", expr),
+    }
+
+    assert!(matches!(
+        grammar::StatementParser::new()
+            .parse("p.scale(2);")
+            .unwrap(),
+        Statement::Expression(_)
+    ));
+
+    // Only `self` can be written without a type.
+    assert!(grammar::ProgramParser::new()
+        .parse("fn f(x) { return; }")
+        .is_err());
+    assert!(grammar::ProgramParser::new()
+        .parse("impl Point { fn len(self) -> int { return 0; } }")
+        .is_err());
+}
+
+#[test]
+fn parse_generics() {
+    use crate::ast::{DataType, Expr, Opcode, Statement};
+
+    let program = grammar::ProgramParser::new()
+        .parse("struct Pair<T> { a: T, b: T } fn first<T>(p: Pair<T>) -> T { return p.a; }")
+        .unwrap();
+    assert_eq!(program.structs[0].type_pars.len(), 1);
+    assert_eq!(program.functions[0].type_pars.len(), 1);
+
+    // `>>` closes both lists of type arguments.
+    match grammar::StatementParser::new()
+        .parse("let p: Pair<Pair<int>> = first::<Pair<int>>(q);")
+        .unwrap()
+    {
+        Statement::Definition(ref id, ref value) => {
+            match id.ty {
+                Some(DataType::Struct(ref name)) => {
+                    assert_eq!(name.to_string(), "Pair<Pair<int>>")
+                }
+                ref ty => panic!("Expected a struct, got {:?}", ty),
+            }
+            assert!(matches!(**value, Expr::Call(ref id, _) if id.type_args.len() == 1));
+        }
+        ref stmt => panic!("Expected a definition, got {:?}", stmt),
+    }
+
+    // The type of `as` has no type arguments, so `<` is a comparison.
+    assert!(matches!(
+        *grammar::ExprParser::new().parse("x as int < y").unwrap(),
+        Expr::Binary(Opcode::Lt, _, _)
+    ));
+}
+
+#[test]
+fn parse_closures() {
+    use crate::ast::{DataType, Expr, Statement};
+
+    match grammar::StatementParser::new()
+        .parse("let f: fn(int, bool) -> fn(int) = fn(x: int) -> int { return x + n; };")
+        .unwrap()
+    {
+        Statement::Definition(ref id, ref value) => {
+            match id.ty {
+                Some(ref ty @ DataType::Function(ref pars, Some(ref ret_ty))) => {
+                    assert_eq!(pars.len(), 2);
+                    assert!(matches!(**ret_ty, DataType::Function(_, None)));
+                    assert_eq!(ty.to_string(), "fn(int, bool) -> fn(int)");
+                }
+                ref ty => panic!("Expected a function type, got {:?}", ty),
+            }
+            match **value {
+                Expr::Closure(ref closure) => {
+                    assert_eq!(closure.pars.len(), 1);
+                    assert_eq!(closure.ret_ty, Some(DataType::Int));
+                }
+                ref expr => panic!("Expected a closure, got {:?}", expr),
+            }
+        }
+        ref stmt => panic!("Expected a definition, got {:?}", stmt),
+    }
+
+    // The parameters of closures need a type.
+    assert!(grammar::ExprParser::new()
+        .parse("fn(x) { return x; }")
+        .is_err());
+}
@@ -4,6 +4,9 @@ use insta::assert_snapshot;
 
 macro_rules! compile {
     ($input:expr) => {
+        compile!($input, OptLevel::O0);
+    };
+    ($input:expr, $opt_level:expr) => {
         use llvm_sys::core::*;
 
         let _ = env_logger::builder().is_test(true).try_init();
@@ -13,35 +16,31 @@ macro_rules! compile {
         // Setup LLVM
         unsafe {
             let context = LLVMContextCreate();
-            let module = LLVMModuleCreateWithName(c_str!("main"));
+            let module = LLVMModuleCreateWithNameInContext(c_str!("main"), context);
             let builder = LLVMCreateBuilderInContext(context);
-            let mut codegen = Codegen::new(context, module, builder);
+            let target = Target::new(Some("x86_64-unknown-linux-gnu"), None, &[]).unwrap();
+            let mut codegen = Codegen::new(context, module, builder, target).unwrap();
             let mut runner = Runner;
 
             // Parse
-            let mut program = grammar::ProgramParser::new().parse(&input).unwrap();
-
-            // Run the visitors
-            
-            /*
-            runner
-                .run_visitors(&mut passes, &mut program)
-                .expect("Running visitor failed");
+            let program = grammar::ProgramParser::new().parse(&input).unwrap();
 
-            // Codegen and get IR
+            // Lower and codegen
+            let lowered = runner.lowering(&program).expect("Lowering failed");
             runner
-                .run_codegen(
-                    &mut CodegenVisitor::new(),
-                    &mut codegen,
-                    CodegenTraversal,
-                    &mut program,
-                )
+                .run_codegen(&mut codegen, &lowered)
                 .expect("Codegen failed");
+            runner
+                .optimize(&mut codegen, $opt_level)
+                .expect("Optimization failed");
 
             let ir = crate::utils::LLVMString::new(LLVMPrintModuleToString(module)).to_string();
 
+            LLVMDisposeBuilder(builder);
+            LLVMDisposeModule(module);
+            LLVMContextDispose(context);
+
             assert_snapshot!(ir);
-            */
         }
     };
 }
@@ -81,4 +80,12 @@ fn test_call_when_names_in_order() {
 #[test]
 fn test_call_when_names_not_in_order() {
     compile!("fn main() -> int { let a : int = 1; return f(a); } fn f(a:int) -> int { return a; }");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_optimized_call() {
+    compile!(
+        "fn f(b: int) -> int { if b == 3 { return 10; } return b + 1; } fn main() -> int { return f(3); }",
+        OptLevel::O2
+    );
+}
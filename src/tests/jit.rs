@@ -0,0 +1,62 @@
+use crate::grammar;
+use crate::tests::prelude::*;
+
+macro_rules! run {
+    ($input:expr $(, $arg:expr)*) => {{
+        use llvm_sys::core::*;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let input = $input;
+        let args: Vec<String> = vec![$($arg.to_string()),*];
+
+        // Setup LLVM
+        unsafe {
+            let context = LLVMContextCreate();
+            let module = LLVMModuleCreateWithNameInContext(c_str!("main"), context);
+            let builder = LLVMCreateBuilderInContext(context);
+            let target = Target::new(None, None, &[]).unwrap();
+            let mut codegen = Codegen::new(context, module, builder, target).unwrap();
+            let mut runner = Runner;
+
+            // Parse
+            let program = grammar::ProgramParser::new().parse(&input).unwrap();
+
+            // Lower, codegen and run
+            let lowered = runner.lowering(&program).expect("Lowering failed");
+            runner
+                .run_codegen(&mut codegen, &lowered)
+                .expect("Codegen failed");
+            let result = runner.run_main(&mut codegen, &args);
+
+            LLVMDisposeBuilder(builder);
+            LLVMDisposeModule(module);
+            LLVMContextDispose(context);
+
+            result
+        }
+    }};
+}
+
+#[test]
+fn test_exit_code() {
+    let result = run!(
+        "fn f(b: int) -> int { if b == 3 { return 10; } return b + 1; } fn main() -> int { return f(3); }"
+    );
+
+    assert_eq!(result.unwrap(), 10);
+}
+
+#[test]
+fn test_void_main() {
+    assert_eq!(run!("fn main() { return; }").unwrap(), 0);
+}
+
+#[test]
+fn test_arguments() {
+    let program = "fn main(a: int, b: int) -> int { return a + b; }";
+
+    assert_eq!(run!(program, "20", "22").unwrap(), 42);
+    assert!(run!(program, "20").is_err());
+    assert!(run!(program, "20", "x").is_err());
+}
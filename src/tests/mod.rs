@@ -1,3 +1,6 @@
+mod debug_info;
 mod grammar;
 mod ir;
-mod prelude;
\ No newline at end of file
+mod jit;
+mod prelude;
+mod target;
\ No newline at end of file
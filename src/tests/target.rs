@@ -0,0 +1,80 @@
+use crate::grammar;
+use crate::tests::prelude::*;
+use insta::assert_snapshot;
+
+macro_rules! compile_asm {
+    ($input:expr, $triple:expr) => {
+        use llvm_sys::core::*;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let input = $input;
+        let output = std::env::temp_dir().join(format!("mill-test-{}.s", $triple));
+
+        // Setup LLVM
+        unsafe {
+            let context = LLVMContextCreate();
+            let module = LLVMModuleCreateWithNameInContext(c_str!("main"), context);
+            let builder = LLVMCreateBuilderInContext(context);
+            let target = Target::new(Some($triple), None, &[]).unwrap();
+            let mut codegen = Codegen::new(context, module, builder, target).unwrap();
+            let mut runner = Runner;
+
+            // Parse
+            let program = grammar::ProgramParser::new().parse(&input).unwrap();
+
+            // Lower, codegen and emit the assembly
+            let lowered = runner.lowering(&program).expect("Lowering failed");
+            runner
+                .run_codegen(&mut codegen, &lowered)
+                .expect("Codegen failed");
+            runner
+                .emit(&mut codegen, EmitKind::Asm, &output)
+                .expect("Emitting failed");
+
+            LLVMDisposeBuilder(builder);
+            LLVMDisposeModule(module);
+            LLVMContextDispose(context);
+        }
+
+        let asm = std::fs::read_to_string(&output).unwrap();
+        let _ = std::fs::remove_file(&output);
+
+        assert_snapshot!(asm);
+    };
+}
+
+const PROGRAM: &str =
+    "fn f(b: int) -> int { if b == 3 { return 10; } return b + 1; } fn main() -> int { return f(3); }";
+
+#[test]
+fn test_aarch64() {
+    compile_asm!(PROGRAM, "aarch64-unknown-linux-gnu");
+}
+
+#[test]
+fn test_riscv64() {
+    compile_asm!(PROGRAM, "riscv64-unknown-linux-gnu");
+}
+
+#[test]
+fn test_unknown_target() {
+    assert!(Target::new(Some("unknown-triple"), None, &[]).is_err());
+}
+
+#[test]
+fn test_unknown_cpu() {
+    let triple = Some("aarch64-unknown-linux-gnu");
+    assert!(Target::new(triple, Some("cortex-a72"), &[]).is_ok());
+    assert!(Target::new(triple, Some("notacpu"), &[]).is_err());
+    assert!(Target::new(triple, Some("skylake"), &[]).is_err());
+}
+
+#[test]
+fn test_unknown_target_feature() {
+    let triple = Some("aarch64-unknown-linux-gnu");
+    let features = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    assert!(Target::new(triple, None, &features(&["+neon", "-crc"])).is_ok());
+    assert!(Target::new(triple, None, &features(&["+notafeature"])).is_err());
+    assert!(Target::new(triple, None, &features(&["neon"])).is_err());
+}
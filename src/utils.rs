@@ -21,15 +21,10 @@ impl LLVMString {
 
     #[allow(dead_code)]
     pub(crate) fn create_from_str(string: &str) -> LLVMString {
-        debug_assert_eq!(string.as_bytes()[string.as_bytes().len() - 1], 0);
+        debug_assert_eq!(string.as_bytes()[string.len() - 1], 0);
 
         unsafe { LLVMString::new(LLVMCreateMessage(string.as_ptr() as *const _)) }
     }
-
-    #[allow(dead_code)]
-    pub fn to_string(&self) -> String {
-        (*self).to_string_lossy().into_owned()
-    }
 }
 
 impl Deref for LLVMString {
@@ -48,7 +43,7 @@ impl Debug for LLVMString {
 
 impl Display for LLVMString {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        write!(f, "{:?}", self.deref())
+        write!(f, "{}", self.to_string_lossy())
     }
 }
 
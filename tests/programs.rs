@@ -0,0 +1,296 @@
+//! Compiles and runs every program in `tests/programs`.
+//!
+//! The expectations of a program are given by comments at the top of the file:
+//!
+//! ```text
+//! // expect-ir
+//! // expect-exit: 10
+//! // expect-stdout: first line
+//! // expect-stdout: second line
+//! // expect-stderr: index 3 is out of bounds
+//! // expect-error: Symbol h is not defined
+//! // args: 20 22
+//! // flags: --gc=marksweep
+//! ```
+//!
+//! `expect-ir` compares the LLVM IR with the snapshot `programs__<name>.snap`.
+//! The snapshots are compared after every program has been checked.
+//! `expect-exit`, `expect-stdout` and `expect-stderr` run `main` with the given `args`,
+//! once with `mill run` and once as executable, which is linked with `--emit exe`.
+//! `expect-stderr` requires that stderr of the run contains the given text.
+//! `flags` are passed to every invocation of the compiler.
+//! `expect-error` requires that the compilation fails with a message
+//! containing the given text.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// The LLVM IR of a program, which is compared with its snapshot.
+struct Snapshot {
+    name: String,
+    ir: String,
+}
+
+#[derive(Debug, Default)]
+struct Expectations {
+    ir: bool,
+    exit_code: Option<i32>,
+    stdout: Option<String>,
+    stderr: Option<String>,
+    error: Option<String>,
+    args: Vec<String>,
+    flags: Vec<String>,
+}
+
+impl Expectations {
+    fn parse(content: &str) -> Result<Self, String> {
+        let mut expectations = Expectations::default();
+
+        for line in content.lines().map(str::trim) {
+            let header = match line.strip_prefix("//") {
+                Some(header) => header.trim(),
+                None if line.is_empty() => continue,
+                // The header ends with the first line of code.
+                None => break,
+            };
+
+            let (key, value) = match header.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (header, ""),
+            };
+
+            match key {
+                "expect-ir" => expectations.ir = true,
+                "expect-exit" => {
+                    expectations.exit_code = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid exit code {}", value))?,
+                    )
+                }
+                "expect-stdout" => {
+                    let stdout = expectations.stdout.get_or_insert_with(String::new);
+                    stdout.push_str(value);
+                    stdout.push('\n');
+                }
+                "expect-stderr" => expectations.stderr = Some(value.to_string()),
+                "expect-error" => expectations.error = Some(value.to_string()),
+                "args" => {
+                    expectations.args = value.split_whitespace().map(str::to_string).collect()
+                }
+                "flags" => {
+                    expectations.flags = value.split_whitespace().map(str::to_string).collect()
+                }
+                // Other comments are allowed in the header.
+                _ => {}
+            }
+        }
+
+        if expectations.error.is_some()
+            && (expectations.ir
+                || expectations.exit_code.is_some()
+                || expectations.stdout.is_some()
+                || expectations.stderr.is_some())
+        {
+            return Err("expect-error cannot be combined with other expectations".to_string());
+        }
+
+        if !expectations.ir
+            && expectations.exit_code.is_none()
+            && expectations.stdout.is_none()
+            && expectations.stderr.is_none()
+            && expectations.error.is_none()
+        {
+            return Err("Program has no expectations".to_string());
+        }
+
+        Ok(expectations)
+    }
+}
+
+fn mill(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mill"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Cannot run mill")
+}
+
+/// Checks the expectations of the program. Its IR is returned,
+/// when it has to be compared with the snapshot.
+fn check(path: &Path, directory: &Path) -> Result<Option<Snapshot>, String> {
+    let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let expectations = Expectations::parse(&content)?;
+    // Locations in the snapshots are relative to the crate, so they do not depend on the checkout.
+    let file = path
+        .strip_prefix(env!("CARGO_MANIFEST_DIR"))
+        .unwrap_or(path)
+        .to_str()
+        .unwrap();
+    let name = path.file_stem().unwrap().to_str().unwrap();
+
+    // The target is fixed, so the snapshots do not depend on the host.
+    let mut args = vec![
+        "-f",
+        file,
+        "--target",
+        "x86_64-unknown-linux-gnu",
+        "--emit",
+        "llvm-ir",
+        "-o",
+        "-",
+    ];
+    args.extend(expectations.flags.iter().map(String::as_str));
+
+    let compiled = mill(&args);
+    let stderr = String::from_utf8_lossy(&compiled.stderr);
+
+    if let Some(error) = expectations.error {
+        if compiled.status.success() {
+            return Err(format!(
+                "Compilation succeeded, but expected error `{}`",
+                error
+            ));
+        }
+
+        if !stderr.contains(&error) {
+            return Err(format!("Expected error `{}`, but got:\n{}", error, stderr));
+        }
+
+        return Ok(None);
+    }
+
+    if !compiled.status.success() {
+        return Err(format!("Compilation failed:\n{}", stderr));
+    }
+
+    if expectations.exit_code.is_some()
+        || expectations.stdout.is_some()
+        || expectations.stderr.is_some()
+    {
+        let mut args = vec!["run", "-f", file];
+        args.extend(expectations.flags.iter().map(String::as_str));
+        args.push("--");
+        args.extend(expectations.args.iter().map(String::as_str));
+
+        check_run(&expectations, mill(&args)).map_err(|err| format!("mill run: {}", err))?;
+
+        // The executable is linked for the host.
+        let executable = directory.join(name);
+        let mut args = vec!["-f", file, "--emit", "exe", "-o"];
+        args.push(executable.to_str().unwrap());
+        args.extend(expectations.flags.iter().map(String::as_str));
+
+        let linked = mill(&args);
+        if !linked.status.success() {
+            return Err(format!(
+                "Linking failed:\n{}",
+                String::from_utf8_lossy(&linked.stderr)
+            ));
+        }
+
+        let output = Command::new(&executable)
+            .args(&expectations.args)
+            .output()
+            .map_err(|err| format!("Cannot run {}: {}", executable.display(), err))?;
+        check_run(&expectations, output).map_err(|err| format!("executable: {}", err))?;
+    }
+
+    Ok(expectations.ir.then(|| Snapshot {
+        name: name.to_string(),
+        ir: String::from_utf8_lossy(&compiled.stdout).to_string(),
+    }))
+}
+
+/// Compares the exit code and the output of a run with the expectations.
+fn check_run(expectations: &Expectations, output: Output) -> Result<(), String> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    if let Some(exit_code) = expectations.exit_code {
+        if output.status.code() != Some(exit_code) {
+            return Err(format!(
+                "Expected exit code {}, but got {:?}:\n{}",
+                exit_code,
+                output.status.code(),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+
+    if let Some(expected) = &expectations.stdout {
+        if stdout != *expected {
+            return Err(format!(
+                "Expected stdout:\n{}\nbut got:\n{}",
+                expected, stdout
+            ));
+        }
+    }
+
+    if let Some(expected) = &expectations.stderr {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        if !stderr.contains(expected) {
+            return Err(format!(
+                "Expected stderr containing `{}`, but got:\n{}",
+                expected, stderr
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn programs() {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+
+    let mut paths: Vec<_> = fs::read_dir(&directory)
+        .expect("Cannot read tests/programs")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|x| x == "mill"))
+        .collect();
+    paths.sort();
+
+    assert!(!paths.is_empty(), "No programs found");
+
+    // The executables are linked into a private directory, which is removed afterwards.
+    let executables = tempfile::tempdir().expect("Cannot create a temporary directory");
+
+    let mut failures = Vec::new();
+    let mut snapshots = Vec::new();
+    for path in paths.iter() {
+        match check(path, executables.path()) {
+            Ok(snapshot) => snapshots.extend(snapshot),
+            Err(err) => failures.push(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+
+    for Snapshot { name, ir } in snapshots {
+        insta::assert_snapshot!(name, ir);
+    }
+}
+
+#[test]
+fn run_for_other_target() {
+    let output = mill(&[
+        "run",
+        "-f",
+        "tests/programs/arguments.mill",
+        "--target",
+        "aarch64-unknown-linux-gnu",
+        "--",
+        "20",
+        "22",
+    ]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains("Programs for other targets cannot be run"),
+        "{}",
+        stderr
+    );
+}